@@ -0,0 +1,477 @@
+use bevy::{app::AppExit, input::common_conditions::input_just_released, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::HeadlessRun;
+use crate::config::GameConfig;
+use crate::daily::{date_string, today};
+use crate::debug::GodMode;
+use crate::generator::GENERATOR_MAX_DIFFICULTY;
+use crate::level::{CurrentLevel, LevelData, LevelSet};
+use crate::modes::GameMode;
+use crate::stats::play_counts;
+use crate::storage::{load_ron, save_ron};
+use crate::{
+    run_gameplay_tick, spawn_start_overlay, stages_per_set, CollisionEvent, CoopSettings,
+    ExplosionEvent, GameState, Level, StartGameOverlay, Surface,
+};
+
+// Unlocks and progress, kept between runs of the game
+const ACHIEVEMENTS_PATH: &str = "achievements.ron";
+
+// Every achievement there is. New ones only need a line here, and a goal if none fits.
+pub const ACHIEVEMENTS: &[Achievement] = &[
+    Achievement {
+        id: "flawless_wall",
+        name: "Flawless",
+        description: "Clear a wall without losing a life",
+        goal: Goal::FlawlessWall,
+    },
+    Achievement {
+        id: "combo_10",
+        name: "Chain Reaction",
+        description: "Break 10 bricks before the ball comes back to a paddle",
+        goal: Goal::Combo(10),
+    },
+    Achievement {
+        id: "top_wall_50",
+        name: "Sky High",
+        description: "Hit the top wall 50 times",
+        goal: Goal::TopWallHits(50),
+    },
+    // There is no difficulty setting for a whole game. Generated boards are the only ones with
+    // a difficulty, so the hardest of them stands in.
+    Achievement {
+        id: "hard_wall",
+        name: "Hard Won",
+        description: "Clear a board generated on the hardest difficulty",
+        goal: Goal::HardWall,
+    },
+    // The daily challenge is the only game that can be finished, rather than played until the
+    // lives run out
+    Achievement {
+        id: "daily_finished",
+        name: "Daily Champion",
+        description: "Clear every wall of a daily challenge and beat its boss",
+        goal: Goal::FinishDaily,
+    },
+];
+
+// Toasts
+const TOAST_SECONDS: f32 = 3.0;
+const TOAST_FONT_SIZE: f32 = 22.0;
+const TOAST_TEXT_COLOR: Color = Color::rgb(1.0, 0.85, 0.3);
+const TOAST_TOP: f32 = 60.0;
+const TOAST_SPACING: f32 = 28.0;
+const TOAST_RIGHT_PADDING: Val = Val::Px(10.0);
+
+// Achievements screen
+const ACHIEVEMENTS_FONT_SIZE: f32 = 25.0;
+const ACHIEVEMENTS_TEXT_COLOR: Color = Color::rgb(1.0, 0.85, 0.3);
+const ACHIEVEMENTS_VERTICAL_PADDING: Val = Val::Px(200.0);
+const ACHIEVEMENTS_LEFT_PADDING: Val = Val::Px(250.0);
+const ACHIEVEMENTS_OVERLAY_SIZE: Vec3 = Vec3::new(1500.0, 1500.0, 0.0);
+const ACHIEVEMENTS_OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.95);
+
+pub struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        let record = AchievementRecord::load().unwrap_or_else(|error| {
+            error!("Could not load the achievements: {error}");
+            AchievementRecord::default()
+        });
+        app.insert_resource(record)
+            .init_resource::<PlayFacts>()
+            .insert_resource(AchievementBackends(vec![Box::new(LogBackend)]))
+            .add_event::<AchievementUnlocked>()
+            .add_systems(
+                Update,
+                track_play
+                    .after(run_gameplay_tick)
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(play_counts)
+                    // God mode can't lose, so it earns nothing
+                    .run_if(not(resource_exists::<GodMode>())),
+            )
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                (
+                    check_daily_finished
+                        .run_if(play_counts)
+                        .run_if(not(resource_exists::<GodMode>())),
+                    save_record.run_if(not(resource_exists::<HeadlessRun>())),
+                )
+                    .chain(),
+            )
+            // A new game starts from the start screen or the game over screen
+            .add_systems(OnExit(GameState::NewGame), reset_play_facts)
+            .add_systems(OnExit(GameState::GameOver), reset_play_facts)
+            .add_systems(Update, (report_unlocks.after(track_play), fade_toasts))
+            // Quitting keeps the progress made so far
+            .add_systems(
+                Last,
+                save_record
+                    .run_if(not(resource_exists::<HeadlessRun>()))
+                    .run_if(on_event::<AppExit>()),
+            )
+            .add_systems(
+                Update,
+                enter_achievements
+                    .run_if(in_state(GameState::NewGame))
+                    .run_if(input_just_released(KeyCode::A)),
+            )
+            .add_systems(
+                Update,
+                leave_achievements
+                    .run_if(in_state(GameState::Achievements))
+                    .run_if(input_just_released(KeyCode::Tab)),
+            );
+    }
+}
+
+pub struct Achievement {
+    // Stays the same across versions, for the save file and any backend
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub goal: Goal,
+}
+
+// What an achievement asks of the player
+#[derive(Clone, Copy)]
+pub enum Goal {
+    // Clear a wall without a life being lost on it
+    FlawlessWall,
+    // Break this many bricks in a row without the ball coming back to a paddle or the floor
+    Combo(u32),
+    // Hit the top wall this many times, over any number of games
+    TopWallHits(u32),
+    HardWall,
+    FinishDaily,
+}
+
+impl Goal {
+    fn met(self, facts: &PlayFacts, record: &AchievementRecord) -> bool {
+        match self {
+            Goal::FlawlessWall => facts.flawless_wall,
+            Goal::Combo(bricks) => facts.combo >= bricks,
+            Goal::TopWallHits(hits) => record.top_wall_hits >= hits,
+            Goal::HardWall => facts.hard_wall,
+            Goal::FinishDaily => facts.daily_finished,
+        }
+    }
+
+    // How far along a goal that builds up over games is
+    fn progress(self, record: &AchievementRecord) -> Option<(u32, u32)> {
+        match self {
+            Goal::TopWallHits(hits) => Some((record.top_wall_hits.min(hits), hits)),
+            Goal::FlawlessWall | Goal::Combo(_) | Goal::HardWall | Goal::FinishDaily => None,
+        }
+    }
+}
+
+// Sent when an achievement is unlocked
+#[derive(Event)]
+pub struct AchievementUnlocked(pub &'static Achievement);
+
+// Somewhere unlocks are reported besides the local file, such as a storefront's own
+// achievements. Every backend is told of each unlock as it happens.
+pub trait AchievementBackend: Send + Sync {
+    fn unlock(&mut self, achievement: &Achievement);
+}
+
+#[derive(Resource)]
+pub struct AchievementBackends(pub Vec<Box<dyn AchievementBackend>>);
+
+// Writes unlocks to the log, standing in until there is a platform to report them to
+struct LogBackend;
+
+impl AchievementBackend for LogBackend {
+    fn unlock(&mut self, achievement: &Achievement) {
+        info!("Achievement unlocked: {}", achievement.id);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Unlock {
+    id: String,
+    // Days since 1970-01-01
+    day: i64,
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AchievementRecord {
+    unlocked: Vec<Unlock>,
+    top_wall_hits: u32,
+}
+
+impl AchievementRecord {
+    fn load() -> Result<Self, String> {
+        load_ron(ACHIEVEMENTS_PATH)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        save_ron(ACHIEVEMENTS_PATH, self)
+    }
+
+    fn unlocked(&self, id: &str) -> Option<&Unlock> {
+        self.unlocked.iter().find(|unlock| unlock.id == id)
+    }
+}
+
+// What has happened so far in the game being played
+#[derive(Resource, Default)]
+struct PlayFacts {
+    // Bricks broken since the ball last came back
+    combo: u32,
+    // The wall being played, once a frame of it has been seen. A continued game starts partway
+    // through, and only the walls cleared in it count.
+    wall: Option<usize>,
+    life_lost_on_wall: bool,
+    // Whether the wall being played was generated on the hardest difficulty
+    wall_hard: bool,
+    flawless_wall: bool,
+    hard_wall: bool,
+    daily_finished: bool,
+}
+
+// Unlocks whatever the play so far has earned
+fn unlock_earned(
+    facts: &PlayFacts,
+    record: &mut AchievementRecord,
+    unlocked: &mut EventWriter<AchievementUnlocked>,
+) {
+    for achievement in ACHIEVEMENTS {
+        if record.unlocked(achievement.id).is_some() || !achievement.goal.met(facts, record) {
+            continue;
+        }
+        record.unlocked.push(Unlock {
+            id: achievement.id.to_string(),
+            day: today(),
+        });
+        unlocked.send(AchievementUnlocked(achievement));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn track_play(
+    mut collisions: EventReader<CollisionEvent>,
+    mut explosions: EventReader<ExplosionEvent>,
+    level: Res<Level>,
+    current_level: Res<CurrentLevel>,
+    config: Res<GameConfig>,
+    mut facts: ResMut<PlayFacts>,
+    mut record: ResMut<AchievementRecord>,
+    mut unlocked: EventWriter<AchievementUnlocked>,
+) {
+    let top_wall = config.layout.top_wall - config.layout.wall_size / 2.;
+    for collision in collisions.iter() {
+        match collision.surface {
+            Surface::Paddle => facts.combo = 0,
+            Surface::Floor => {
+                facts.combo = 0;
+                facts.life_lost_on_wall |= collision.life_lost;
+            }
+            Surface::Wall if collision.position.y >= top_wall => record.top_wall_hits += 1,
+            _ => {}
+        }
+    }
+    // The boss isn't a brick
+    facts.combo += explosions
+        .iter()
+        .filter(|explosion| explosion.row.is_some())
+        .count() as u32;
+    match facts.wall {
+        Some(wall) if level.index > wall => {
+            facts.flawless_wall |= !facts.life_lost_on_wall;
+            facts.hard_wall |= facts.wall_hard;
+            facts.wall = Some(level.index);
+            facts.life_lost_on_wall = false;
+            facts.wall_hard = hardest(&current_level.0);
+        }
+        Some(_) => {}
+        None => {
+            facts.wall = Some(level.index);
+            facts.wall_hard = hardest(&current_level.0);
+        }
+    }
+    unlock_earned(&facts, &mut record, &mut unlocked);
+}
+
+fn hardest(level: &LevelData) -> bool {
+    level.difficulty == Some(GENERATOR_MAX_DIFFICULTY)
+}
+
+fn check_daily_finished(
+    level: Res<Level>,
+    level_set: Option<Res<LevelSet>>,
+    mode: Res<GameMode>,
+    mut facts: ResMut<PlayFacts>,
+    mut record: ResMut<AchievementRecord>,
+    mut unlocked: EventWriter<AchievementUnlocked>,
+) {
+    let Some(level_set) = level_set else {
+        return;
+    };
+    facts.daily_finished = level_set.ends && level.index >= stages_per_set(Some(&level_set), *mode);
+    unlock_earned(&facts, &mut record, &mut unlocked);
+}
+
+fn reset_play_facts(mut facts: ResMut<PlayFacts>) {
+    *facts = PlayFacts::default();
+}
+
+fn save_record(record: Res<AchievementRecord>) {
+    if let Err(error) = record.save() {
+        error!("Could not write the achievements: {error}");
+    }
+}
+
+#[derive(Component)]
+struct Toast {
+    time_left: f32,
+}
+
+// Saves each unlock straight away, tells the backends, and puts up a toast for it
+fn report_unlocks(
+    mut commands: Commands,
+    mut unlocked: EventReader<AchievementUnlocked>,
+    record: Res<AchievementRecord>,
+    mut backends: ResMut<AchievementBackends>,
+    toast_query: Query<(), With<Toast>>,
+) {
+    if unlocked.is_empty() {
+        return;
+    }
+    save_record(record);
+    // New toasts go below any still showing
+    let shown = toast_query.iter().count();
+    for (slot, AchievementUnlocked(achievement)) in (shown..).zip(unlocked.iter()) {
+        for backend in &mut backends.0 {
+            backend.unlock(achievement);
+        }
+        commands.spawn((
+            TextBundle::from_section(
+                format!("Achievement unlocked: {}", achievement.name),
+                TextStyle {
+                    font_size: TOAST_FONT_SIZE,
+                    color: TOAST_TEXT_COLOR,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(TOAST_TOP + slot as f32 * TOAST_SPACING),
+                right: TOAST_RIGHT_PADDING,
+                ..default()
+            }),
+            Toast {
+                time_left: TOAST_SECONDS,
+            },
+        ));
+    }
+}
+
+fn fade_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toast_query: Query<(Entity, &mut Toast, &mut Text)>,
+) {
+    for (toast_ent, mut toast, mut text) in &mut toast_query {
+        toast.time_left -= time.delta_seconds();
+        if toast.time_left <= 0.0 {
+            commands.entity(toast_ent).despawn();
+            continue;
+        }
+        // Fades out over its last second
+        let alpha = toast.time_left.min(1.0);
+        text.sections[0].style.color = TOAST_TEXT_COLOR.with_a(alpha);
+    }
+}
+
+#[derive(Component)]
+struct AchievementsScreen;
+
+fn achievements_text(record: &AchievementRecord) -> String {
+    let mut text = format!(
+        "Achievements, {} of {} unlocked\n\n",
+        record.unlocked.len(),
+        ACHIEVEMENTS.len()
+    );
+    for achievement in ACHIEVEMENTS {
+        let status = match (
+            record.unlocked(achievement.id),
+            achievement.goal.progress(record),
+        ) {
+            (Some(unlock), _) => format!("unlocked {}", date_string(unlock.day)),
+            (None, Some((done, needed))) => format!("{done}/{needed}"),
+            (None, None) => "locked".to_string(),
+        };
+        text.push_str(&format!(
+            "{}: {} ({status})\n",
+            achievement.name, achievement.description
+        ));
+    }
+    text.push_str("\nTab: back");
+    text
+}
+
+// A on the start screen lists the achievements
+fn enter_achievements(
+    mut commands: Commands,
+    start_query: Query<Entity, With<StartGameOverlay>>,
+    record: Res<AchievementRecord>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for start_ent in &start_query {
+        commands.entity(start_ent).despawn();
+    }
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, 1.0),
+                scale: ACHIEVEMENTS_OVERLAY_SIZE,
+                ..default()
+            },
+            sprite: Sprite {
+                color: ACHIEVEMENTS_OVERLAY_COLOR,
+                ..default()
+            },
+            ..default()
+        },
+        AchievementsScreen,
+    ));
+    commands.spawn((
+        TextBundle::from_section(
+            achievements_text(&record),
+            TextStyle {
+                font_size: ACHIEVEMENTS_FONT_SIZE,
+                color: ACHIEVEMENTS_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: ACHIEVEMENTS_VERTICAL_PADDING,
+            left: ACHIEVEMENTS_LEFT_PADDING,
+            ..default()
+        }),
+        AchievementsScreen,
+    ));
+    next_state.set(GameState::Achievements);
+}
+
+// Tab goes back to the start screen
+fn leave_achievements(
+    mut commands: Commands,
+    coop: Res<CoopSettings>,
+    screen_query: Query<Entity, With<AchievementsScreen>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for screen_ent in &screen_query {
+        commands.entity(screen_ent).despawn();
+    }
+    spawn_start_overlay(&mut commands, &coop);
+    next_state.set(GameState::NewGame);
+}
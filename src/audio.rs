@@ -0,0 +1,394 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bevy::{
+    audio::{AddAudioSource, Decodable, Source, Volume},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+
+use crate::generator::Rng;
+use crate::settings::Settings;
+use crate::soundbank::{SoundBank, SoundBankHandle, SoundEvent};
+use crate::{GameState, Level};
+
+// Sound effects
+// Sounds past this many at once are dropped, so a burst of hits doesn't clip
+const MAX_VOICES: usize = 8;
+// Sounds are panned by placing them between two ears this far apart, a sound at either end of
+// the board coming from just short of one ear
+const EAR_GAP: f32 = 2.0;
+const PAN_WIDTH: f32 = 0.8;
+
+// Music
+// Seconds for one track to fade out while the next fades in
+const CROSSFADE_TIME: f32 = 1.5;
+// The level's track keeps playing under the pause menu, this much quieter
+const PAUSE_DUCK: f32 = 0.3;
+
+// Synth
+const SAMPLE_RATE: u32 = 22050;
+const NOTE_ATTACK: f32 = 0.01;
+// How quickly a note dies away after it is struck
+const NOTE_DECAY: f32 = 4.0;
+const LEAD_LEVEL: f32 = 0.12;
+const BASS_LEVEL: f32 = 0.25;
+
+// Tracks are written as MIDI note numbers, one per eighth note, with 0 for a rest. The bass
+// line repeats under the lead if it is shorter.
+const MENU_TUNE: Tune = Tune {
+    tempo: 84.0,
+    lead: &[
+        64, 67, 71, 67, 72, 71, 67, 64, 62, 65, 69, 65, 72, 69, 65, 62, 60, 64, 67, 64, 71, 67, 64,
+        60, 59, 62, 67, 62, 71, 67, 62, 0,
+    ],
+    bass: &[40, 0, 40, 0, 38, 0, 38, 0, 36, 0, 36, 0, 43, 0, 43, 0],
+};
+const LEVEL_TUNES: [Tune; 3] = [
+    Tune {
+        tempo: 138.0,
+        lead: &[
+            72, 0, 72, 74, 76, 0, 72, 0, 79, 0, 77, 76, 74, 0, 0, 0, 71, 0, 71, 72, 74, 0, 71, 0,
+            77, 0, 76, 74, 72, 0, 0, 0,
+        ],
+        bass: &[
+            48, 48, 55, 48, 48, 48, 55, 48, 43, 43, 50, 43, 43, 43, 50, 43,
+        ],
+    },
+    Tune {
+        tempo: 146.0,
+        lead: &[
+            69, 72, 76, 72, 69, 72, 76, 81, 79, 76, 72, 76, 79, 0, 77, 76, 74, 77, 81, 77, 74, 77,
+            81, 84, 83, 79, 76, 79, 83, 0, 81, 0,
+        ],
+        bass: &[45, 0, 45, 52, 45, 0, 45, 52, 50, 0, 50, 57, 52, 0, 52, 59],
+    },
+    Tune {
+        tempo: 128.0,
+        lead: &[
+            67, 0, 70, 0, 72, 0, 70, 67, 65, 0, 67, 0, 70, 0, 0, 0, 67, 0, 70, 0, 72, 0, 75, 74,
+            72, 0, 70, 0, 67, 0, 0, 0,
+        ],
+        bass: &[43, 43, 0, 43, 46, 46, 0, 46, 41, 41, 0, 41, 38, 38, 0, 38],
+    },
+];
+const GAME_OVER_TUNE: Tune = Tune {
+    tempo: 72.0,
+    lead: &[
+        69, 0, 68, 0, 67, 0, 66, 0, 65, 0, 0, 0, 64, 0, 0, 0, 62, 0, 64, 0, 65, 0, 64, 0, 57, 0, 0,
+        0, 0, 0, 0, 0,
+    ],
+    bass: &[45, 0, 0, 0, 41, 0, 0, 0, 38, 0, 0, 0, 40, 0, 0, 0],
+};
+
+pub struct MixerPlugin;
+
+impl Plugin for MixerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<MusicTrack>()
+            .add_systems(Startup, (load_music, start_voices))
+            .add_systems(PreUpdate, count_voices)
+            .add_systems(Update, (choose_music, fade_music).chain());
+    }
+}
+
+// Every sound goes out on a bus, turned up or down in the settings along with the master volume
+#[derive(Clone, Copy)]
+pub enum Bus {
+    Sfx,
+    Music,
+}
+
+pub fn bus_volume(settings: &Settings, bus: Bus) -> f32 {
+    let volume = match bus {
+        Bus::Sfx => settings.sfx_volume,
+        Bus::Music => settings.music_volume,
+    };
+    if settings.muted {
+        return 0.0;
+    }
+    settings.master_volume * volume
+}
+
+// A sound effect that is playing
+#[derive(Component)]
+struct Voice;
+
+#[derive(Resource)]
+struct Voices {
+    playing: usize,
+    // Sounds vary just to keep them from growing tiresome, so this isn't the game's seeded
+    // randomness
+    rng: Rng,
+}
+
+fn start_voices(mut commands: Commands) {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    commands.insert_resource(Voices {
+        playing: 0,
+        rng: Rng::new(seed),
+    });
+}
+
+// Sound effects still playing from earlier frames
+fn count_voices(mut voices: ResMut<Voices>, voice_query: Query<(), With<Voice>>) {
+    voices.playing = voice_query.iter().count();
+}
+
+#[derive(SystemParam)]
+pub struct SoundEffects<'w, 's> {
+    commands: Commands<'w, 's>,
+    settings: Res<'w, Settings>,
+    voices: ResMut<'w, Voices>,
+    bank: Res<'w, SoundBankHandle>,
+    banks: Res<'w, Assets<SoundBank>>,
+}
+
+impl SoundEffects<'_, '_> {
+    // Plays one of the sound bank's sounds for `event`, as loud as the effects bus allows.
+    // `speed` raises or lowers the pitch the bank picks, and `pan` runs from -1 for hard left to
+    // 1 for hard right.
+    pub fn play(&mut self, event: SoundEvent, speed: f32, pan: f32) {
+        let volume = bus_volume(&self.settings, Bus::Sfx);
+        if volume <= 0.0 || self.voices.playing >= MAX_VOICES {
+            return;
+        }
+        // Nothing plays until the bank has loaded
+        let Some(bank) = self.banks.get(&self.bank.0) else {
+            return;
+        };
+        let Some(chosen) = bank.choose(event, &mut self.voices.rng) else {
+            return;
+        };
+        self.voices.playing += 1;
+        let emitter = Vec3::new(pan.clamp(-1.0, 1.0) * PAN_WIDTH * EAR_GAP / 2.0, 0.0, 0.0);
+        self.commands.spawn((
+            SpatialAudioBundle {
+                source: chosen.sound,
+                // auto-despawn the entity when playback finishes
+                settings: PlaybackSettings::DESPAWN
+                    .with_volume(Volume::new_relative(volume * chosen.volume))
+                    .with_speed(speed * chosen.speed),
+                spatial: SpatialSettings::new(Transform::IDENTITY, EAR_GAP, emitter),
+            },
+            Voice,
+        ));
+    }
+}
+
+struct Tune {
+    // Beats per minute, each beat two notes long
+    tempo: f32,
+    lead: &'static [u8],
+    bass: &'static [u8],
+}
+
+// A tune played on a small synth, so the music needs no files
+#[derive(TypeUuid, TypePath)]
+#[uuid = "6c7b3f8e-2a4d-4f51-9d0e-8b1a7c3e5f20"]
+pub struct MusicTrack {
+    tune: &'static Tune,
+}
+
+impl Decodable for MusicTrack {
+    type DecoderItem = f32;
+    type Decoder = TuneDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        TuneDecoder {
+            tune: self.tune,
+            sample: 0,
+            samples_per_note: (SAMPLE_RATE as f32 * 30.0 / self.tune.tempo) as u32,
+        }
+    }
+}
+
+pub struct TuneDecoder {
+    tune: &'static Tune,
+    sample: u32,
+    samples_per_note: u32,
+}
+
+impl TuneDecoder {
+    fn len(&self) -> u32 {
+        self.tune.lead.len() as u32 * self.samples_per_note
+    }
+}
+
+fn frequency(note: u8) -> f32 {
+    440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+impl Iterator for TuneDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.sample >= self.len() {
+            return None;
+        }
+        let step = (self.sample / self.samples_per_note) as usize;
+        let time = self.sample as f32 / SAMPLE_RATE as f32;
+        let since_struck = (self.sample % self.samples_per_note) as f32 / SAMPLE_RATE as f32;
+        let envelope = (since_struck / NOTE_ATTACK).min(1.0) * (-since_struck * NOTE_DECAY).exp();
+        self.sample += 1;
+
+        let mut value = 0.0;
+        let lead = self.tune.lead[step];
+        if lead > 0 {
+            // A square wave, bright enough to carry the tune
+            let phase = (time * frequency(lead)).fract();
+            value += LEAD_LEVEL * if phase < 0.5 { 1.0 } else { -1.0 };
+        }
+        let bass = self.tune.bass[step % self.tune.bass.len()];
+        if bass > 0 {
+            // A triangle wave, softer underneath it
+            let phase = (time * frequency(bass)).fract();
+            value += BASS_LEVEL * (4.0 * (phase - 0.5).abs() - 1.0);
+        }
+        Some(value * envelope)
+    }
+}
+
+impl Source for TuneDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(Duration::from_secs_f32(
+            self.len() as f32 / SAMPLE_RATE as f32,
+        ))
+    }
+}
+
+#[derive(Resource)]
+struct Music {
+    menu: Handle<MusicTrack>,
+    levels: Vec<Handle<MusicTrack>>,
+    game_over: Handle<MusicTrack>,
+}
+
+// Which track fits what is going on
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MusicCue {
+    Menu,
+    // Levels take turns through the tracks
+    Level(usize),
+    GameOver,
+}
+
+impl Music {
+    fn track(&self, cue: MusicCue) -> Handle<MusicTrack> {
+        match cue {
+            MusicCue::Menu => self.menu.clone(),
+            MusicCue::Level(index) => self.levels[index % self.levels.len()].clone(),
+            MusicCue::GameOver => self.game_over.clone(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct MusicPlayer {
+    cue: MusicCue,
+    // How far faded in, from 0 to 1
+    fade: f32,
+    // Fading out to make way for another track
+    leaving: bool,
+}
+
+fn load_music(mut commands: Commands, mut tracks: ResMut<Assets<MusicTrack>>) {
+    commands.insert_resource(Music {
+        menu: tracks.add(MusicTrack { tune: &MENU_TUNE }),
+        levels: LEVEL_TUNES
+            .iter()
+            .map(|tune| tracks.add(MusicTrack { tune }))
+            .collect(),
+        game_over: tracks.add(MusicTrack {
+            tune: &GAME_OVER_TUNE,
+        }),
+    });
+}
+
+// Starts the track the game state calls for, and sends any other on its way out
+fn choose_music(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    level: Res<Level>,
+    music: Res<Music>,
+    mut player_query: Query<&mut MusicPlayer>,
+) {
+    let cue = match state.get() {
+        GameState::NewGame
+        | GameState::Lobby
+        | GameState::Editor
+        | GameState::Settings
+        | GameState::Stats
+        | GameState::Achievements => MusicCue::Menu,
+        GameState::InGame | GameState::Paused => MusicCue::Level(level.index),
+        GameState::GameOver => MusicCue::GameOver,
+    };
+    let mut playing = false;
+    for mut player in &mut player_query {
+        if player.leaving {
+            continue;
+        }
+        if player.cue == cue {
+            playing = true;
+        } else {
+            player.leaving = true;
+        }
+    }
+    if !playing {
+        commands.spawn((
+            AudioSourceBundle {
+                source: music.track(cue),
+                // Silent to begin with, then faded in
+                settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(0.0)),
+            },
+            MusicPlayer {
+                cue,
+                fade: 0.0,
+                leaving: false,
+            },
+        ));
+    }
+}
+
+fn fade_music(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    state: Res<State<GameState>>,
+    mut player_query: Query<(Entity, &mut MusicPlayer, Option<&AudioSink>)>,
+) {
+    let step = time.delta_seconds() / CROSSFADE_TIME;
+    let duck = match state.get() {
+        GameState::Paused => PAUSE_DUCK,
+        _ => 1.0,
+    };
+    for (entity, mut player, sink) in &mut player_query {
+        if player.leaving {
+            player.fade -= step;
+            if player.fade <= 0.0 {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        } else {
+            player.fade = (player.fade + step).min(1.0);
+        }
+        // The sink turns up once the track starts playing
+        if let Some(sink) = sink {
+            sink.set_volume(bus_volume(&settings, Bus::Music) * player.fade * duck);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{BoardLayout, GameConfig};
+use crate::level::BrickBehaviour;
+use crate::{
+    apply_velocity, check_for_collisions, Brick, Collider, GameplayTick, HitPoints, PaddleHitEvent,
+    Velocity,
+};
+
+// Brick behaviours
+// Descending bricks stop this far above the bottom wall, leaving the paddles room to play under
+// them
+const DESCEND_CLEARANCE: f32 = 200.;
+// Leeway when looking for bricks in the neighbouring cells of a regrowing one
+const NEIGHBOUR_TOLERANCE: f32 = 1.0;
+
+pub struct BehaviourPlugin;
+
+impl Plugin for BehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            GameplayTick,
+            (
+                turn_moving_bricks
+                    .after(apply_velocity)
+                    .before(check_for_collisions),
+                (descend_bricks, regrow_bricks).after(check_for_collisions),
+            ),
+        );
+    }
+}
+
+// The stretch of its row a moving brick travels back and forth along
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct BrickPath {
+    pub left: f32,
+    pub right: f32,
+}
+
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct Descends {
+    pub every: u32,
+    // Paddle hits since the brick last dropped
+    pub hits: u32,
+}
+
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct Regrows {
+    pub delay: f32,
+    pub hit_points: u32,
+}
+
+// A broken regrowing brick waiting to come back. It keeps its place but is hidden and can't
+// be hit.
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct Dormant {
+    pub time_left: f32,
+}
+
+// Gives a freshly spawned brick the components its behaviour needs
+pub fn insert_behaviour(
+    brick: &mut EntityCommands,
+    behaviour: BrickBehaviour,
+    translation: Vec3,
+    hit_points: Option<u32>,
+    layout: &BoardLayout,
+) {
+    match behaviour {
+        BrickBehaviour::Static => {}
+        BrickBehaviour::Moving { range, speed } => {
+            let reach = range as f32 * layout.cell_step().x;
+            brick.insert((
+                Velocity(Vec2::new(speed, 0.)),
+                BrickPath {
+                    left: translation.x - reach,
+                    right: translation.x + reach,
+                },
+            ));
+        }
+        BrickBehaviour::Descending { every } => {
+            brick.insert(Descends { every, hits: 0 });
+        }
+        // Bricks that can't break have nothing to grow back from
+        BrickBehaviour::Regrowing { delay } => {
+            if let Some(hit_points) = hit_points {
+                brick.insert(Regrows { delay, hit_points });
+            }
+        }
+    }
+}
+
+// Puts a broken regrowing brick to sleep instead of despawning it
+pub fn make_dormant(commands: &mut Commands, brick: Entity, regrows: &Regrows) {
+    commands
+        .entity(brick)
+        .remove::<(Collider, HitPoints)>()
+        .insert((
+            Dormant {
+                time_left: regrows.delay,
+            },
+            Visibility::Hidden,
+        ));
+}
+
+// Moving bricks turn around at either end of their path
+fn turn_moving_bricks(mut query: Query<(&mut Transform, &mut Velocity, &BrickPath), With<Brick>>) {
+    for (mut transform, mut velocity, path) in &mut query {
+        if transform.translation.x <= path.left {
+            transform.translation.x = path.left;
+            velocity.x = velocity.x.abs();
+        } else if transform.translation.x >= path.right {
+            transform.translation.x = path.right;
+            velocity.x = -velocity.x.abs();
+        }
+    }
+}
+
+fn descend_bricks(
+    mut paddle_hits: EventReader<PaddleHitEvent>,
+    mut query: Query<(&mut Transform, &mut Descends), With<Brick>>,
+    config: Res<GameConfig>,
+) {
+    let hits = paddle_hits.iter().count() as u32;
+    if hits == 0 {
+        return;
+    }
+    let step = config.layout.cell_step().y;
+    let floor = config.layout.bottom_wall + DESCEND_CLEARANCE;
+    for (mut transform, mut descends) in &mut query {
+        descends.hits += hits;
+        while descends.every > 0 && descends.hits >= descends.every {
+            descends.hits -= descends.every;
+            if transform.translation.y - step >= floor {
+                transform.translation.y -= step;
+            }
+        }
+    }
+}
+
+type LiveBrickQuery<'w, 's> =
+    Query<'w, 's, &'static Transform, (With<Brick>, With<Collider>, Without<Dormant>)>;
+
+// Dormant bricks come back once their delay is up, as long as a brick next to them is still
+// standing. A brick whose neighbours are all gone stays cleared.
+fn regrow_bricks(
+    mut commands: Commands,
+    time_step: Res<FixedTime>,
+    mut dormant_query: Query<(Entity, &Transform, &Regrows, &mut Dormant), With<Brick>>,
+    live_query: LiveBrickQuery,
+    config: Res<GameConfig>,
+) {
+    let step = config.layout.cell_step();
+    for (brick, transform, regrows, mut dormant) in &mut dormant_query {
+        dormant.time_left -= time_step.period.as_secs_f32();
+        if dormant.time_left > 0. {
+            continue;
+        }
+        let home = transform.translation;
+        let has_neighbour = live_query.iter().any(|other| {
+            let offset = (other.translation - home).truncate().abs();
+            let beside =
+                (offset.x - step.x).abs() <= NEIGHBOUR_TOLERANCE && offset.y <= NEIGHBOUR_TOLERANCE;
+            let above_or_below =
+                (offset.y - step.y).abs() <= NEIGHBOUR_TOLERANCE && offset.x <= NEIGHBOUR_TOLERANCE;
+            beside || above_or_below
+        });
+        if has_neighbour {
+            commands.entity(brick).remove::<Dormant>().insert((
+                Collider,
+                HitPoints(regrows.hit_points),
+                Visibility::Inherited,
+            ));
+        } else {
+            commands.entity(brick).despawn();
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{BoardLayout, GameConfig};
+use crate::debug::GodMode;
+use crate::level::{CurrentLevel, LevelSet};
+use crate::{
+    apply_velocity, check_for_collisions, leave_floor_to_partner, lives_pool_for_landing,
+    refit_floor_bounds, spawn_bricks, start_next_set, Brick, Collider, CollisionEvent,
+    CoopSettings, ExplosionEvent, GameState, GameplayTick, Level, Lives, Paddle, PaddleBounds,
+    Player, Scoreboard, Surface, Velocity,
+};
+
+// Boss
+// Walls before each boss in a classic game played without a level set
+pub const BOSS_EVERY: usize = 3;
+const BOSS_BASE_HEALTH: u32 = 20;
+const BOSS_HEALTH_PER_ENCOUNTER: u32 = 10;
+// Score for beating a boss, times how many bosses have been met
+pub const BOSS_BONUS: usize = 50;
+// The boss comes in this high over the middle of the floor
+const BOSS_HEIGHT: f32 = 200.0;
+// How far either side of the middle the boss travels
+const BOSS_TRAVEL: f32 = 220.0;
+const BOSS_ARM_COLOR: Color = Color::rgb(0.55, 0.2, 0.6);
+// Offset from the core, size and the damage a ball hit does, for each part. The core comes
+// first.
+const BOSS_PARTS: [(Vec2, Vec2, u32); 3] = [
+    (Vec2::new(0.0, 0.0), Vec2::new(160.0, 50.0), 2),
+    (Vec2::new(-125.0, -10.0), Vec2::new(80.0, 30.0), 1),
+    (Vec2::new(125.0, -10.0), Vec2::new(80.0, 30.0), 1),
+];
+
+// Health share at or below which each phase starts, and how it fights
+const BOSS_PHASES: [BossPhase; 3] = [
+    BossPhase {
+        below: 1.0,
+        speed: 80.0,
+        reload: 1.6,
+        shots: 1,
+        color: Color::rgb(0.8, 0.2, 0.3),
+    },
+    BossPhase {
+        below: 0.66,
+        speed: 130.0,
+        reload: 1.3,
+        shots: 3,
+        color: Color::rgb(0.9, 0.45, 0.1),
+    },
+    BossPhase {
+        below: 0.33,
+        speed: 180.0,
+        reload: 1.0,
+        shots: 5,
+        color: Color::rgb(1.0, 0.1, 0.1),
+    },
+];
+
+// Projectiles
+const PROJECTILE_SIZE: Vec3 = Vec3::new(12.0, 12.0, 0.0);
+const PROJECTILE_SPEED: f32 = 260.0;
+// Sideways speed between neighbouring shots of a spread
+const PROJECTILE_SPREAD: f32 = 70.0;
+const PROJECTILE_COLOR: Color = Color::rgb(1.0, 0.9, 0.2);
+// Each hit narrows the paddle by this factor, and once it is down to the smallest width the
+// next hit costs a life
+const PADDLE_SHRINK: f32 = 0.8;
+// The smallest a paddle gets, as a share of its full width
+const PADDLE_MIN_SHARE: f32 = 0.6;
+
+// Health bar
+const HEALTH_BAR_TOP: Val = Val::Px(45.0);
+const HEALTH_BAR_LEFT: Val = Val::Px(560.0);
+const HEALTH_BAR_WIDTH: Val = Val::Px(300.0);
+const HEALTH_BAR_HEIGHT: Val = Val::Px(16.0);
+const HEALTH_BAR_BACKGROUND: Color = Color::rgb(0.25, 0.05, 0.05);
+const HEALTH_BAR_COLOR: Color = Color::rgb(0.9, 0.1, 0.1);
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BossHitEvent>()
+            .add_systems(Startup, spawn_health_bar)
+            .add_systems(Update, update_health_bar)
+            .add_systems(
+                GameplayTick,
+                (
+                    damage_boss,
+                    steer_boss,
+                    fire_projectiles,
+                    hit_paddles_with_projectiles,
+                )
+                    .chain()
+                    .after(check_for_collisions)
+                    .after(apply_velocity),
+            );
+    }
+}
+
+struct BossPhase {
+    below: f32,
+    speed: f32,
+    reload: f32,
+    shots: u32,
+    color: Color,
+}
+
+// Shared state of a boss, kept on its core
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct Boss {
+    // How many bosses have been met this game, this one included
+    pub encounter: u32,
+    pub health: u32,
+    pub max_health: u32,
+    pub phase: usize,
+    // Seconds until the next volley
+    pub reload: f32,
+}
+
+// One collider of a boss, linked to the core by where it sits relative to it
+#[derive(Component, Clone, Copy)]
+pub struct BossPart {
+    pub offset: Vec2,
+    pub damage: u32,
+}
+
+#[derive(Component)]
+pub struct BossProjectile;
+
+// Sent when the ball hits a part of the boss
+#[derive(Event)]
+pub struct BossHitEvent {
+    pub damage: u32,
+    pub player: Option<usize>,
+}
+
+#[derive(Component)]
+struct HealthBar;
+
+#[derive(Component)]
+struct HealthBarFill;
+
+// Boss parts are bricks as far as clearing the board goes, so anything that resets the bricks
+// takes the boss with it
+fn part_bundle(index: usize, core: Vec3, velocity: Vec2, color: Color) -> impl Bundle {
+    let (offset, size, damage) = BOSS_PARTS[index];
+    (
+        SpriteBundle {
+            transform: Transform {
+                translation: core + offset.extend(0.0),
+                scale: size.extend(0.0),
+                ..default()
+            },
+            sprite: Sprite { color, ..default() },
+            ..default()
+        },
+        BossPart { offset, damage },
+        Velocity(velocity),
+        Brick,
+        Collider,
+    )
+}
+
+fn part_color(index: usize, boss: &Boss) -> Color {
+    match index {
+        0 => BOSS_PHASES[boss.phase].color,
+        _ => BOSS_ARM_COLOR,
+    }
+}
+
+pub fn new_boss(encounter: u32) -> Boss {
+    let max_health = BOSS_BASE_HEALTH + (BOSS_HEALTH_PER_ENCOUNTER * (encounter - 1));
+    Boss {
+        encounter,
+        health: max_health,
+        max_health,
+        phase: 0,
+        reload: BOSS_PHASES[0].reload,
+    }
+}
+
+fn boss_start(layout: &BoardLayout) -> Vec3 {
+    Vec3::new(layout.floor_middle(), BOSS_HEIGHT, 0.0)
+}
+
+pub fn spawn_boss(commands: &mut Commands, encounter: u32, layout: &BoardLayout) {
+    let boss = new_boss(encounter);
+    let velocity = Vec2::new(BOSS_PHASES[0].speed, 0.0);
+    for index in 0..BOSS_PARTS.len() {
+        let mut part = commands.spawn(part_bundle(
+            index,
+            boss_start(layout),
+            velocity,
+            part_color(index, &boss),
+        ));
+        if index == 0 {
+            part.insert(boss);
+        }
+    }
+}
+
+// Rebuilds a boss from a snapshot
+pub fn restore_boss(world: &mut World, boss: Boss, core: Vec3, velocity: Vec2) {
+    for index in 0..BOSS_PARTS.len() {
+        let mut part = world.spawn(part_bundle(index, core, velocity, part_color(index, &boss)));
+        if index == 0 {
+            part.insert(boss);
+        }
+    }
+}
+
+pub fn projectile_bundle(translation: Vec3, velocity: Vec2) -> impl Bundle {
+    (
+        SpriteBundle {
+            transform: Transform {
+                translation,
+                scale: PROJECTILE_SIZE,
+                ..default()
+            },
+            sprite: Sprite {
+                color: PROJECTILE_COLOR,
+                ..default()
+            },
+            ..default()
+        },
+        BossProjectile,
+        Velocity(velocity),
+    )
+}
+
+fn phase_for(boss: &Boss) -> usize {
+    let share = boss.health as f32 / boss.max_health as f32;
+    BOSS_PHASES
+        .iter()
+        .rposition(|phase| share <= phase.below)
+        .unwrap_or(0)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn damage_boss(
+    mut commands: Commands,
+    mut boss_hits: EventReader<BossHitEvent>,
+    mut boss_query: Query<&mut Boss>,
+    part_query: Query<Entity, With<BossPart>>,
+    mut paddle_query: Query<(&mut Transform, &mut PaddleBounds), With<Paddle>>,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut level: ResMut<Level>,
+    mut current_level: ResMut<CurrentLevel>,
+    level_set: Option<Res<LevelSet>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
+    config: Res<GameConfig>,
+) {
+    let Ok(mut boss) = boss_query.get_single_mut() else {
+        boss_hits.clear();
+        return;
+    };
+    for hit in boss_hits.iter() {
+        if boss.health == 0 {
+            break;
+        }
+        boss.health = boss.health.saturating_sub(hit.damage);
+        if boss.health > 0 {
+            continue;
+        }
+
+        let bonus = BOSS_BONUS * boss.encounter as usize;
+        scoreboard.score += bonus;
+        if let Some(player_score) = hit
+            .player
+            .and_then(|player| scoreboard.player_scores.get_mut(player))
+        {
+            *player_score += bonus;
+        }
+        explosion_events.send(ExplosionEvent { row: None });
+    }
+
+    if boss.health > 0 {
+        boss.phase = boss.phase.max(phase_for(&boss));
+        return;
+    }
+
+    // Beaten: paddles are made whole again and the next level set begins
+    for part in &part_query {
+        commands.entity(part).despawn();
+    }
+    for (mut paddle_transform, mut bounds) in &mut paddle_query {
+        paddle_transform.scale.x = config.paddle_size.x;
+        refit_paddle(&mut paddle_transform, &mut bounds, &config);
+    }
+    level.index += 1;
+    start_next_set(
+        &mut commands,
+        &mut current_level,
+        level_set.as_deref(),
+        &mut next_state,
+        &config.layout,
+    );
+}
+
+// Fits a paddle's bounds to its width, on the part of the floor it had
+fn refit_paddle(transform: &mut Transform, bounds: &mut PaddleBounds, config: &GameConfig) {
+    let layout = &config.layout;
+    *bounds = refit_floor_bounds(bounds, layout, layout, transform.scale.x);
+    transform.translation.x = transform.translation.x.clamp(bounds.left, bounds.right);
+}
+
+// Sweeps the boss from side to side, faster each phase, and keeps its parts together
+fn steer_boss(
+    mut core_query: Query<(&Boss, &mut Transform, &mut Velocity, &mut Sprite)>,
+    mut part_query: Query<(&BossPart, &mut Transform, &mut Velocity), Without<Boss>>,
+    config: Res<GameConfig>,
+) {
+    let Ok((boss, mut core_transform, mut core_velocity, mut core_sprite)) =
+        core_query.get_single_mut()
+    else {
+        return;
+    };
+    let phase = &BOSS_PHASES[boss.phase];
+    let middle = config.layout.floor_middle();
+    let direction = if core_transform.translation.x >= middle + BOSS_TRAVEL {
+        core_transform.translation.x = middle + BOSS_TRAVEL;
+        -1.0
+    } else if core_transform.translation.x <= middle - BOSS_TRAVEL {
+        core_transform.translation.x = middle - BOSS_TRAVEL;
+        1.0
+    } else {
+        core_velocity.x.signum()
+    };
+    core_velocity.0 = Vec2::new(direction * phase.speed, 0.0);
+    core_sprite.color = phase.color;
+
+    for (part, mut transform, mut velocity) in &mut part_query {
+        transform.translation = core_transform.translation + part.offset.extend(0.0);
+        velocity.0 = core_velocity.0;
+    }
+}
+
+// Each phase fires a wider spread of shots from under the core
+fn fire_projectiles(
+    mut commands: Commands,
+    time_step: Res<FixedTime>,
+    mut boss_query: Query<(&mut Boss, &Transform)>,
+) {
+    let Ok((mut boss, transform)) = boss_query.get_single_mut() else {
+        return;
+    };
+    boss.reload -= time_step.period.as_secs_f32();
+    if boss.reload > 0.0 {
+        return;
+    }
+    let phase = &BOSS_PHASES[boss.phase];
+    boss.reload = phase.reload;
+
+    let muzzle = transform.translation - Vec3::new(0.0, transform.scale.y / 2.0, 0.0);
+    for shot in 0..phase.shots {
+        let sideways = (shot as f32 - (phase.shots - 1) as f32 / 2.0) * PROJECTILE_SPREAD;
+        commands.spawn(projectile_bundle(
+            muzzle,
+            Vec2::new(sideways, -PROJECTILE_SPEED),
+        ));
+    }
+}
+
+type ProjectilePaddleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Player,
+        &'static mut Transform,
+        &'static mut PaddleBounds,
+    ),
+    (With<Paddle>, Without<BossProjectile>),
+>;
+
+// A projectile that reaches a paddle shrinks it, or costs a life once it is as small as it gets
+#[allow(clippy::too_many_arguments)]
+fn hit_paddles_with_projectiles(
+    mut commands: Commands,
+    projectile_query: Query<(Entity, &Transform), With<BossProjectile>>,
+    boss_query: Query<(), With<Boss>>,
+    mut paddle_query: ProjectilePaddleQuery,
+    brick_query: Query<Entity, With<Brick>>,
+    mut lives: ResMut<Lives>,
+    coop: Res<CoopSettings>,
+    current_level: Res<CurrentLevel>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut collisions: EventWriter<CollisionEvent>,
+    config: Res<GameConfig>,
+    god_mode: Option<Res<GodMode>>,
+) {
+    let min_width = config.paddle_size.x * PADDLE_MIN_SHARE;
+    // Shots outlive their boss only until the end of the tick it falls in
+    if boss_query.is_empty() {
+        for (projectile, _) in &projectile_query {
+            commands.entity(projectile).despawn();
+        }
+        return;
+    }
+
+    for (projectile, projectile_transform) in &projectile_query {
+        if projectile_transform.translation.y < config.layout.bottom_wall {
+            commands.entity(projectile).despawn();
+            continue;
+        }
+        let hit_paddle = paddle_query.iter_mut().find(|(_, _, paddle_transform, _)| {
+            collide(
+                projectile_transform.translation,
+                projectile_transform.scale.truncate(),
+                paddle_transform.translation,
+                paddle_transform.scale.truncate(),
+            )
+            .is_some()
+        });
+        let Some((_, _, mut paddle_transform, mut bounds)) = hit_paddle else {
+            continue;
+        };
+        commands.entity(projectile).despawn();
+
+        if paddle_transform.scale.x > min_width {
+            paddle_transform.scale.x = (paddle_transform.scale.x * PADDLE_SHRINK).max(min_width);
+            refit_paddle(&mut paddle_transform, &mut bounds, &config);
+            continue;
+        }
+
+        // The game may already have ended earlier in this tick
+        if lives.all_lost() || god_mode.is_some() {
+            continue;
+        }
+        let pool = lives_pool_for_landing(
+            &coop,
+            &lives,
+            paddle_transform.translation.x,
+            &config.layout,
+        );
+        lives.lives_left[pool] -= 1;
+        // Told like the ball reaching the floor under the paddle, so it counts the same way
+        collisions.send(CollisionEvent {
+            position: Vec2::new(paddle_transform.translation.x, config.layout.bottom_wall),
+            surface: Surface::Floor,
+            life_lost: true,
+        });
+        if lives.all_lost() {
+            for brick in &brick_query {
+                commands.entity(brick).despawn();
+            }
+            spawn_bricks(&mut commands, &current_level.0, &config.layout);
+            next_state.set(GameState::GameOver);
+        } else if lives.lives_left[pool] == 0 {
+            leave_floor_to_partner(
+                &mut commands,
+                pool,
+                paddle_query
+                    .iter_mut()
+                    .map(|(paddle, player, _, bounds)| (paddle, player, bounds)),
+                &config,
+            );
+        }
+    }
+}
+
+fn spawn_health_bar(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: HEALTH_BAR_TOP,
+                    left: HEALTH_BAR_LEFT,
+                    width: HEALTH_BAR_WIDTH,
+                    height: HEALTH_BAR_HEIGHT,
+                    ..default()
+                },
+                background_color: HEALTH_BAR_BACKGROUND.into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            HealthBar,
+        ))
+        .with_children(|bar| {
+            bar.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: HEALTH_BAR_COLOR.into(),
+                    ..default()
+                },
+                HealthBarFill,
+            ));
+        });
+}
+
+fn update_health_bar(
+    boss_query: Query<&Boss>,
+    mut bar_query: Query<&mut Visibility, With<HealthBar>>,
+    mut fill_query: Query<&mut Style, With<HealthBarFill>>,
+) {
+    let boss = boss_query.get_single().ok();
+    for mut visibility in &mut bar_query {
+        *visibility = match boss {
+            Some(_) => Visibility::Inherited,
+            None => Visibility::Hidden,
+        };
+    }
+    if let Some(boss) = boss {
+        for mut style in &mut fill_query {
+            style.width = Val::Percent(100.0 * boss.health as f32 / boss.max_health as f32);
+        }
+    }
+}
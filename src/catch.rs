@@ -0,0 +1,228 @@
+use bevy::{
+    input::common_conditions::input_just_released,
+    prelude::*,
+    sprite::collide_aabb::{collide, Collision},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    check_for_collisions, move_paddle, Ball, GameState, GameplayTick, Paddle, PaddleInputs, Player,
+    StartMenuText, Velocity,
+};
+
+// Catch
+// How long a caught ball is held before it launches by itself
+const CATCH_HOLD: f32 = 3.0;
+// How long the catch power-up lasts
+pub const CATCH_DURATION: f32 = 20.0;
+// Launch angle from straight up for a ball held at the very end of the paddle, in radians
+pub const MAX_LAUNCH_ANGLE: f32 = 1.0;
+
+pub struct CatchPlugin;
+
+impl Plugin for CatchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CatchOption>()
+            .add_systems(
+                Update,
+                (
+                    toggle_catch_option.run_if(input_just_released(KeyCode::K)),
+                    update_catch_menu_text,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::NewGame)),
+            )
+            .add_systems(
+                GameplayTick,
+                (
+                    hold_caught_balls
+                        .after(move_paddle)
+                        .before(check_for_collisions),
+                    catch_balls.after(check_for_collisions),
+                    wear_off_catch_paddles,
+                ),
+            )
+            .add_systems(OnEnter(GameState::GameOver), release_all_balls);
+    }
+}
+
+// Whether every paddle catches the ball for the whole game, picked on the start screen
+#[derive(Resource, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CatchOption(pub bool);
+
+// A paddle that catches the ball for a while, from a power-up
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct CatchPaddle {
+    pub time_left: f32,
+}
+
+// A ball held on a paddle, where it landed, until the player launches it
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct Caught {
+    pub player: usize,
+    // Distance from the middle of the paddle
+    pub offset: f32,
+    // The ball's speed when it was caught, which it leaves with again
+    pub speed: f32,
+    pub time_left: f32,
+}
+
+impl Caught {
+    // Balls caught further out leave at a steeper angle
+    fn launch_velocity(&self, paddle_width: f32) -> Vec2 {
+        let share = (self.offset / (paddle_width / 2.0)).clamp(-1.0, 1.0);
+        let angle = share * MAX_LAUNCH_ANGLE;
+        Vec2::new(angle.sin(), angle.cos()) * self.speed
+    }
+}
+
+fn toggle_catch_option(mut option: ResMut<CatchOption>) {
+    option.0 = !option.0;
+}
+
+fn update_catch_menu_text(
+    option: Res<CatchOption>,
+    mut query: Query<(Ref<StartMenuText>, &mut Text)>,
+) {
+    for (menu, mut text) in &mut query {
+        if !option.is_changed() && !menu.is_added() {
+            continue;
+        }
+        let state = if option.0 { "on" } else { "off" };
+        text.sections[3].value = format!("\nK: catch {state}");
+    }
+}
+
+type CatchingPaddleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Player,
+        &'static Transform,
+        Option<&'static CatchPaddle>,
+    ),
+    (With<Paddle>, Without<Ball>),
+>;
+
+type FreeBallQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static Transform, &'static mut Velocity),
+    (With<Ball>, Without<Caught>),
+>;
+
+// A ball that has just bounced up off the top of a catching paddle stays there
+fn catch_balls(
+    mut commands: Commands,
+    option: Res<CatchOption>,
+    mut ball_query: FreeBallQuery,
+    paddle_query: CatchingPaddleQuery,
+) {
+    for (ball, ball_transform, mut velocity) in &mut ball_query {
+        if velocity.y <= 0.0 {
+            continue;
+        }
+        for (player, paddle_transform, catch_paddle) in &paddle_query {
+            if !option.0 && catch_paddle.is_none() {
+                continue;
+            }
+            let collision = collide(
+                ball_transform.translation,
+                ball_transform.scale.truncate(),
+                paddle_transform.translation,
+                paddle_transform.scale.truncate(),
+            );
+            if collision != Some(Collision::Top) {
+                continue;
+            }
+            commands.entity(ball).insert(Caught {
+                player: player.0,
+                offset: ball_transform.translation.x - paddle_transform.translation.x,
+                speed: velocity.length(),
+                time_left: CATCH_HOLD,
+            });
+            velocity.0 = Vec2::ZERO;
+            break;
+        }
+    }
+}
+
+type CaughtBallQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static mut Velocity,
+        &'static mut Caught,
+    ),
+    With<Ball>,
+>;
+
+type HoldingPaddleQuery<'w, 's> =
+    Query<'w, 's, (&'static Player, &'static Transform), (With<Paddle>, Without<Ball>)>;
+
+// Caught balls ride along with their paddle. Firing, or holding on too long, launches every ball
+// on that paddle at once. Balls whose paddle has left the floor launch straight away.
+fn hold_caught_balls(
+    mut commands: Commands,
+    time_step: Res<FixedTime>,
+    inputs: Res<PaddleInputs>,
+    mut ball_query: CaughtBallQuery,
+    paddle_query: HoldingPaddleQuery,
+) {
+    let dt = time_step.period.as_secs_f32();
+    let mut releasing = Vec::new();
+    for (_, _, _, mut caught) in &mut ball_query {
+        caught.time_left -= dt;
+        let fire = inputs.0.get(caught.player).is_some_and(|input| input.fire);
+        if fire || caught.time_left <= 0.0 {
+            releasing.push(caught.player);
+        }
+    }
+
+    for (ball, mut ball_transform, mut velocity, caught) in &mut ball_query {
+        let paddle = paddle_query
+            .iter()
+            .find(|(player, _)| player.0 == caught.player)
+            .map(|(_, transform)| transform);
+        let Some(paddle_transform) = paddle else {
+            commands.entity(ball).remove::<Caught>();
+            velocity.0 = Vec2::new(0.0, caught.speed);
+            continue;
+        };
+        if releasing.contains(&caught.player) {
+            commands.entity(ball).remove::<Caught>();
+            velocity.0 = caught.launch_velocity(paddle_transform.scale.x);
+            continue;
+        }
+        // Keep the ball resting just clear of the paddle's top
+        let rest_height = (paddle_transform.scale.y + ball_transform.scale.y) / 2.0 + 1.0;
+        ball_transform.translation.x = paddle_transform.translation.x + caught.offset;
+        ball_transform.translation.y = paddle_transform.translation.y + rest_height;
+    }
+}
+
+fn wear_off_catch_paddles(
+    mut commands: Commands,
+    time_step: Res<FixedTime>,
+    mut paddle_query: Query<(Entity, &mut CatchPaddle)>,
+) {
+    for (paddle, mut catch_paddle) in &mut paddle_query {
+        catch_paddle.time_left -= time_step.period.as_secs_f32();
+        if catch_paddle.time_left <= 0.0 {
+            commands.entity(paddle).remove::<CatchPaddle>();
+        }
+    }
+}
+
+// A ball still held when the game ends goes free, so the next game doesn't start with it stuck
+fn release_all_balls(
+    mut commands: Commands,
+    mut ball_query: Query<(Entity, &mut Velocity, &Caught), With<Ball>>,
+) {
+    for (ball, mut velocity, caught) in &mut ball_query {
+        commands.entity(ball).remove::<Caught>();
+        velocity.0 = Vec2::new(0.0, caught.speed);
+    }
+}
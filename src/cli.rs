@@ -0,0 +1,356 @@
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::{
+    app::{AppExit, PluginGroupBuilder, ScheduleRunnerPlugin},
+    prelude::*,
+    render::{settings::WgpuSettings, RenderPlugin},
+    window::{ExitCondition, WindowResolution},
+    winit::WinitPlugin,
+};
+
+use crate::config::GameConfig;
+use crate::generator::{generate, GeneratorParams};
+use crate::level::LevelData;
+use crate::modes::GameMode;
+use crate::replay::{Playback, ReplayEnded};
+use crate::settings::Settings;
+use crate::snapshot::GameSnapshot;
+use crate::{
+    reset_players, run_gameplay_tick, CoopSettings, GameState, Level, Lives, LocalTick, Paddle,
+    Scoreboard, StartGameOverlay,
+};
+
+pub const USAGE: &str = "\
+Usage: rust-breakout [options]
+
+  --level <file>          play a level file, like the editor's assets/levels/custom.ron
+  --seed <n>              play the board generated from a seed
+  --difficulty <1-5>      how hard a generated board is, with --seed
+  --mode <mode>           classic, time-attack or endless
+  --window-size <WxH>     the window's size in pixels, like 1280x720
+  --fps-cap <n>           at most this many frames a second. The game runs a tick a frame, so
+                          a lower cap also slows it down
+  --mute                  play no sound, without changing the saved volumes
+  --record <file>         write the game's inputs to a replay file when it ends
+  --replay <file>         play a replay file back
+  --headless              run with no window, as fast as possible, then print how the game
+                          ended. Without --replay the paddles stand still.
+  --help                  show this";
+
+// Headless runs
+// A game with no paddle input can bounce between unbreakable bricks forever, so headless runs
+// stop after this many ticks, an hour of play
+const HEADLESS_TICK_LIMIT: u64 = 60 * 60 * 60;
+
+// What was asked for on the command line
+#[derive(Resource, Clone, Default)]
+pub struct Options {
+    pub help: bool,
+    pub level: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub difficulty: Option<u32>,
+    pub mode: Option<GameMode>,
+    pub window_size: Option<(f32, f32)>,
+    pub fps_cap: Option<f64>,
+    pub mute: bool,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub headless: bool,
+}
+
+// Reads the arguments, not counting the program's name
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str, what: &str| {
+            args.next()
+                .filter(|value| !value.starts_with("--"))
+                .ok_or(format!("{name} needs {what}"))
+        };
+        match arg.as_str() {
+            "--help" | "-h" => options.help = true,
+            "--level" => options.level = Some(value("--level", "a file")?.into()),
+            "--seed" => {
+                let seed = value("--seed", "a number")?;
+                let seed = seed
+                    .parse()
+                    .map_err(|_| format!("--seed needs a number, got {seed}"))?;
+                options.seed = Some(seed);
+            }
+            "--difficulty" => {
+                let difficulty = value("--difficulty", "a number from 1 to 5")?;
+                match difficulty.parse() {
+                    Ok(difficulty @ 1..=5) => options.difficulty = Some(difficulty),
+                    _ => {
+                        return Err(format!(
+                            "--difficulty needs a number from 1 to 5, got {difficulty}"
+                        ))
+                    }
+                }
+            }
+            "--mode" => {
+                let mode = value("--mode", "classic, time-attack or endless")?;
+                options.mode = Some(match mode.as_str() {
+                    "classic" => GameMode::Classic,
+                    "time-attack" => GameMode::TimeAttack,
+                    "endless" => GameMode::Endless,
+                    _ => {
+                        return Err(format!(
+                            "--mode needs classic, time-attack or endless, got {mode}"
+                        ))
+                    }
+                });
+            }
+            "--window-size" => {
+                let size = value("--window-size", "a size like 1280x720")?;
+                let parsed = size.split_once('x').and_then(|(width, height)| {
+                    Some((width.parse::<f32>().ok()?, height.parse::<f32>().ok()?))
+                });
+                match parsed {
+                    Some((width, height)) if width >= 1.0 && height >= 1.0 => {
+                        options.window_size = Some((width, height))
+                    }
+                    _ => {
+                        return Err(format!(
+                            "--window-size needs a size like 1280x720, got {size}"
+                        ))
+                    }
+                }
+            }
+            "--fps-cap" => {
+                let cap = value("--fps-cap", "a number of frames a second")?;
+                match cap.parse::<f64>() {
+                    Ok(cap) if cap >= 1.0 && cap.is_finite() => options.fps_cap = Some(cap),
+                    _ => {
+                        return Err(format!(
+                            "--fps-cap needs a number of frames a second, got {cap}"
+                        ))
+                    }
+                }
+            }
+            "--mute" => options.mute = true,
+            "--record" => options.record = Some(value("--record", "a file")?.into()),
+            "--replay" => options.replay = Some(value("--replay", "a file")?.into()),
+            "--headless" => options.headless = true,
+            _ => return Err(format!("unknown option {arg}")),
+        }
+    }
+    options.check()?;
+    Ok(options)
+}
+
+impl Options {
+    // Rules out options that contradict each other, or that would do nothing
+    fn check(&self) -> Result<(), String> {
+        if self.level.is_some() && self.seed.is_some() {
+            return Err("--level and --seed both pick the board, so only one can be used".into());
+        }
+        if self.difficulty.is_some() && self.seed.is_none() {
+            return Err("--difficulty only applies to generated boards, so it needs --seed".into());
+        }
+        if self.replay.is_some() {
+            let board = [
+                ("--level", self.level.is_some()),
+                ("--seed", self.seed.is_some()),
+                ("--mode", self.mode.is_some()),
+                ("--record", self.record.is_some()),
+            ];
+            if let Some((name, _)) = board.iter().find(|(_, given)| *given) {
+                return Err(format!(
+                    "--replay plays the game as it was recorded, so it can't be used with {name}"
+                ));
+            }
+        }
+        if self.headless {
+            if self.window_size.is_some() {
+                return Err(
+                    "--headless has no window, so it can't be used with --window-size".into(),
+                );
+            }
+            if self.fps_cap.is_some() {
+                return Err(
+                    "--headless runs as fast as it can, so it can't be used with --fps-cap".into(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // The board to start on: a level file, a generated board, or the classic wall
+    pub fn level(&self) -> Result<LevelData, String> {
+        if let Some(path) = &self.level {
+            let level = LevelData::load(path)
+                .map_err(|error| format!("could not read {}: {error}", path.display()))?;
+            if let Some(problem) = level.validate().first() {
+                return Err(format!("{} can't be played: {problem}", path.display()));
+            }
+            // The tuning file isn't loaded yet, so this is the board the game starts on. A file
+            // that changes it is turned down if the level no longer fits.
+            if let Err(problem) = GameConfig::default().layout.check_level(&level) {
+                return Err(format!(
+                    "{} doesn't fit the board: {problem}",
+                    path.display()
+                ));
+            }
+            return Ok(level);
+        }
+        if let Some(seed) = self.seed {
+            let mut params = GeneratorParams::for_seed(seed);
+            if let Some(difficulty) = self.difficulty {
+                params.difficulty = difficulty;
+            }
+            return Ok(generate(seed, &params));
+        }
+        Ok(LevelData::default())
+    }
+
+    // The engine's plugins, with a window of the asked size, or none at all
+    pub fn configure_plugins(&self, plugins: PluginGroupBuilder) -> PluginGroupBuilder {
+        if self.headless {
+            return plugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    close_when_requested: false,
+                })
+                // Nothing is drawn, so no graphics card is needed
+                .set(RenderPlugin {
+                    wgpu_settings: WgpuSettings {
+                        backends: None,
+                        ..default()
+                    },
+                })
+                .disable::<WinitPlugin>()
+                .add(ScheduleRunnerPlugin::run_loop(Duration::ZERO));
+        }
+        match self.window_size {
+            Some((width, height)) => plugins.set(WindowPlugin {
+                primary_window: Some(Window {
+                    resolution: WindowResolution::new(width, height),
+                    ..default()
+                }),
+                ..default()
+            }),
+            None => plugins,
+        }
+    }
+}
+
+pub struct CliPlugin;
+
+impl Plugin for CliPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, apply_options)
+            .add_systems(
+                Last,
+                cap_frame_rate.run_if(|options: Res<Options>| options.fps_cap.is_some()),
+            )
+            .add_systems(
+                Update,
+                start_headless_game
+                    .run_if(in_state(GameState::NewGame))
+                    .run_if(resource_exists::<HeadlessRun>())
+                    .run_if(not(resource_exists::<Playback>())),
+            )
+            .add_systems(
+                Update,
+                (
+                    count_headless_ticks
+                        .in_set(LocalTick)
+                        .after(run_gameplay_tick),
+                    end_headless_run
+                        .run_if(on_event::<ReplayEnded>())
+                        .after(LocalTick),
+                )
+                    .run_if(resource_exists::<HeadlessRun>()),
+            )
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                end_headless_run.run_if(resource_exists::<HeadlessRun>()),
+            );
+    }
+}
+
+// A run with no window, which ends the app once its game does
+#[derive(Resource, Default)]
+pub struct HeadlessRun {
+    ticks: u64,
+    ended: bool,
+}
+
+fn apply_options(
+    mut commands: Commands,
+    options: Res<Options>,
+    mut settings: ResMut<Settings>,
+    mut mode: ResMut<GameMode>,
+) {
+    if let Some(picked) = options.mode {
+        *mode = picked;
+    }
+    // Nobody is listening to a headless run
+    settings.muted = options.mute || options.headless;
+    if options.headless {
+        commands.init_resource::<HeadlessRun>();
+    }
+}
+
+// Sleeps off whatever is left of the frame's share of a second
+fn cap_frame_rate(options: Res<Options>, mut frame_start: Local<Option<Instant>>) {
+    let Some(cap) = options.fps_cap else {
+        return;
+    };
+    let frame_time = Duration::from_secs_f64(1.0 / cap);
+    if let Some(start) = *frame_start {
+        let elapsed = start.elapsed();
+        if elapsed < frame_time {
+            thread::sleep(frame_time - elapsed);
+        }
+    }
+    *frame_start = Some(Instant::now());
+}
+
+// Nobody can press Enter, so a headless game starts by itself
+fn start_headless_game(
+    mut commands: Commands,
+    start_query: Query<Entity, With<StartGameOverlay>>,
+    paddle_query: Query<Entity, With<Paddle>>,
+    coop: Res<CoopSettings>,
+    config: Res<GameConfig>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for start_ent in &start_query {
+        commands.entity(start_ent).despawn();
+    }
+    reset_players(&mut commands, &coop, &paddle_query, &config);
+    next_state.set(GameState::InGame);
+}
+
+fn count_headless_ticks(
+    mut run: ResMut<HeadlessRun>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    run.ticks += 1;
+    if run.ticks >= HEADLESS_TICK_LIMIT {
+        warn!("Stopping the headless game after {HEADLESS_TICK_LIMIT} ticks");
+        next_state.set(GameState::GameOver);
+    }
+}
+
+// Prints how the game ended, for scripts to read, and quits
+fn end_headless_run(world: &mut World) {
+    if world.resource::<HeadlessRun>().ended {
+        return;
+    }
+    world.resource_mut::<HeadlessRun>().ended = true;
+    let checksum = GameSnapshot::capture(world).checksum();
+    let ticks = world.resource::<HeadlessRun>().ticks;
+    let score = world.resource::<Scoreboard>().score;
+    let lives = &world.resource::<Lives>().lives_left;
+    let level = world.resource::<Level>().index;
+    println!(
+        "ticks: {ticks}, score: {score}, lives: {lives:?}, level: {level}, checksum: {checksum:016x}"
+    );
+    world.send_event(AppExit);
+}
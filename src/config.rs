@@ -0,0 +1,553 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::behaviour::BrickPath;
+use crate::level::{CurrentLevel, LevelData, LevelSet};
+use crate::multiball::BallAssets;
+use crate::net::NetSession;
+use crate::replay::{Playback, Recording};
+use crate::{
+    refit_floor_bounds, spawn_walls, Ball, Brick, Paddle, PaddleBounds, Player, Velocity, Wall,
+    BALL_STARTING_POSITION, GAP_BETWEEN_PADDLE_AND_FLOOR,
+};
+
+// Tuning
+// The tuning file, under assets. It is watched, so saving it applies it while the game runs.
+const CONFIG_PATH: &str = "config/game.config.ron";
+
+// Limits on tuning values
+// Any faster and the ball can pass clean through a brick in one tick
+const MAX_BALL_SPEED: f32 = 1200.0;
+const MAX_BALL_SIZE: f32 = 100.0;
+const MAX_PADDLE_SPEED: f32 = 50.0;
+// Both paddles have to fit on their half of the floor
+const MAX_PADDLE_WIDTH: f32 = 400.0;
+const MAX_PADDLE_HEIGHT: f32 = 60.0;
+const MAX_STARTING_LIVES: usize = 99;
+// Levels can't be laid out on a grid any bigger than this
+const MAX_GRID_COLUMNS: u32 = 64;
+const MAX_GRID_ROWS: u32 = 64;
+
+// Config errors text
+const CONFIG_ERROR_FONT_SIZE: f32 = 20.0;
+const CONFIG_ERROR_TEXT_COLOR: Color = Color::rgb(1.0, 0.3, 0.3);
+const CONFIG_ERROR_PADDING: Val = Val::Px(8.0);
+
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ConfigFile>()
+            .init_asset_loader::<ConfigFileLoader>()
+            .init_resource::<GameConfig>()
+            .add_systems(Startup, load_config)
+            // A replay plays with the tuning it was recorded with, and the tuning can't change
+            // under an online game or one being recorded, which both count on every tick
+            // playing out the same way
+            .add_systems(
+                Update,
+                read_config_file
+                    .run_if(not(resource_exists::<Playback>()))
+                    .run_if(not(resource_exists::<NetSession>()))
+                    .run_if(not(resource_exists::<Recording>())),
+            )
+            // After everything spawned this frame is in place, and before it is drawn
+            .add_systems(PostUpdate, apply_game_config);
+    }
+}
+
+// How the game plays and looks, read from the tuning file
+#[derive(Resource, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub ball_speed: f32,
+    pub ball_size: f32,
+    // How far a paddle moves in a tick
+    pub paddle_speed: f32,
+    pub paddle_size: Vec2,
+    pub starting_lives: usize,
+    pub background_color: Color,
+    pub wall_color: Color,
+    pub ball_color: Color,
+    pub paddle_colors: [Color; 2],
+    pub layout: BoardLayout,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            ball_speed: 400.0,
+            ball_size: 30.0,
+            paddle_speed: 10.0,
+            paddle_size: Vec2::new(120.0, 20.0),
+            starting_lives: 3,
+            background_color: Color::rgb(0., 0., 0.25),
+            wall_color: Color::rgb(0., 0.75, 0.),
+            ball_color: Color::PURPLE,
+            paddle_colors: [Color::ORANGE, Color::CYAN],
+            layout: BoardLayout::default(),
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn ball_scale(&self) -> Vec3 {
+        Vec3::new(self.ball_size, self.ball_size, 0.0)
+    }
+
+    pub fn paddle_scale(&self) -> Vec3 {
+        self.paddle_size.extend(0.0)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let in_range = |name: &str, value: f32, max: f32| {
+            if value > 0.0 && value <= max {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{name} is {value}, but has to be above 0 and at most {max}"
+                ))
+            }
+        };
+        in_range("ball_speed", self.ball_speed, MAX_BALL_SPEED)?;
+        in_range("ball_size", self.ball_size, MAX_BALL_SIZE)?;
+        in_range("paddle_speed", self.paddle_speed, MAX_PADDLE_SPEED)?;
+        in_range("paddle_size width", self.paddle_size.x, MAX_PADDLE_WIDTH)?;
+        in_range("paddle_size height", self.paddle_size.y, MAX_PADDLE_HEIGHT)?;
+        self.layout.validate()?;
+        // Both paddles have to fit on their half of the floor
+        let (left_edge, right_edge) = self.layout.floor_edges();
+        let half_floor = (right_edge - left_edge) / 2.0;
+        if self.paddle_size.x >= half_floor {
+            return Err(format!(
+                "paddle_size width is {}, but has to be under {half_floor} to fit on half the floor",
+                self.paddle_size.x
+            ));
+        }
+        let ball_top = BALL_STARTING_POSITION.y + self.ball_size / 2.0;
+        if ball_top >= self.layout.cell_bottom(self.layout.grid_rows - 1) {
+            return Err(format!(
+                "ball_size is {}, too big to start the ball under the bricks",
+                self.ball_size
+            ));
+        }
+        if !(1..=MAX_STARTING_LIVES).contains(&self.starting_lives) {
+            return Err(format!(
+                "starting_lives is {}, but has to be from 1 to {MAX_STARTING_LIVES}",
+                self.starting_lives
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Where the walls stand and how the brick grid is laid out between them
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoardLayout {
+    // The middle of each wall
+    pub left_wall: f32,
+    pub right_wall: f32,
+    pub top_wall: f32,
+    pub bottom_wall: f32,
+    pub wall_size: f32,
+    // Columns and rows of the grid. The classic wall fills it, and the editor and generator lay
+    // levels out across its columns.
+    pub grid_columns: u32,
+    pub grid_rows: u32,
+    pub cell_size: Vec2,
+    // Between neighbouring cells
+    pub cell_space: f32,
+    // The middle of the top left cell
+    pub cell_top: f32,
+    pub cell_left: f32,
+}
+
+impl Default for BoardLayout {
+    fn default() -> Self {
+        BoardLayout {
+            left_wall: -400.,
+            right_wall: 465.,
+            top_wall: 325.,
+            bottom_wall: -325.,
+            wall_size: 10.,
+            grid_columns: 10,
+            grid_rows: 5,
+            cell_size: Vec2::new(80., 30.),
+            cell_space: 5.,
+            cell_top: 300.,
+            cell_left: -350.,
+        }
+    }
+}
+
+impl BoardLayout {
+    pub fn brick_size(&self) -> Vec3 {
+        self.cell_size.extend(0.0)
+    }
+
+    // From one cell to the next, across and down
+    pub fn cell_step(&self) -> Vec2 {
+        self.cell_size + Vec2::splat(self.cell_space)
+    }
+
+    pub fn floor_middle(&self) -> f32 {
+        (self.left_wall + self.right_wall) / 2.
+    }
+
+    // The stretch of floor between the side walls that paddles travel along
+    pub fn floor_edges(&self) -> (f32, f32) {
+        (
+            self.left_wall + self.wall_size,
+            self.right_wall - self.wall_size,
+        )
+    }
+
+    // Where the paddles stand
+    pub fn paddle_y(&self) -> f32 {
+        self.bottom_wall + GAP_BETWEEN_PADDLE_AND_FLOOR
+    }
+
+    // Middle and size of the left, right, top and bottom walls
+    pub fn walls(&self) -> [(Vec2, Vec3); 4] {
+        let height = self.top_wall - self.bottom_wall;
+        let width = self.right_wall - self.left_wall + self.wall_size;
+        let side = Vec3::new(self.wall_size, height, 0.0);
+        let across = Vec3::new(width, self.wall_size, 0.0);
+        [
+            (Vec2::new(self.left_wall, 0.0), side),
+            (Vec2::new(self.right_wall, 0.0), side),
+            (Vec2::new(self.floor_middle(), self.top_wall), across),
+            (Vec2::new(self.floor_middle(), self.bottom_wall), across),
+        ]
+    }
+
+    // How many rows of cells fit above the paddles
+    pub fn level_rows(&self) -> u32 {
+        let room = self.cell_top - self.cell_size.y / 2. - self.paddle_y();
+        (room / self.cell_step().y).ceil().max(0.) as u32
+    }
+
+    // The bottom edge of a row of cells
+    fn cell_bottom(&self, row: u32) -> f32 {
+        self.cell_top - row as f32 * self.cell_step().y - self.cell_size.y / 2.
+    }
+
+    // Whether that many columns and rows of cells fit between the walls, with the bottom row
+    // above `floor`
+    fn fits(&self, columns: u32, rows: u32, floor: (f32, &str)) -> Result<(), String> {
+        let inner_left = self.left_wall + self.wall_size / 2.;
+        let inner_right = self.right_wall - self.wall_size / 2.;
+        let inner_top = self.top_wall - self.wall_size / 2.;
+        let left = self.cell_left - self.cell_size.x / 2.;
+        let right = left + columns as f32 * self.cell_step().x - self.cell_space;
+        let top = self.cell_top + self.cell_size.y / 2.;
+        if left <= inner_left || right >= inner_right {
+            return Err(format!(
+                "{columns} columns of bricks reach from {left} to {right}, past the side walls"
+            ));
+        }
+        if top >= inner_top {
+            return Err(format!(
+                "the top row of bricks reaches {top}, past the top wall"
+            ));
+        }
+        let bottom = self.cell_bottom(rows.saturating_sub(1));
+        let (floor, below) = floor;
+        if bottom <= floor {
+            return Err(format!(
+                "{rows} rows of bricks reach down to {bottom}, past {below}"
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.wall_size <= 0.0 {
+            return Err(format!(
+                "wall_size is {}, but has to be above 0",
+                self.wall_size
+            ));
+        }
+        let start = BALL_STARTING_POSITION;
+        if !(self.left_wall < start.x && start.x < self.right_wall) {
+            return Err(format!(
+                "left_wall and right_wall have to be either side of where the ball starts, {}",
+                start.x
+            ));
+        }
+        if !(self.bottom_wall < start.y && start.y < self.top_wall) {
+            return Err(format!(
+                "bottom_wall and top_wall have to be either side of where the ball starts, {}",
+                start.y
+            ));
+        }
+        if self.cell_size.cmple(Vec2::ZERO).any() || self.cell_space < 0.0 {
+            return Err("cell_size has to be above 0, and cell_space at least 0".to_string());
+        }
+        if !(1..=MAX_GRID_COLUMNS).contains(&self.grid_columns)
+            || !(1..=MAX_GRID_ROWS).contains(&self.grid_rows)
+        {
+            return Err(format!(
+                "the grid is {} by {}, but has to be from 1 to {MAX_GRID_COLUMNS} columns and \
+                 1 to {MAX_GRID_ROWS} rows",
+                self.grid_columns, self.grid_rows
+            ));
+        }
+        // The classic wall fills the grid, and the ball starts under it
+        let start = (BALL_STARTING_POSITION.y, "where the ball starts");
+        self.fits(self.grid_columns, self.grid_rows, start)
+    }
+
+    // Whether a level's bricks all land on the grid, between the walls
+    pub fn check_level(&self, level: &LevelData) -> Result<(), String> {
+        let columns = level.bricks.iter().map(|brick| brick.column + 1).max();
+        let rows = level.bricks.iter().map(|brick| brick.row + 1).max();
+        let (Some(columns), Some(rows)) = (columns, rows) else {
+            return Ok(());
+        };
+        if columns > self.grid_columns {
+            return Err(format!(
+                "the level is {columns} columns wide, but the grid has {}",
+                self.grid_columns
+            ));
+        }
+        self.fits(columns, rows, (self.paddle_y(), "the paddles"))
+            .map_err(|error| format!("the level doesn't fit: {error}"))
+    }
+
+    // Where a point on another layout's board lands on this one, keeping its place on the grid
+    fn remap(&self, from: &BoardLayout, point: Vec2) -> Vec2 {
+        let from_origin = Vec2::new(from.cell_left, from.cell_top);
+        let origin = Vec2::new(self.cell_left, self.cell_top);
+        origin + (point - from_origin) / from.cell_step() * self.cell_step()
+    }
+}
+
+// The tuning file as loaded, or what is wrong with it. A broken file still loads, so the problem
+// can be shown on screen.
+#[derive(TypeUuid, TypePath)]
+#[uuid = "3a9c6e14-7d2b-4f08-b5e1-9c4d2a7f6b30"]
+struct ConfigFile(Result<GameConfig, String>);
+
+#[derive(Default)]
+struct ConfigFileLoader;
+
+impl AssetLoader for ConfigFileLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let config = ron::de::from_bytes::<GameConfig>(bytes)
+                .map_err(|error| error.to_string())
+                .and_then(|config| config.validate().map(|_| config));
+            load_context.set_default_asset(LoadedAsset::new(ConfigFile(config)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["config.ron"]
+    }
+}
+
+#[derive(Resource)]
+struct ConfigHandle(Handle<ConfigFile>);
+
+#[derive(Component)]
+struct ConfigErrorText;
+
+fn load_config(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ConfigHandle(asset_server.load(CONFIG_PATH)));
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: CONFIG_ERROR_FONT_SIZE,
+                color: CONFIG_ERROR_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: CONFIG_ERROR_PADDING,
+            left: CONFIG_ERROR_PADDING,
+            ..default()
+        }),
+        ConfigErrorText,
+    ));
+}
+
+// Takes up the tuning file each time it is saved. One with mistakes in is shown on screen and
+// left out, and the game carries on with the last good one.
+fn read_config_file(
+    mut file_events: EventReader<AssetEvent<ConfigFile>>,
+    handle: Res<ConfigHandle>,
+    files: Res<Assets<ConfigFile>>,
+    mut config: ResMut<GameConfig>,
+    mut current_level: ResMut<CurrentLevel>,
+    level_set: Option<Res<LevelSet>>,
+    mut text_query: Query<&mut Text, With<ConfigErrorText>>,
+) {
+    for file_event in file_events.iter() {
+        let (AssetEvent::Created { handle: changed } | AssetEvent::Modified { handle: changed }) =
+            file_event
+        else {
+            continue;
+        };
+        if *changed != handle.0 {
+            continue;
+        }
+        let Some(file) = files.get(changed) else {
+            continue;
+        };
+        let checked = file.0.clone().and_then(|new_config| {
+            fits_levels(&config, &new_config, &current_level, level_set.as_deref())
+                .map(|_| new_config)
+        });
+        let message = match checked {
+            Ok(new_config) => {
+                if *config != new_config {
+                    // The classic wall fills the grid, so it grows or shrinks with it
+                    if current_level.0 == LevelData::classic(&config.layout) {
+                        current_level.0 = LevelData::classic(&new_config.layout);
+                    }
+                    *config = new_config;
+                }
+                String::new()
+            }
+            Err(error) => {
+                error!("Could not apply {CONFIG_PATH}: {error}");
+                format!("Could not apply {CONFIG_PATH}: {error}")
+            }
+        };
+        for mut text in &mut text_query {
+            text.sections[0].value = message.clone();
+        }
+    }
+}
+
+// Whether the levels being played still fit a new config's layout
+fn fits_levels(
+    config: &GameConfig,
+    new_config: &GameConfig,
+    current_level: &CurrentLevel,
+    level_set: Option<&LevelSet>,
+) -> Result<(), String> {
+    let layout = &new_config.layout;
+    // The classic wall is laid out anew to fill the grid
+    if current_level.0 != LevelData::classic(&config.layout) {
+        layout.check_level(&current_level.0)?;
+    }
+    for level in level_set.iter().flat_map(|level_set| &level_set.levels) {
+        layout.check_level(level)?;
+    }
+    Ok(())
+}
+
+type ConfigPaddleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Player,
+        &'static mut Transform,
+        &'static mut Sprite,
+        &'static mut PaddleBounds,
+    ),
+    (With<Paddle>, Without<Ball>),
+>;
+
+type ConfigBallQuery<'w, 's> =
+    Query<'w, 's, (&'static mut Transform, &'static mut Velocity), (With<Ball>, Without<Paddle>)>;
+
+type ConfigBrickQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Transform, Option<&'static mut BrickPath>),
+    (With<Brick>, Without<Ball>, Without<Paddle>),
+>;
+
+type ConfigWallQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static mut Sprite), (With<Wall>, Without<Paddle>)>;
+
+// Puts a changed config into effect on everything already in play. Only what changed is touched,
+// so saving the file with new colors doesn't disturb the balls or paddles mid-game.
+#[allow(clippy::too_many_arguments)]
+fn apply_game_config(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    mut applied: Local<GameConfig>,
+    mut ball_assets: ResMut<BallAssets>,
+    mut paddle_query: ConfigPaddleQuery,
+    mut ball_query: ConfigBallQuery,
+    mut brick_query: ConfigBrickQuery,
+    mut wall_query: ConfigWallQuery,
+) {
+    // Everything was spawned with the config as it stands, so there is nothing to do yet
+    if *applied == *config {
+        return;
+    }
+    let layout = &config.layout;
+    let moved = applied.layout != *layout;
+    ball_assets.scale = config.ball_scale();
+    for (mut transform, mut velocity) in &mut ball_query {
+        transform.scale = config.ball_scale();
+        // Held balls stand still, and are launched at the speed they were caught at
+        if velocity.0 != Vec2::ZERO && applied.ball_speed != config.ball_speed {
+            velocity.0 = velocity.0.normalize() * config.ball_speed;
+        }
+        if moved {
+            let reach = Vec2::splat((layout.wall_size + config.ball_size) / 2.);
+            let inside = transform.translation.truncate().clamp(
+                Vec2::new(layout.left_wall, layout.bottom_wall) + reach,
+                Vec2::new(layout.right_wall, layout.top_wall) - reach,
+            );
+            transform.translation = inside.extend(transform.translation.z);
+        }
+    }
+    for (player, mut transform, mut sprite, mut bounds) in &mut paddle_query {
+        sprite.color = config.paddle_colors[player.0.min(1)];
+        // Paddles shrunk by the boss stay shrunk unless the size itself changed
+        let resized = applied.paddle_size != config.paddle_size;
+        if !resized && !moved {
+            continue;
+        }
+        if resized {
+            transform.scale = config.paddle_scale();
+        }
+        // Paddles keep to the part of the floor they had, made to fit their new width
+        *bounds = refit_floor_bounds(&bounds, &applied.layout, layout, transform.scale.x);
+        transform.translation.x = transform.translation.x.clamp(bounds.left, bounds.right);
+        transform.translation.y = layout.paddle_y();
+    }
+    // Bricks keep their places on the grid as it moves
+    if moved {
+        for (mut transform, path) in &mut brick_query {
+            let translation = layout.remap(&applied.layout, transform.translation.truncate());
+            transform.translation = translation.extend(transform.translation.z);
+            transform.scale = layout.brick_size();
+            if let Some(mut path) = path {
+                path.left = layout.remap(&applied.layout, Vec2::new(path.left, 0.)).x;
+                path.right = layout.remap(&applied.layout, Vec2::new(path.right, 0.)).x;
+            }
+        }
+    }
+    for (wall, mut sprite) in &mut wall_query {
+        if moved {
+            commands.entity(wall).despawn();
+        } else {
+            sprite.color = config.wall_color;
+        }
+    }
+    if moved {
+        spawn_walls(&mut commands, &config);
+    }
+    *applied = config.clone();
+}
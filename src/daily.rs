@@ -0,0 +1,356 @@
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::{
+    input::common_conditions::{input_just_pressed, input_just_released},
+    prelude::*,
+    window::WindowCloseRequested,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::catch::CatchOption;
+use crate::config::GameConfig;
+use crate::generator::{generate, GeneratorParams, Rng};
+use crate::level::{CurrentLevel, LevelData, LevelSet};
+use crate::modes::GameMode;
+use crate::storage::{load_ron, save_ron};
+use crate::{
+    reset_ball, reset_players, spawn_bricks, BallStateQuery, Brick, CoopSettings, GameState, Level,
+    Lives, Paddle, Scoreboard, StartGameOverlay,
+};
+
+// Daily challenge
+const DAILY_HISTORY_PATH: &str = "daily_results.ron";
+const DAILY_RESULT_PATH: &str = "daily_result.txt";
+const DAILY_LEVEL_COUNT: usize = 3;
+// Mixed into the day number so daily boards don't match the boards of small `--seed` values
+const DAILY_SEED_SALT: u64 = 0xDA11_C4A1;
+
+// Daily result text
+const DAILY_RESULT_FONT_SIZE: f32 = 22.0;
+const DAILY_RESULT_TEXT_COLOR: Color = Color::rgb(1.0, 0.8, 0.4);
+const DAILY_RESULT_VERTICAL_PADDING: Val = Val::Px(440.0);
+const DAILY_RESULT_LEFT_PADDING: Val = Val::Px(300.0);
+
+pub struct DailyPlugin;
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            start_daily
+                .run_if(in_state(GameState::NewGame))
+                .run_if(input_just_released(KeyCode::D)),
+        )
+        .add_systems(
+            OnEnter(GameState::GameOver),
+            finish_daily.run_if(resource_exists::<DailyRun>()),
+        )
+        .add_systems(OnExit(GameState::GameOver), clear_daily_result)
+        .add_systems(
+            Update,
+            record_daily_on_quit
+                .run_if(resource_exists::<DailyRun>())
+                .run_if(in_state(GameState::InGame).or_else(in_state(GameState::Paused)))
+                .run_if(
+                    input_just_pressed(KeyCode::Escape).or_else(on_event::<WindowCloseRequested>()),
+                )
+                .before(bevy::window::close_on_esc),
+        );
+    }
+}
+
+#[derive(Component)]
+struct DailyResultText;
+
+// Everything the date decides about a daily run
+#[derive(Clone)]
+pub struct DailyChallenge {
+    // Days since 1970-01-01, in UTC so everyone plays the same board on the same day
+    pub day: i64,
+    pub seed: u64,
+    pub levels: Vec<LevelData>,
+    pub ball_speed: f32,
+    pub lives: usize,
+}
+
+impl DailyChallenge {
+    pub fn for_day(day: i64) -> Self {
+        let mut rng = Rng::new(day as u64 ^ DAILY_SEED_SALT);
+        let seed = rng.next_u64() % 1_000_000_000;
+        let first_difficulty = 1 + rng.below(3);
+        let levels = (0..DAILY_LEVEL_COUNT as u32)
+            .map(|index| {
+                let level_seed = seed + index as u64;
+                let params = GeneratorParams {
+                    difficulty: first_difficulty + index,
+                    ..GeneratorParams::for_seed(level_seed)
+                };
+                generate(level_seed, &params)
+            })
+            .collect();
+        DailyChallenge {
+            day,
+            seed,
+            levels,
+            ball_speed: 1.0 + (rng.below(4) as f32 / 10.),
+            lives: 1 + rng.below(3) as usize,
+        }
+    }
+}
+
+// The daily run being played
+#[derive(Resource)]
+pub struct DailyRun(DailyChallenge);
+
+#[derive(Clone, Serialize, Deserialize)]
+struct DailyResult {
+    day: i64,
+    score: usize,
+    walls_cleared: usize,
+    // Unfinished runs were quit or are still being played
+    finished: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct DailyHistory {
+    results: Vec<DailyResult>,
+}
+
+impl DailyHistory {
+    fn load() -> Result<Self, String> {
+        load_ron(DAILY_HISTORY_PATH)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        save_ron(DAILY_HISTORY_PATH, self)
+    }
+
+    fn result_for(&self, day: i64) -> Option<&DailyResult> {
+        self.results.iter().find(|result| result.day == day)
+    }
+
+    fn record(&mut self, result: DailyResult) {
+        match self.results.iter_mut().find(|old| old.day == result.day) {
+            Some(old) => *old = result,
+            None => self.results.push(result),
+        }
+    }
+
+    // Days played in a row up to `today`. Not having played yet today doesn't break the streak.
+    fn streak(&self, today: i64) -> usize {
+        let played = |day: i64| self.result_for(day).is_some();
+        let mut day = if played(today) { today } else { today - 1 };
+        let mut streak = 0;
+        while played(day) {
+            streak += 1;
+            day -= 1;
+        }
+        streak
+    }
+}
+
+pub fn today() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| (elapsed.as_secs() / 86_400) as i64)
+}
+
+// Year, month and day of a day number, from Howard Hinnant's `civil_from_days`
+pub fn civil_from_days(day: i64) -> (i64, u32, u32) {
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day_of_month = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day_of_month)
+}
+
+pub fn date_string(day: i64) -> String {
+    let (year, month, day_of_month) = civil_from_days(day);
+    format!("{year:04}-{month:02}-{day_of_month:02}")
+}
+
+// The start screen line for the daily challenge
+pub fn menu_option() -> String {
+    let today = today();
+    let history = DailyHistory::load().unwrap_or_default();
+    match history.result_for(today) {
+        Some(result) => format!(
+            "Daily done: {} points, streak {}",
+            result.score,
+            history.streak(today)
+        ),
+        None => "D: daily challenge".to_string(),
+    }
+}
+
+// One line that sums up a run, for players to paste and compare
+fn share_string(challenge: &DailyChallenge, result: &DailyResult, streak: usize) -> String {
+    let walls: String = (0..DAILY_LEVEL_COUNT)
+        .map(|index| {
+            if index < result.walls_cleared {
+                '#'
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!(
+        "Breakout daily {} (seed {}) [{walls}] {} points, speed x{:.1}, {} lives, streak {streak}",
+        date_string(challenge.day),
+        challenge.seed,
+        result.score,
+        challenge.ball_speed,
+        challenge.lives,
+    )
+}
+
+// D on the start screen plays today's challenge, if it hasn't been played yet
+#[allow(clippy::too_many_arguments)]
+fn start_daily(
+    mut commands: Commands,
+    start_query: Query<Entity, With<StartGameOverlay>>,
+    brick_query: Query<Entity, With<Brick>>,
+    paddle_query: Query<Entity, With<Paddle>>,
+    mut ball_query: BallStateQuery,
+    mut current_level: ResMut<CurrentLevel>,
+    mut next_state: ResMut<NextState<GameState>>,
+    config: Res<GameConfig>,
+) {
+    let today = today();
+    let mut history = match DailyHistory::load() {
+        Ok(history) => history,
+        Err(error) => {
+            error!("Could not read the daily history: {error}");
+            return;
+        }
+    };
+    if history.result_for(today).is_some() {
+        return;
+    }
+
+    // The attempt counts from the moment it starts, so quitting doesn't buy a retry
+    history.record(DailyResult {
+        day: today,
+        score: 0,
+        walls_cleared: 0,
+        finished: false,
+    });
+    if let Err(error) = history.save() {
+        error!("Could not write the daily history: {error}");
+        return;
+    }
+
+    let challenge = DailyChallenge::for_day(today);
+    for start_ent in &start_query {
+        commands.entity(start_ent).despawn();
+    }
+    for brick in &brick_query {
+        commands.entity(brick).despawn();
+    }
+    // Dailies are single player so every result compares with every other
+    reset_players(
+        &mut commands,
+        &CoopSettings::default(),
+        &paddle_query,
+        &config,
+    );
+    commands.insert_resource(Lives {
+        lives_left: vec![challenge.lives],
+    });
+    reset_ball(&mut ball_query, &config);
+    ball_query.single_mut().1 .0 *= challenge.ball_speed;
+
+    current_level.0 = challenge.levels[0].clone();
+    spawn_bricks(&mut commands, &current_level.0, &config.layout);
+    commands.insert_resource(LevelSet {
+        levels: challenge.levels.clone(),
+        ends: true,
+    });
+    commands.insert_resource(GameMode::Classic);
+    commands.insert_resource(CatchOption::default());
+    commands.insert_resource(DailyRun(challenge));
+    next_state.set(GameState::InGame);
+}
+
+fn record_daily(run: &DailyRun, scoreboard: &Scoreboard, level: &Level, finished: bool) -> String {
+    let result = DailyResult {
+        day: run.0.day,
+        score: scoreboard.score,
+        walls_cleared: level.index.min(DAILY_LEVEL_COUNT),
+        finished,
+    };
+    let mut history = DailyHistory::load().unwrap_or_else(|error| {
+        error!("Could not read the daily history: {error}");
+        DailyHistory::default()
+    });
+    history.record(result.clone());
+    if let Err(error) = history.save() {
+        error!("Could not write the daily history: {error}");
+    }
+    share_string(&run.0, &result, history.streak(run.0.day))
+}
+
+// The run ends with the game, win or lose, and the board goes back to the classic wall
+fn finish_daily(
+    mut commands: Commands,
+    run: Res<DailyRun>,
+    scoreboard: Res<Scoreboard>,
+    level: Res<Level>,
+    brick_query: Query<Entity, With<Brick>>,
+    mut current_level: ResMut<CurrentLevel>,
+    config: Res<GameConfig>,
+) {
+    let result = record_daily(&run, &scoreboard, &level, true);
+    info!("{result}");
+    if let Err(error) = fs::write(DAILY_RESULT_PATH, format!("{result}\n")) {
+        error!("Could not write the daily result: {error}");
+    }
+
+    commands.remove_resource::<DailyRun>();
+    commands.remove_resource::<LevelSet>();
+    current_level.0 = LevelData::classic(&config.layout);
+    for brick in &brick_query {
+        commands.entity(brick).despawn();
+    }
+    spawn_bricks(&mut commands, &current_level.0, &config.layout);
+
+    commands.spawn((
+        TextBundle::from_section(
+            format!("{result}\nSaved to {DAILY_RESULT_PATH}"),
+            TextStyle {
+                font_size: DAILY_RESULT_FONT_SIZE,
+                color: DAILY_RESULT_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: DAILY_RESULT_VERTICAL_PADDING,
+            left: DAILY_RESULT_LEFT_PADDING,
+            ..default()
+        }),
+        DailyResultText,
+    ));
+}
+
+fn clear_daily_result(mut commands: Commands, query: Query<Entity, With<DailyResultText>>) {
+    for text in &query {
+        commands.entity(text).despawn();
+    }
+}
+
+// Quitting mid-run keeps the score reached so far
+fn record_daily_on_quit(run: Res<DailyRun>, scoreboard: Res<Scoreboard>, level: Res<Level>) {
+    record_daily(&run, &scoreboard, &level, false);
+}
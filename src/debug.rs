@@ -0,0 +1,392 @@
+use std::path::Path;
+
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    ecs::system::CommandQueue,
+    input::InputSystem,
+    prelude::*,
+    window::ReceivedCharacter,
+};
+
+use crate::boss::BossPart;
+use crate::config::GameConfig;
+use crate::level::{CurrentLevel, LevelData};
+use crate::multiball::{extra_ball_bundle, BallAssets};
+use crate::net::NetSession;
+use crate::replay::{Playback, Recording};
+use crate::{
+    spawn_bricks, Ball, Brick, Collider, GameState, Lives, Velocity, BALL_STARTING_POSITION,
+    INITIAL_BALL_DIRECTION,
+};
+
+// Debug overlay
+const OVERLAY_FONT_SIZE: f32 = 18.0;
+const OVERLAY_TEXT_COLOR: Color = Color::rgb(1.0, 1.0, 0.4);
+const OVERLAY_TOP: Val = Val::Px(40.0);
+const OVERLAY_RIGHT: Val = Val::Px(8.0);
+const COLLIDER_OUTLINE_COLOR: Color = Color::rgb(1.0, 0.2, 1.0);
+const VELOCITY_LINE_COLOR: Color = Color::rgb(1.0, 1.0, 0.4);
+// Velocity lines show where a ball will be this many seconds from now
+const VELOCITY_LINE_SECONDS: f32 = 0.25;
+
+// Console
+const CONSOLE_FONT_SIZE: f32 = 18.0;
+const CONSOLE_TEXT_COLOR: Color = Color::rgb(0.8, 1.0, 0.8);
+const CONSOLE_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.85);
+const CONSOLE_PADDING: Val = Val::Px(8.0);
+// Lines of earlier commands and their replies kept on screen
+const CONSOLE_HISTORY: usize = 8;
+const MAX_TIME_SCALE: f32 = 8.0;
+const CONSOLE_HELP: &str = "spawn_ball, set_lives <n>, load_level <file>, god_mode, \
+     timescale <x>, help";
+
+pub struct DebugPlugin;
+
+impl Plugin for DebugPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+        app.init_resource::<DebugOverlay>()
+            .init_resource::<Console>()
+            .init_resource::<TimeScale>()
+            .add_systems(Startup, (spawn_overlay_text, spawn_console_text))
+            // Ahead of everything else, so the console can keep its typing from the game
+            .add_systems(PreUpdate, edit_console.after(InputSystem))
+            .add_systems(
+                Update,
+                (
+                    run_console_command.run_if(|console: Res<Console>| console.submitted.is_some()),
+                    update_console_text,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (toggle_overlay, (draw_debug_shapes, update_overlay_text))
+                    .chain()
+                    .run_if(|console: Res<Console>| !console.open),
+            );
+    }
+}
+
+// F3 shows collider outlines, ball velocities, frame rate, game state and entity counts
+#[derive(Resource, Default)]
+struct DebugOverlay {
+    visible: bool,
+}
+
+#[derive(Component)]
+struct OverlayText;
+
+// The developer console, opened with the backquote key. While it is open it takes the keyboard.
+#[derive(Resource, Default)]
+struct Console {
+    open: bool,
+    line: String,
+    submitted: Option<String>,
+    history: Vec<String>,
+}
+
+#[derive(Component)]
+struct ConsoleText;
+
+// Set from the console: the floor and boss shots cost no lives
+#[derive(Resource)]
+pub struct GodMode;
+
+// Set from the console: how many gameplay ticks run each frame, on average
+#[derive(Resource)]
+pub struct TimeScale {
+    scale: f32,
+    // Parts of a tick carried over to the next frame
+    owed: f32,
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale {
+            scale: 1.0,
+            owed: 0.0,
+        }
+    }
+}
+
+impl TimeScale {
+    pub fn ticks_this_frame(&mut self) -> u32 {
+        self.owed += self.scale;
+        let ticks = self.owed.floor();
+        self.owed -= ticks;
+        ticks as u32
+    }
+}
+
+fn spawn_overlay_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: OVERLAY_FONT_SIZE,
+                color: OVERLAY_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: OVERLAY_TOP,
+            right: OVERLAY_RIGHT,
+            ..default()
+        }),
+        OverlayText,
+    ));
+}
+
+fn spawn_console_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle {
+            visibility: Visibility::Hidden,
+            background_color: CONSOLE_BACKGROUND.into(),
+            ..TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: CONSOLE_FONT_SIZE,
+                    color: CONSOLE_TEXT_COLOR,
+                    ..default()
+                },
+            )
+        }
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(0.0),
+            left: Val::Px(0.0),
+            right: Val::Px(0.0),
+            padding: UiRect::all(CONSOLE_PADDING),
+            ..default()
+        }),
+        ConsoleText,
+    ));
+}
+
+fn toggle_overlay(keyboard_input: Res<Input<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        overlay.visible = !overlay.visible;
+    }
+}
+
+fn draw_debug_shapes(
+    overlay: Res<DebugOverlay>,
+    mut gizmos: Gizmos,
+    collider_query: Query<&Transform, With<Collider>>,
+    ball_query: Query<(&Transform, &Velocity), With<Ball>>,
+) {
+    if !overlay.visible {
+        return;
+    }
+    for transform in &collider_query {
+        gizmos.rect_2d(
+            transform.translation.truncate(),
+            0.0,
+            transform.scale.truncate(),
+            COLLIDER_OUTLINE_COLOR,
+        );
+    }
+    for (transform, velocity) in &ball_query {
+        let position = transform.translation.truncate();
+        gizmos.line_2d(
+            position,
+            position + velocity.0 * VELOCITY_LINE_SECONDS,
+            VELOCITY_LINE_COLOR,
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_overlay_text(
+    overlay: Res<DebugOverlay>,
+    diagnostics: Res<DiagnosticsStore>,
+    game_state: Res<State<GameState>>,
+    entity_query: Query<()>,
+    collider_query: Query<(), With<Collider>>,
+    ball_query: Query<(), With<Ball>>,
+    sprite_query: Query<(), With<Sprite>>,
+    node_query: Query<(), With<Node>>,
+    mut text_query: Query<&mut Text, With<OverlayText>>,
+) {
+    let value = if overlay.visible {
+        let fps = diagnostics
+            .get(FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.smoothed())
+            .unwrap_or_default();
+        format!(
+            "FPS: {fps:.0}\nState: {:?}\nEntities: {}\nColliders: {}\nBalls: {}\nSprites: {}\n\
+             UI nodes: {}",
+            game_state.get(),
+            entity_query.iter().count(),
+            collider_query.iter().count(),
+            ball_query.iter().count(),
+            sprite_query.iter().count(),
+            node_query.iter().count(),
+        )
+    } else {
+        String::new()
+    };
+    for mut text in &mut text_query {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+fn edit_console(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut console: ResMut<Console>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Grave) {
+        console.open = !console.open;
+        console.line.clear();
+        characters.clear();
+        keyboard_input.reset_all();
+        return;
+    }
+    if !console.open {
+        return;
+    }
+    for event in characters.iter() {
+        if !event.char.is_control() && event.char != '`' {
+            console.line.push(event.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        console.line.pop();
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.line);
+        console.submitted = Some(line);
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        console.open = false;
+        console.line.clear();
+    }
+    // Nothing else sees keys pressed into the console, so typing doesn't play the game
+    keyboard_input.reset_all();
+}
+
+fn run_console_command(world: &mut World) {
+    let Some(line) = world.resource_mut::<Console>().submitted.take() else {
+        return;
+    };
+    let reply = match run_command(world, line.trim()) {
+        Ok(reply) => reply,
+        Err(error) => format!("error: {error}"),
+    };
+    let mut console = world.resource_mut::<Console>();
+    console.history.push(format!("> {line}"));
+    console.history.push(reply);
+    let excess = console.history.len().saturating_sub(CONSOLE_HISTORY);
+    console.history.drain(..excess);
+}
+
+// Carries out one console command, returning what to say back
+fn run_command(world: &mut World, line: &str) -> Result<String, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let argument = words.next();
+    if command.is_empty() || command == "help" {
+        return Ok(CONSOLE_HELP.to_string());
+    }
+    // A cheat in either of these would be out of step with the other player, or the recording
+    if world.contains_resource::<NetSession>() {
+        return Err("the console can't change an online game".to_string());
+    }
+    if world.contains_resource::<Recording>() || world.contains_resource::<Playback>() {
+        return Err("the console can't change a recorded or replayed game".to_string());
+    }
+
+    match command {
+        "spawn_ball" => {
+            let assets = world.resource::<BallAssets>().clone();
+            let speed = world.resource::<GameConfig>().ball_speed;
+            world.spawn(extra_ball_bundle(
+                &assets,
+                BALL_STARTING_POSITION,
+                INITIAL_BALL_DIRECTION.normalize() * speed,
+                None,
+            ));
+            Ok("Spawned a ball".to_string())
+        }
+        "set_lives" => {
+            let lives = argument
+                .and_then(|lives| lives.parse::<usize>().ok())
+                .filter(|lives| *lives > 0)
+                .ok_or("set_lives needs a number above 0")?;
+            for pool in &mut world.resource_mut::<Lives>().lives_left {
+                *pool = lives;
+            }
+            Ok(format!("Lives set to {lives}"))
+        }
+        "load_level" => {
+            let path = argument.ok_or("load_level needs a file")?;
+            let level = LevelData::load(Path::new(path))?;
+            if let Some(problem) = level.validate().first() {
+                return Err(format!("{path} can't be played: {problem}"));
+            }
+            let layout = world.resource::<GameConfig>().layout.clone();
+            if let Err(problem) = layout.check_level(&level) {
+                return Err(format!("{path} doesn't fit the board: {problem}"));
+            }
+            // Only once the level is known to be good, so a bad one leaves the board as it was
+            let mut brick_query =
+                world.query_filtered::<Entity, (With<Brick>, Without<BossPart>)>();
+            let bricks: Vec<Entity> = brick_query.iter(world).collect();
+            for brick in bricks {
+                world.despawn(brick);
+            }
+            let mut queue = CommandQueue::default();
+            spawn_bricks(&mut Commands::new(&mut queue, world), &level, &layout);
+            queue.apply(world);
+            world.insert_resource(CurrentLevel(level));
+            Ok(format!("Loaded {path}"))
+        }
+        "god_mode" => {
+            if world.remove_resource::<GodMode>().is_some() {
+                Ok("God mode off".to_string())
+            } else {
+                world.insert_resource(GodMode);
+                Ok("God mode on".to_string())
+            }
+        }
+        "timescale" => {
+            let scale = argument
+                .and_then(|scale| scale.parse::<f32>().ok())
+                .filter(|scale| *scale > 0.0 && *scale <= MAX_TIME_SCALE)
+                .ok_or(format!(
+                    "timescale needs a number above 0 and at most {MAX_TIME_SCALE}"
+                ))?;
+            world.resource_mut::<TimeScale>().scale = scale;
+            // Effects and particles keep pace with the gameplay
+            world.resource_mut::<Time>().set_relative_speed(scale);
+            Ok(format!("Time scale set to {scale}"))
+        }
+        _ => Err(format!("unknown command {command}, try help")),
+    }
+}
+
+fn update_console_text(
+    console: Res<Console>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    for (mut text, mut visibility) in &mut text_query {
+        *visibility = if console.open {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+        let mut lines = console.history.clone();
+        lines.push(format!("> {}_", console.line));
+        text.sections[0].value = lines.join("\n");
+    }
+}
@@ -0,0 +1,455 @@
+use std::path::Path;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::config::{BoardLayout, GameConfig};
+use crate::level::{
+    cell_at, cell_translation, BrickBehaviour, BrickData, CurrentLevel, LevelData,
+    CUSTOM_LEVEL_PATH,
+};
+use crate::{
+    reset_ball, reset_players, spawn_bricks, spawn_start_overlay, BallStateQuery, Brick, BrickKind,
+    CoopSettings, GameState, Paddle, StartGameOverlay,
+};
+
+// Editor
+// Rows of the grid that can be edited, if that many fit above the paddles
+const EDITOR_GRID_ROWS: u32 = 12;
+const EDITOR_MAX_HIT_POINTS: u32 = 5;
+const EDITOR_GRID_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.15);
+const EDITOR_HOVER_COLOR: Color = Color::YELLOW;
+const EDITOR_PALETTE: [Color; 6] = [
+    Color::rgb(0.25, 0.75, 0.25),
+    Color::rgb(0.65, 0.75, 0.65),
+    Color::rgb(0.9, 0.3, 0.3),
+    Color::rgb(0.3, 0.5, 0.9),
+    Color::rgb(0.9, 0.8, 0.3),
+    Color::GRAY,
+];
+// The behaviours B cycles through, in order
+const EDITOR_BEHAVIOURS: [BrickBehaviour; 4] = [
+    BrickBehaviour::Static,
+    BrickBehaviour::Moving {
+        range: 2,
+        speed: 80.0,
+    },
+    BrickBehaviour::Descending { every: 4 },
+    BrickBehaviour::Regrowing { delay: 6.0 },
+];
+
+// Editor text
+const EDITOR_FONT_SIZE: f32 = 18.5;
+const EDITOR_TEXT_PADDING: Val = Val::Px(8.0);
+const EDITOR_TEXT_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
+const EDITOR_PROBLEM_COLOR: Color = Color::rgb(1.0, 0.5, 0.5);
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, enter_editor.run_if(in_state(GameState::NewGame)))
+            .add_systems(
+                Update,
+                (
+                    edit_level,
+                    (draw_editor_grid, update_editor_bricks, update_editor_text),
+                    leave_editor,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Editor)),
+            )
+            .add_systems(
+                Update,
+                stop_playtest
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(resource_exists::<Playtest>()),
+            );
+    }
+}
+
+#[derive(Component)]
+struct EditorBrick;
+
+#[derive(Component)]
+struct EditorText;
+
+// Present while a level from the editor is being played
+#[derive(Resource)]
+pub struct Playtest;
+
+// The level being edited, with its history and the brick that clicks will place
+#[derive(Resource)]
+struct Editor {
+    level: LevelData,
+    undo: Vec<LevelData>,
+    redo: Vec<LevelData>,
+    color: usize,
+    kind: BrickKind,
+    hit_points: u32,
+    behaviour: usize,
+    hovered: Option<(u32, u32)>,
+    status: String,
+}
+
+impl Editor {
+    fn new(level: LevelData) -> Self {
+        Editor {
+            level,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            color: 0,
+            kind: BrickKind::Normal,
+            hit_points: 1,
+            behaviour: 0,
+            hovered: None,
+            status: String::new(),
+        }
+    }
+
+    // Applies a change to the level, remembering the old one if anything changed
+    fn edit(&mut self, change: impl FnOnce(&mut LevelData)) {
+        let before = self.level.clone();
+        change(&mut self.level);
+        if self.level != before {
+            // Once edited, a generated level no longer matches its seed or difficulty
+            self.level.seed = None;
+            self.level.difficulty = None;
+            self.undo.push(before);
+            self.redo.clear();
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(level) = self.undo.pop() {
+            self.redo.push(std::mem::replace(&mut self.level, level));
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(level) = self.redo.pop() {
+            self.undo.push(std::mem::replace(&mut self.level, level));
+        }
+    }
+
+    fn tool_brick(&self, column: u32, row: u32) -> BrickData {
+        BrickData {
+            column,
+            row,
+            color: EDITOR_PALETTE[self.color],
+            kind: self.kind,
+            hit_points: self.hit_points,
+            behaviour: EDITOR_BEHAVIOURS[self.behaviour],
+        }
+    }
+}
+
+fn enter_editor(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    start_query: Query<Entity, With<StartGameOverlay>>,
+    brick_query: Query<Entity, With<Brick>>,
+    editor: Option<Res<Editor>>,
+    current_level: Res<CurrentLevel>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard_input.just_released(KeyCode::E) {
+        return;
+    }
+    for start_ent in start_query.iter().chain(&brick_query) {
+        commands.entity(start_ent).despawn();
+    }
+
+    // Pick up where the last edit left off, or else from the saved custom level
+    if editor.is_none() {
+        let level = LevelData::load(Path::new(CUSTOM_LEVEL_PATH))
+            .unwrap_or_else(|_| current_level.0.clone());
+        commands.insert_resource(Editor::new(level));
+    }
+    spawn_editor_text(&mut commands);
+    next_state.set(GameState::Editor);
+}
+
+fn spawn_editor_text(commands: &mut Commands) {
+    commands.spawn((
+        TextBundle::from_sections([
+            TextSection::new(
+                "",
+                TextStyle {
+                    font_size: EDITOR_FONT_SIZE,
+                    color: EDITOR_TEXT_COLOR,
+                    ..default()
+                },
+            ),
+            TextSection::from_style(TextStyle {
+                font_size: EDITOR_FONT_SIZE,
+                color: EDITOR_PROBLEM_COLOR,
+                ..default()
+            }),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: EDITOR_TEXT_PADDING,
+            left: EDITOR_TEXT_PADDING,
+            ..default()
+        }),
+        EditorText,
+    ));
+}
+
+fn cursor_world_position(
+    window_query: &Query<&Window, With<PrimaryWindow>>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let window = window_query.get_single().ok()?;
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, window.cursor_position()?)
+}
+
+fn edit_level(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    mut editor: ResMut<Editor>,
+    config: Res<GameConfig>,
+) {
+    let layout = &config.layout;
+    let hovered = cursor_world_position(&window_query, &camera_query)
+        .and_then(|position| cell_at(layout, position, editor_rows(layout)));
+    if editor.hovered != hovered {
+        editor.hovered = hovered;
+    }
+
+    if let Some((column, row)) = hovered {
+        if mouse_input.pressed(MouseButton::Left) {
+            let brick = editor.tool_brick(column, row);
+            editor.edit(|level| match level.brick_at(column, row) {
+                Some(index) => level.bricks[index] = brick,
+                None => level.bricks.push(brick),
+            });
+        }
+        if mouse_input.pressed(MouseButton::Right) {
+            editor.edit(|level| level.bricks.retain(|b| (b.column, b.row) != (column, row)));
+        }
+        if keyboard_input.just_pressed(KeyCode::R) {
+            let color = EDITOR_PALETTE[editor.color];
+            editor.edit(|level| {
+                if let Some(index) = level.brick_at(column, row) {
+                    level.bricks[index].color = color;
+                }
+            });
+        }
+    }
+
+    let palette_keys = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+    ];
+    if let Some(color) = palette_keys
+        .iter()
+        .position(|key| keyboard_input.just_pressed(*key))
+    {
+        editor.color = color;
+    }
+    if keyboard_input.just_pressed(KeyCode::K) {
+        editor.kind = match editor.kind {
+            BrickKind::Normal => BrickKind::Unbreakable,
+            BrickKind::Unbreakable => BrickKind::Normal,
+        };
+    }
+    if keyboard_input.just_pressed(KeyCode::B) {
+        editor.behaviour = (editor.behaviour + 1) % EDITOR_BEHAVIOURS.len();
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        editor.hit_points = (editor.hit_points + 1).min(EDITOR_MAX_HIT_POINTS);
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        editor.hit_points = (editor.hit_points - 1).max(1);
+    }
+
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl && keyboard_input.just_pressed(KeyCode::Z) {
+        editor.undo();
+    }
+    if ctrl && keyboard_input.just_pressed(KeyCode::Y) {
+        editor.redo();
+    }
+    if keyboard_input.just_pressed(KeyCode::S) {
+        editor.status = if !editor.level.validate().is_empty() {
+            "Fix the problems below before saving".to_string()
+        } else {
+            match editor.level.save(Path::new(CUSTOM_LEVEL_PATH)) {
+                Ok(()) => format!("Saved to {CUSTOM_LEVEL_PATH}"),
+                Err(error) => format!("Could not save: {error}"),
+            }
+        };
+    }
+}
+
+fn editor_rows(layout: &BoardLayout) -> u32 {
+    EDITOR_GRID_ROWS.min(layout.level_rows())
+}
+
+fn draw_editor_grid(mut gizmos: Gizmos, editor: Res<Editor>, config: Res<GameConfig>) {
+    let layout = &config.layout;
+    for row in 0..editor_rows(layout) {
+        for column in 0..layout.grid_columns {
+            let color = if editor.hovered == Some((column, row)) {
+                EDITOR_HOVER_COLOR
+            } else {
+                EDITOR_GRID_COLOR
+            };
+            gizmos.rect_2d(
+                cell_translation(layout, column, row).truncate(),
+                0.,
+                layout.cell_size,
+                color,
+            );
+        }
+    }
+}
+
+// Redraws the level whenever it changes
+fn update_editor_bricks(
+    mut commands: Commands,
+    editor: Res<Editor>,
+    editor_brick_query: Query<Entity, With<EditorBrick>>,
+    config: Res<GameConfig>,
+) {
+    if !editor.is_changed() {
+        return;
+    }
+    for editor_brick in &editor_brick_query {
+        commands.entity(editor_brick).despawn();
+    }
+    for brick in &editor.level.bricks {
+        commands.spawn((
+            SpriteBundle {
+                transform: Transform {
+                    translation: cell_translation(&config.layout, brick.column, brick.row),
+                    scale: config.layout.brick_size(),
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: brick.color,
+                    ..default()
+                },
+                ..default()
+            },
+            EditorBrick,
+        ));
+    }
+}
+
+fn update_editor_text(editor: Res<Editor>, mut query: Query<&mut Text, With<EditorText>>) {
+    if !editor.is_changed() {
+        return;
+    }
+    let tool = match editor.kind {
+        BrickKind::Normal => format!("normal, {} hit points", editor.hit_points),
+        BrickKind::Unbreakable => "unbreakable".to_string(),
+    };
+    let behaviour = EDITOR_BEHAVIOURS[editor.behaviour].name();
+    let hovered = editor
+        .hovered
+        .and_then(|(column, row)| editor.level.brick_at(column, row))
+        .map(|index| {
+            let brick = &editor.level.bricks[index];
+            match brick.kind {
+                BrickKind::Normal => format!(
+                    "column {}, row {}: normal, {} hit points, {}",
+                    brick.column,
+                    brick.row,
+                    brick.hit_points,
+                    brick.behaviour.name()
+                ),
+                BrickKind::Unbreakable => format!(
+                    "column {}, row {}: unbreakable, {}",
+                    brick.column,
+                    brick.row,
+                    brick.behaviour.name()
+                ),
+            }
+        })
+        .unwrap_or_default();
+    let problems = editor.level.validate().join("\n");
+
+    for mut text in &mut query {
+        text.sections[0].value = format!(
+            "LEVEL EDITOR\n\
+             Left click: place, Right click: erase, R: recolor\n\
+             1-6: color ({}), K: kind, Up/Down: hit points, B: behaviour\n\
+             Placing: {tool}, {behaviour}\n\
+             Ctrl+Z/Ctrl+Y: undo/redo, S: save, P: playtest, TAB: menu\n\
+             {hovered}\n{}\n",
+            editor.color + 1,
+            editor.status
+        );
+        text.sections[1].value = problems.clone();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn leave_editor(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut editor: ResMut<Editor>,
+    editor_brick_query: Query<Entity, With<EditorBrick>>,
+    editor_text_query: Query<Entity, With<EditorText>>,
+    paddle_query: Query<Entity, With<Paddle>>,
+    mut ball_query: BallStateQuery,
+    mut current_level: ResMut<CurrentLevel>,
+    coop: Res<CoopSettings>,
+    mut next_state: ResMut<NextState<GameState>>,
+    config: Res<GameConfig>,
+) {
+    let playtest = keyboard_input.just_released(KeyCode::P);
+    let back_to_menu = keyboard_input.just_released(KeyCode::Tab);
+    if !playtest && !back_to_menu {
+        return;
+    }
+    if playtest && !editor.level.validate().is_empty() {
+        editor.status = "Fix the problems below before playtesting".to_string();
+        return;
+    }
+
+    for editor_ent in editor_brick_query.iter().chain(&editor_text_query) {
+        commands.entity(editor_ent).despawn();
+    }
+    if playtest {
+        current_level.0 = editor.level.clone();
+        commands.insert_resource(Playtest);
+        reset_players(&mut commands, &coop, &paddle_query, &config);
+        reset_ball(&mut ball_query, &config);
+        next_state.set(GameState::InGame);
+    } else {
+        spawn_start_overlay(&mut commands, &coop);
+        next_state.set(GameState::NewGame);
+    }
+    spawn_bricks(&mut commands, &current_level.0, &config.layout);
+}
+
+// F2 during a playtest goes straight back to editing
+fn stop_playtest(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    brick_query: Query<Entity, With<Brick>>,
+    mut editor: ResMut<Editor>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard_input.just_released(KeyCode::F2) {
+        return;
+    }
+    for brick in &brick_query {
+        commands.entity(brick).despawn();
+    }
+    commands.remove_resource::<Playtest>();
+    spawn_editor_text(&mut commands);
+    // Marks the editor changed so its bricks and text are drawn again
+    editor.set_changed();
+    next_state.set(GameState::Editor);
+}
@@ -0,0 +1,206 @@
+use bevy::{prelude::*, render::texture::DEFAULT_IMAGE_HANDLE};
+
+use crate::net::Resimulating;
+use crate::settings::Settings;
+use crate::{run_gameplay_tick, Broken, CollisionEvent, ExplosionEvent, Lives, Paddle, Surface};
+
+// Screen shake
+// Shake grows with the square of trauma, so small knocks barely show and big ones rattle
+const SHAKE_MAX_OFFSET: f32 = 14.0;
+const SHAKE_MAX_ANGLE: f32 = 0.03;
+// Trauma lost per second
+const SHAKE_DECAY: f32 = 1.5;
+const COLLISION_TRAUMA: f32 = 0.08;
+const EXPLOSION_TRAUMA: f32 = 0.2;
+const LIFE_LOST_TRAUMA: f32 = 0.7;
+
+// Hit-stop
+const EXPLOSION_HIT_STOP: f32 = 0.04;
+const LIFE_LOST_HIT_STOP: f32 = 0.15;
+
+// Brick flash
+const FLASH_TIME: f32 = 0.12;
+
+// Paddle squash
+// The paddle flattens on a hit, then wobbles back through a stretch as it settles
+const SQUASH_AMOUNT: f32 = 0.25;
+const SQUASH_DAMPING: f32 = 9.0;
+const SQUASH_FREQUENCY: f32 = 30.0;
+const SQUASH_TIME: f32 = 0.5;
+
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraEffects>().add_systems(
+            Update,
+            (
+                (react_to_hits, flash_broken_bricks, squash_paddles).after(run_gameplay_tick),
+                (shake_camera, fade_brick_flashes, animate_paddle_squash),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct CameraEffects {
+    trauma: f32,
+    // Real seconds the simulation stays frozen for
+    hit_stop: f32,
+    // Lives left last frame, to tell when one is lost
+    lives_left: usize,
+}
+
+// Whether hit-stop has let go of the simulation
+pub fn simulation_running(effects: Res<CameraEffects>) -> bool {
+    effects.hit_stop <= 0.0
+}
+
+#[derive(Component)]
+struct BrickFlash {
+    time_left: f32,
+}
+
+#[derive(Component)]
+struct PaddleSquash {
+    time: f32,
+}
+
+fn react_to_hits(
+    mut collisions: EventReader<CollisionEvent>,
+    mut explosions: EventReader<ExplosionEvent>,
+    lives: Res<Lives>,
+    settings: Res<Settings>,
+    mut effects: ResMut<CameraEffects>,
+    resimulating: Option<Res<Resimulating>>,
+) {
+    let shake = settings.screen_shake.factor();
+    let hit_stop = settings.hit_stop.factor();
+    let mut trauma = 0.0;
+    let mut stop: f32 = 0.0;
+    let replayed = resimulating.as_deref().copied().unwrap_or_default();
+
+    trauma += collisions.iter().skip(replayed.collisions).count() as f32 * COLLISION_TRAUMA;
+    if explosions.iter().skip(replayed.explosions).count() > 0 {
+        trauma += EXPLOSION_TRAUMA;
+        stop = stop.max(EXPLOSION_HIT_STOP);
+    }
+    // Fresh lives at the start of a game don't count as anything
+    let lives_left = lives.lives_left.iter().sum();
+    if !lives.is_added() && lives_left < effects.lives_left {
+        trauma += LIFE_LOST_TRAUMA;
+        stop = stop.max(LIFE_LOST_HIT_STOP);
+    }
+    effects.lives_left = lives_left;
+
+    effects.trauma = (effects.trauma + trauma * shake).min(1.0);
+    effects.hit_stop = effects.hit_stop.max(stop * hit_stop);
+}
+
+fn shake_camera(
+    time: Res<Time>,
+    mut effects: ResMut<CameraEffects>,
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+) {
+    let dt = time.delta_seconds();
+    effects.hit_stop = (effects.hit_stop - dt).max(0.0);
+    effects.trauma = (effects.trauma - SHAKE_DECAY * dt).max(0.0);
+
+    // Sums of sines at unrelated frequencies wander about without repeating visibly
+    let shake = effects.trauma * effects.trauma;
+    let t = time.elapsed_seconds();
+    let wobble = |a: f32, b: f32| ((t * a).sin() + (t * b).sin()) / 2.0;
+    for mut transform in &mut camera_query {
+        transform.translation.x = SHAKE_MAX_OFFSET * shake * wobble(47.0, 71.0);
+        transform.translation.y = SHAKE_MAX_OFFSET * shake * wobble(53.0, 89.0);
+        transform.rotation = Quat::from_rotation_z(SHAKE_MAX_ANGLE * shake * wobble(37.0, 61.0));
+    }
+}
+
+// A broken brick turns white, then fades away before it is gone
+fn flash_broken_bricks(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    mut broken_query: Query<(Entity, &mut Sprite, &mut Handle<Image>), Added<Broken>>,
+) {
+    let strength = settings.brick_flash.factor();
+    for (brick, mut sprite, mut texture) in &mut broken_query {
+        if strength <= 0.0 {
+            commands.entity(brick).despawn();
+            continue;
+        }
+        // Plain white, whatever the theme drew it with
+        *texture = DEFAULT_IMAGE_HANDLE.typed();
+        sprite.color = Color::WHITE.with_a(strength);
+        commands.entity(brick).insert(BrickFlash {
+            time_left: FLASH_TIME,
+        });
+    }
+}
+
+fn fade_brick_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut flash_query: Query<(Entity, &mut BrickFlash, &mut Sprite)>,
+) {
+    for (flash, mut brick_flash, mut sprite) in &mut flash_query {
+        brick_flash.time_left -= time.delta_seconds();
+        if brick_flash.time_left <= 0.0 {
+            commands.entity(flash).despawn();
+            continue;
+        }
+        let left = brick_flash.time_left / FLASH_TIME;
+        sprite.color.set_a(settings.brick_flash.factor() * left);
+    }
+}
+
+// A paddle the ball struck gets squashed. Collisions lie on the edge of what was struck, so a
+// paddle's are the ones inside its own rectangle.
+fn squash_paddles(
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    settings: Res<Settings>,
+    paddle_query: Query<(Entity, &Transform), With<Paddle>>,
+    resimulating: Option<Res<Resimulating>>,
+) {
+    let replayed = resimulating.map_or(0, |replayed| replayed.collisions);
+    for collision in collisions.iter().skip(replayed) {
+        if collision.surface != Surface::Paddle || settings.paddle_squash.factor() <= 0.0 {
+            continue;
+        }
+        let struck = paddle_query.iter().find(|(_, transform)| {
+            let half_size = transform.scale.truncate() / 2.0;
+            let center = transform.translation.truncate();
+            collision.position.cmpge(center - half_size).all()
+                && collision.position.cmple(center + half_size).all()
+        });
+        if let Some((paddle, _)) = struck {
+            commands.entity(paddle).insert(PaddleSquash { time: 0.0 });
+        }
+    }
+}
+
+// Squash only changes how the paddle is drawn. Its transform is what the ball collides with, so
+// it is left alone.
+fn animate_paddle_squash(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut paddle_query: Query<(Entity, &mut PaddleSquash, &mut Sprite)>,
+) {
+    for (paddle, mut squash, mut sprite) in &mut paddle_query {
+        squash.time += time.delta_seconds();
+        if squash.time >= SQUASH_TIME {
+            sprite.custom_size = Some(Vec2::ONE);
+            commands.entity(paddle).remove::<PaddleSquash>();
+            continue;
+        }
+        let amount = SQUASH_AMOUNT
+            * settings.paddle_squash.factor()
+            * (-SQUASH_DAMPING * squash.time).exp()
+            * (SQUASH_FREQUENCY * squash.time).cos();
+        sprite.custom_size = Some(Vec2::new(1.0 + amount, 1.0 - amount));
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+
+use crate::config::{BoardLayout, GameConfig};
+use crate::level::{BrickBehaviour, BrickData, CurrentLevel, LevelData};
+use crate::{spawn_bricks, Brick, BrickKind, GameState};
+
+// Generator
+const GENERATOR_MIN_ROWS: u32 = 3;
+const GENERATOR_MAX_ROWS: u32 = 8;
+pub const GENERATOR_MAX_DIFFICULTY: u32 = 5;
+const UNBREAKABLE_BRICK_COLOR: Color = Color::GRAY;
+
+// Seed text
+const SEED_FONT_SIZE: f32 = 24.0;
+const SEED_TEXT_PADDING: Val = Val::Px(8.0);
+const SEED_TEXT_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
+
+pub struct GeneratorPlugin;
+
+impl Plugin for GeneratorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_seed_text)
+            .add_systems(
+                Update,
+                roll_random_board.run_if(in_state(GameState::NewGame)),
+            )
+            .add_systems(Update, update_seed_text);
+    }
+}
+
+#[derive(Component)]
+struct SeedText;
+
+// SplitMix64. Small, fast and gives the same numbers on every platform, so seeds can be shared.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // Uniform in [0, n)
+    pub fn below(&mut self, n: u32) -> u32 {
+        (self.next_u64() % n as u64) as u32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    None,
+    // Left and right halves mirror each other
+    Mirror,
+    // The layout looks the same turned upside down
+    Radial,
+}
+
+#[derive(Clone, Debug)]
+pub struct GeneratorParams {
+    // Share of grid cells that get a brick
+    pub density: f32,
+    pub symmetry: Symmetry,
+    // Share of bricks that take more than one hit
+    pub tough_share: f32,
+    // Share of bricks that can't be broken at all
+    pub unbreakable_share: f32,
+    // 1 to 5, adds rows and hit points
+    pub difficulty: u32,
+    // How wide the board is, in grid cells
+    pub columns: u32,
+}
+
+impl Default for GeneratorParams {
+    fn default() -> Self {
+        GeneratorParams {
+            density: 0.7,
+            symmetry: Symmetry::Mirror,
+            tough_share: 0.2,
+            unbreakable_share: 0.05,
+            difficulty: 2,
+            columns: BoardLayout::default().grid_columns,
+        }
+    }
+}
+
+impl GeneratorParams {
+    // Params picked from the seed itself, so the seed alone is enough to share a board
+    pub fn for_seed(seed: u64) -> Self {
+        let mut rng = Rng::new(seed ^ 0x5EED);
+        GeneratorParams {
+            density: 0.45 + (rng.next_f32() * 0.5),
+            symmetry: match rng.below(3) {
+                0 => Symmetry::None,
+                1 => Symmetry::Mirror,
+                _ => Symmetry::Radial,
+            },
+            tough_share: rng.next_f32() * 0.4,
+            unbreakable_share: rng.next_f32() * 0.15,
+            difficulty: 1 + rng.below(GENERATOR_MAX_DIFFICULTY),
+            columns: BoardLayout::default().grid_columns,
+        }
+    }
+}
+
+pub fn random_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    // Spread the clock's bits so seeds rolled close together look nothing alike
+    Rng::new(nanos).next_u64() % 1_000_000_000
+}
+
+pub fn generate(seed: u64, params: &GeneratorParams) -> LevelData {
+    let mut rng = Rng::new(seed);
+    let difficulty = params.difficulty.clamp(1, GENERATOR_MAX_DIFFICULTY);
+    let rows = (GENERATOR_MIN_ROWS + difficulty).min(GENERATOR_MAX_ROWS);
+    let columns = params.columns;
+
+    let mut bricks: Vec<BrickData> = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            // Only the cells that lead their symmetry group are rolled, the rest copy them
+            if mirrored_cells(column, row, columns, rows, params.symmetry)[0] != (column, row) {
+                continue;
+            }
+            if rng.next_f32() >= params.density {
+                continue;
+            }
+            let roll = rng.next_f32();
+            let (kind, hit_points) = if roll < params.unbreakable_share {
+                (BrickKind::Unbreakable, 0)
+            } else if roll < params.unbreakable_share + params.tough_share {
+                (BrickKind::Normal, 2 + rng.below(difficulty))
+            } else {
+                (BrickKind::Normal, 1)
+            };
+            for (column, row) in mirrored_cells(column, row, columns, rows, params.symmetry) {
+                if bricks.iter().any(|b| (b.column, b.row) == (column, row)) {
+                    continue;
+                }
+                bricks.push(BrickData {
+                    column,
+                    row,
+                    color: brick_color(kind, hit_points, row),
+                    kind,
+                    hit_points,
+                    behaviour: BrickBehaviour::Static,
+                });
+            }
+        }
+    }
+
+    let mut level = LevelData {
+        bricks,
+        seed: Some(seed),
+        difficulty: Some(difficulty),
+    };
+    make_completable(&mut level, columns, rows, params.symmetry);
+    level
+}
+
+pub fn brick_color(kind: BrickKind, hit_points: u32, row: u32) -> Color {
+    match (kind, hit_points) {
+        (BrickKind::Unbreakable, _) => UNBREAKABLE_BRICK_COLOR,
+        (BrickKind::Normal, 1) => {
+            let shade = 0.25 + (row as f32 / 10.);
+            Color::rgb(shade, 0.75, shade)
+        }
+        (BrickKind::Normal, _) => {
+            Color::rgb(0.9, (0.75 - (0.15 * hit_points as f32)).max(0.1), 0.3)
+        }
+    }
+}
+
+// A cell and the cells its symmetry copies it to, smallest first
+fn mirrored_cells(
+    column: u32,
+    row: u32,
+    columns: u32,
+    rows: u32,
+    symmetry: Symmetry,
+) -> Vec<(u32, u32)> {
+    let mut cells = vec![(column, row)];
+    match symmetry {
+        Symmetry::None => {}
+        Symmetry::Mirror => cells.push((columns - 1 - column, row)),
+        Symmetry::Radial => cells.push((columns - 1 - column, rows - 1 - row)),
+    }
+    cells.sort_by_key(|(column, row)| (*row, *column));
+    cells.dedup();
+    cells
+}
+
+// The ball can only come up from below the grid, and can't pass unbreakable bricks. Any
+// breakable brick walled off by them is made reachable by turning the nearest wall brick
+// breakable, until every brick can be cleared.
+fn make_completable(level: &mut LevelData, columns: u32, rows: u32, symmetry: Symmetry) {
+    if !level.bricks.iter().any(|b| b.kind == BrickKind::Normal) {
+        match level.bricks.first().map(|b| (b.column, b.row)) {
+            Some((column, row)) => {
+                for (column, row) in mirrored_cells(column, row, columns, rows, symmetry) {
+                    if let Some(index) = level.brick_at(column, row) {
+                        make_breakable(&mut level.bricks[index]);
+                    }
+                }
+            }
+            None => {
+                for (column, row) in mirrored_cells(columns / 2, 0, columns, rows, symmetry) {
+                    level.bricks.push(BrickData {
+                        column,
+                        row,
+                        color: brick_color(BrickKind::Normal, 1, row),
+                        kind: BrickKind::Normal,
+                        hit_points: 1,
+                        behaviour: BrickBehaviour::Static,
+                    });
+                }
+            }
+        }
+    }
+
+    loop {
+        let reached = reachable_cells(level, columns, rows);
+        let stranded: Vec<(u32, u32)> = level
+            .bricks
+            .iter()
+            .filter(|b| {
+                b.kind == BrickKind::Normal && !reached[cell_index(b.column, b.row, columns)]
+            })
+            .map(|b| (b.column, b.row))
+            .collect();
+        if stranded.is_empty() {
+            return;
+        }
+
+        // Wall bricks the ball can already get to
+        let breach = level
+            .bricks
+            .iter()
+            .filter(|b| b.kind == BrickKind::Unbreakable)
+            .filter(|b| {
+                b.row == rows - 1
+                    || neighbours(b.column, b.row, columns, rows)
+                        .any(|(column, row)| reached[cell_index(column, row, columns)])
+            })
+            .min_by_key(|b| {
+                stranded
+                    .iter()
+                    .map(|(column, row)| b.column.abs_diff(*column) + b.row.abs_diff(*row))
+                    .min()
+                    .unwrap_or(u32::MAX)
+            })
+            .map(|b| (b.column, b.row));
+        // Something always walls the stranded bricks off, so there is always a breach
+        let Some((column, row)) = breach else {
+            return;
+        };
+        for (column, row) in mirrored_cells(column, row, columns, rows, symmetry) {
+            if let Some(index) = level.brick_at(column, row) {
+                make_breakable(&mut level.bricks[index]);
+            }
+        }
+    }
+}
+
+fn make_breakable(brick: &mut BrickData) {
+    brick.kind = BrickKind::Normal;
+    brick.hit_points = brick.hit_points.max(1);
+    brick.color = brick_color(BrickKind::Normal, brick.hit_points, brick.row);
+}
+
+fn cell_index(column: u32, row: u32, columns: u32) -> usize {
+    (row * columns + column) as usize
+}
+
+fn neighbours(column: u32, row: u32, columns: u32, rows: u32) -> impl Iterator<Item = (u32, u32)> {
+    [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .into_iter()
+        .map(move |(dc, dr)| (column as i32 + dc, row as i32 + dr))
+        .filter(move |(c, r)| *c >= 0 && *r >= 0 && *c < columns as i32 && *r < rows as i32)
+        .map(|(c, r)| (c as u32, r as u32))
+}
+
+// Flood fill from under the grid through empty cells and breakable bricks
+fn reachable_cells(level: &LevelData, columns: u32, rows: u32) -> Vec<bool> {
+    let mut blocked = vec![false; (columns * rows) as usize];
+    for brick in &level.bricks {
+        if brick.kind == BrickKind::Unbreakable && brick.row < rows {
+            blocked[cell_index(brick.column, brick.row, columns)] = true;
+        }
+    }
+    let mut reached = vec![false; (columns * rows) as usize];
+    let mut queue: VecDeque<(u32, u32)> = (0..columns).map(|column| (column, rows - 1)).collect();
+    while let Some((column, row)) = queue.pop_front() {
+        let index = cell_index(column, row, columns);
+        if reached[index] || blocked[index] {
+            continue;
+        }
+        reached[index] = true;
+        queue.extend(neighbours(column, row, columns, rows));
+    }
+    reached
+}
+
+fn spawn_seed_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: SEED_FONT_SIZE,
+                color: SEED_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: SEED_TEXT_PADDING,
+            right: SEED_TEXT_PADDING,
+            ..default()
+        }),
+        SeedText,
+    ));
+}
+
+fn update_seed_text(current_level: Res<CurrentLevel>, mut query: Query<&mut Text, With<SeedText>>) {
+    if !current_level.is_changed() {
+        return;
+    }
+    for mut text in &mut query {
+        text.sections[0].value = match current_level.0.seed {
+            Some(seed) => format!("Seed: {seed}"),
+            None => String::new(),
+        };
+    }
+}
+
+// G on the start screen swaps the board for a freshly generated one
+fn roll_random_board(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    brick_query: Query<Entity, With<Brick>>,
+    mut current_level: ResMut<CurrentLevel>,
+    config: Res<GameConfig>,
+) {
+    if !keyboard_input.just_released(KeyCode::G) {
+        return;
+    }
+    let seed = random_seed();
+    let params = GeneratorParams {
+        columns: config.layout.grid_columns,
+        ..GeneratorParams::for_seed(seed)
+    };
+    current_level.0 = generate(seed, &params);
+    for brick in &brick_query {
+        commands.entity(brick).despawn();
+    }
+    spawn_bricks(&mut commands, &current_level.0, &config.layout);
+}
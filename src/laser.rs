@@ -0,0 +1,219 @@
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::SoundEffects;
+use crate::behaviour::Regrows;
+use crate::boss::{BossHitEvent, BossPart};
+use crate::config::GameConfig;
+use crate::net::Resimulating;
+use crate::soundbank::SoundEvent;
+use crate::{
+    apply_velocity, brick_row, check_for_collisions, damage_brick, run_gameplay_tick, score_brick,
+    Brick, BrickBrokenEvent, Collider, ExplosionEvent, GameState, GameplayTick, HitPoints, Paddle,
+    PaddleInputs, Player, Scoreboard, Velocity,
+};
+
+// Laser
+// How long the upgrade lasts once picked up
+pub const LASER_DURATION: f32 = 15.0;
+// Seconds between shots while fire is held
+const LASER_COOLDOWN: f32 = 0.35;
+// The cannons sit this far in from either end of the paddle
+const LASER_CANNON_INSET: f32 = 12.0;
+const LASER_BOLT_SIZE: Vec3 = Vec3::new(6.0, 18.0, 0.0);
+const LASER_BOLT_SPEED: f32 = 700.0;
+const LASER_BOLT_COLOR: Color = Color::rgb(1.0, 0.3, 0.3);
+// Boss damage from one bolt
+const LASER_BOSS_DAMAGE: u32 = 1;
+
+pub struct LaserPlugin;
+
+impl Plugin for LaserPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LaserHitEvent>()
+            .add_systems(
+                GameplayTick,
+                (
+                    fire_lasers.before(apply_velocity),
+                    hit_bricks_with_lasers
+                        .after(apply_velocity)
+                        .before(check_for_collisions),
+                ),
+            )
+            .add_systems(
+                Update,
+                play_laser_sound
+                    .after(run_gameplay_tick)
+                    .run_if(in_state(GameState::InGame)),
+            )
+            .add_systems(OnEnter(GameState::GameOver), clear_laser_bolts);
+    }
+}
+
+// Twin cannons fitted to a paddle for a while
+#[derive(Component, Clone, Copy, Serialize, Deserialize)]
+pub struct LaserCannons {
+    pub time_left: f32,
+    // Seconds until the cannons can fire again
+    pub cooldown: f32,
+}
+
+impl LaserCannons {
+    pub fn new() -> Self {
+        LaserCannons {
+            time_left: LASER_DURATION,
+            cooldown: 0.0,
+        }
+    }
+}
+
+// A shot on its way up, credited to whoever fired it
+#[derive(Component, Clone, Copy)]
+pub struct LaserBolt {
+    pub player: usize,
+}
+
+// Sent each time a bolt hits something, for its own sound apart from the ball's
+#[derive(Event, Default)]
+pub struct LaserHitEvent;
+
+pub fn laser_bolt_bundle(translation: Vec3, player: usize) -> impl Bundle {
+    (
+        SpriteBundle {
+            transform: Transform {
+                translation,
+                scale: LASER_BOLT_SIZE,
+                ..default()
+            },
+            sprite: Sprite {
+                color: LASER_BOLT_COLOR,
+                ..default()
+            },
+            ..default()
+        },
+        LaserBolt { player },
+        Velocity(Vec2::new(0.0, LASER_BOLT_SPEED)),
+    )
+}
+
+// Fires both cannons while the fire key is held, and takes the cannons away once they run out
+fn fire_lasers(
+    mut commands: Commands,
+    time_step: Res<FixedTime>,
+    inputs: Res<PaddleInputs>,
+    mut paddle_query: Query<(Entity, &Player, &Transform, &mut LaserCannons), With<Paddle>>,
+) {
+    let dt = time_step.period.as_secs_f32();
+    for (paddle, player, transform, mut cannons) in &mut paddle_query {
+        cannons.time_left -= dt;
+        cannons.cooldown -= dt;
+        if cannons.time_left <= 0.0 {
+            commands.entity(paddle).remove::<LaserCannons>();
+            continue;
+        }
+        let fire = inputs.0.get(player.0).is_some_and(|input| input.fire);
+        if !fire || cannons.cooldown > 0.0 {
+            continue;
+        }
+        cannons.cooldown = LASER_COOLDOWN;
+
+        let reach = transform.scale.x / 2.0 - LASER_CANNON_INSET;
+        let muzzle_height = (transform.scale.y + LASER_BOLT_SIZE.y) / 2.0;
+        for side in [-1.0, 1.0] {
+            let muzzle = transform.translation + Vec3::new(side * reach, muzzle_height, 0.0);
+            commands.spawn(laser_bolt_bundle(muzzle, player.0));
+        }
+    }
+}
+
+type LaserTargetQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        Option<&'static mut HitPoints>,
+        Option<&'static Regrows>,
+        Option<&'static BossPart>,
+        &'static Sprite,
+    ),
+    (With<Brick>, With<Collider>, Without<LaserBolt>),
+>;
+
+// A bolt stops at the first brick it meets, damaging it the same way the ball does. Unbreakable
+// bricks just soak it up.
+#[allow(clippy::too_many_arguments)]
+fn hit_bricks_with_lasers(
+    mut commands: Commands,
+    bolt_query: Query<(Entity, &Transform, &LaserBolt)>,
+    mut target_query: LaserTargetQuery,
+    mut scoreboard: ResMut<Scoreboard>,
+    mut laser_hits: EventWriter<LaserHitEvent>,
+    mut explosions: EventWriter<ExplosionEvent>,
+    mut bricks_broken: EventWriter<BrickBrokenEvent>,
+    mut boss_hits: EventWriter<BossHitEvent>,
+    config: Res<GameConfig>,
+) {
+    for (bolt, bolt_transform, laser_bolt) in &bolt_query {
+        if bolt_transform.translation.y > config.layout.top_wall {
+            commands.entity(bolt).despawn();
+            continue;
+        }
+        let target = target_query.iter_mut().find(|(_, transform, ..)| {
+            collide(
+                bolt_transform.translation,
+                bolt_transform.scale.truncate(),
+                transform.translation,
+                transform.scale.truncate(),
+            )
+            .is_some()
+        });
+        let Some((brick, transform, maybe_hit_points, maybe_regrows, maybe_boss_part, sprite)) =
+            target
+        else {
+            continue;
+        };
+        commands.entity(bolt).despawn();
+        laser_hits.send_default();
+
+        if maybe_boss_part.is_some() {
+            boss_hits.send(BossHitEvent {
+                damage: LASER_BOSS_DAMAGE,
+                player: Some(laser_bolt.player),
+            });
+        }
+        // A brick already broken by an earlier bolt this tick isn't broken again
+        let Some(mut hit_points) = maybe_hit_points.filter(|hit_points| hit_points.0 > 0) else {
+            continue;
+        };
+        if damage_brick(&mut commands, brick, &mut hit_points, maybe_regrows) {
+            explosions.send(ExplosionEvent {
+                row: brick_row(transform.translation, &config.layout),
+            });
+            bricks_broken.send(BrickBrokenEvent {
+                translation: transform.translation,
+                color: sprite.color,
+            });
+            score_brick(&mut scoreboard, Some(laser_bolt.player));
+        }
+    }
+}
+
+fn clear_laser_bolts(mut commands: Commands, bolt_query: Query<Entity, With<LaserBolt>>) {
+    for bolt in &bolt_query {
+        commands.entity(bolt).despawn();
+    }
+}
+
+fn play_laser_sound(
+    mut laser_hits: EventReader<LaserHitEvent>,
+    mut sound_effects: SoundEffects,
+    resimulating: Option<Res<Resimulating>>,
+) {
+    let replayed = resimulating.map_or(0, |replayed| replayed.laser_hits);
+    // Once per frame, however many bolts landed
+    if laser_hits.len() > replayed {
+        sound_effects.play(SoundEvent::LaserHit, 1.0, 0.0);
+    }
+    laser_hits.clear();
+}
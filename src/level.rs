@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::BoardLayout;
+use crate::storage::{read_ron, save_ron};
+use crate::BrickKind;
+
+// Where the editor keeps its level
+pub const CUSTOM_LEVEL_PATH: &str = "assets/levels/custom.ron";
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrickData {
+    pub column: u32,
+    pub row: u32,
+    pub color: Color,
+    pub kind: BrickKind,
+    pub hit_points: u32,
+    // Levels from before brick behaviours existed only had static bricks
+    #[serde(default)]
+    pub behaviour: BrickBehaviour,
+}
+
+// What a brick does besides sitting still and taking hits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum BrickBehaviour {
+    #[default]
+    Static,
+    // Slides back and forth along its row, up to `range` cells either side of where it starts
+    Moving {
+        range: u32,
+        speed: f32,
+    },
+    // Drops a row after every `every` paddle hits
+    Descending {
+        every: u32,
+    },
+    // Comes back `delay` seconds after breaking, unless every brick around it is gone too
+    Regrowing {
+        delay: f32,
+    },
+}
+
+impl BrickBehaviour {
+    pub fn name(self) -> &'static str {
+        match self {
+            BrickBehaviour::Static => "static",
+            BrickBehaviour::Moving { .. } => "moving",
+            BrickBehaviour::Descending { .. } => "descending",
+            BrickBehaviour::Regrowing { .. } => "regrowing",
+        }
+    }
+}
+
+// A brick layout on the grid, as stored in level files
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelData {
+    pub bricks: Vec<BrickData>,
+    // The seed a generated level came from
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // How hard a generated level was made, from 1 to 5
+    #[serde(default)]
+    pub difficulty: Option<u32>,
+}
+
+impl LevelData {
+    // The original wall: the whole grid filled with single-hit bricks shading from green to white
+    pub fn classic(layout: &BoardLayout) -> Self {
+        let mut bricks = Vec::new();
+        for row in 0..layout.grid_rows {
+            for column in 0..layout.grid_columns {
+                let shade = 0.25 + (row as f32 / 10.);
+                bricks.push(BrickData {
+                    column,
+                    row,
+                    color: Color::rgb(shade, 0.75, shade),
+                    kind: BrickKind::Normal,
+                    hit_points: 1,
+                    behaviour: BrickBehaviour::Static,
+                });
+            }
+        }
+        LevelData {
+            bricks,
+            seed: None,
+            difficulty: None,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        read_ron(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| error.to_string())?;
+        }
+        save_ron(path, self)
+    }
+
+    pub fn brick_at(&self, column: u32, row: u32) -> Option<usize> {
+        self.bricks
+            .iter()
+            .position(|brick| brick.column == column && brick.row == row)
+    }
+
+    // Problems that would make the level unplayable
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !self
+            .bricks
+            .iter()
+            .any(|brick| brick.kind == BrickKind::Normal)
+        {
+            problems.push("Level has no destructible bricks".to_string());
+        }
+        for (index, brick) in self.bricks.iter().enumerate() {
+            if brick.kind == BrickKind::Normal && brick.hit_points == 0 {
+                problems.push(format!(
+                    "Brick at column {}, row {} has no hit points",
+                    brick.column, brick.row
+                ));
+            }
+            if self.brick_at(brick.column, brick.row) != Some(index) {
+                problems.push(format!(
+                    "More than one brick at column {}, row {}",
+                    brick.column, brick.row
+                ));
+            }
+        }
+        problems
+    }
+}
+
+// The classic wall on the built-in grid
+impl Default for LevelData {
+    fn default() -> Self {
+        LevelData::classic(&BoardLayout::default())
+    }
+}
+
+// The layout that is played, and spawned again each time it is cleared
+#[derive(Resource, Default)]
+pub struct CurrentLevel(pub LevelData);
+
+// Layouts played one after another, each taking over from the last as it is cleared
+#[derive(Resource)]
+pub struct LevelSet {
+    pub levels: Vec<LevelData>,
+    // Whether the game ends after the set, and its boss in a classic game, instead of starting
+    // it over
+    pub ends: bool,
+}
+
+pub fn cell_translation(layout: &BoardLayout, column: u32, row: u32) -> Vec3 {
+    let step = layout.cell_step();
+    let grid_cell_top = layout.cell_top - row as f32 * step.y;
+    let grid_cell_left = layout.cell_left + column as f32 * step.x;
+    Vec3::new(grid_cell_left, grid_cell_top, 0.0)
+}
+
+// The grid cell under a point in the world, if any
+pub fn cell_at(layout: &BoardLayout, position: Vec2, rows: u32) -> Option<(u32, u32)> {
+    let step = layout.cell_step();
+    let column = ((position.x - layout.cell_left + (layout.cell_size.x / 2.)) / step.x).floor();
+    let row = ((layout.cell_top + (layout.cell_size.y / 2.) - position.y) / step.y).floor();
+    if column < 0. || row < 0. || column >= layout.grid_columns as f32 || row >= rows as f32 {
+        return None;
+    }
+    Some((column as u32, row as u32))
+}
@@ -1,51 +1,65 @@
+use std::time::Duration;
+
 use bevy::{
+    asset::ChangeWatcher,
+    ecs::{schedule::ScheduleLabel, system::SystemParam},
     prelude::*,
     sprite::collide_aabb::{collide, Collision},
     sprite::MaterialMesh2dBundle,
 };
+use serde::{Deserialize, Serialize};
 
-// Constants
-// Grid/Bricks
-const GRID_HEIGHT: f32 = 5.;
-const GRID_WIDTH: f32 = 10.;
-const GRID_CELL_SPACE: f32 = 5.;
-const GRID_CELL_WIDTH: f32 = 80.;
-const GRID_CELL_HEIGHT: f32 = 30.;
-const GRID_CELL_TOP: f32 = 300.;
-const GRID_CELL_LEFT: f32 = -350.;
-const BRICK_SIZE: Vec3 = Vec3::new(GRID_CELL_WIDTH, GRID_CELL_HEIGHT, 0.0);
-
-// BG
-const BACKGROUND_COLOR: Color = Color::rgb(0., 0., 0.25);
-
-// Walls
-const LEFT_WALL: f32 = -400.;
-const RIGHT_WALL: f32 = 465.;
-const TOP_WALL: f32 = 325.;
-const BOTTOM_WALL: f32 = -325.;
-const WALL_COLOR: Color = Color::rgb(0., 0.75, 0.);
-const TB_WALL_ADJUST: f32 = 32.5;
-const WALL_SIZE: f32 = 10.;
+mod achievements;
+mod audio;
+mod behaviour;
+mod boss;
+mod catch;
+mod cli;
+mod config;
+mod daily;
+mod debug;
+mod editor;
+mod effects;
+mod generator;
+mod laser;
+mod level;
+mod modes;
+mod multiball;
+mod net;
+mod palette;
+mod particles;
+mod powerups;
+mod replay;
+mod save;
+mod settings;
+mod snapshot;
+mod soundbank;
+mod stats;
+mod storage;
+#[cfg(test)]
+mod tests;
+mod theme;
+
+use behaviour::Regrows;
+use boss::BossPart;
+use config::{BoardLayout, GameConfig};
+use level::{cell_translation, CurrentLevel, LevelData, LevelSet};
+use soundbank::SoundEvent;
 
+// Constants
 // Ball
-const BALL_COLOR: Color = Color::PURPLE;
-const BALL_SPEED: f32 = 400.0;
 const BALL_STARTING_POSITION: Vec3 = Vec3::new(0.0, -50.0, 1.0);
-const BALL_SIZE: Vec3 = Vec3::new(30.0, 30.0, 0.0);
-const LEFT_WALL_SIZE: Vec3 = Vec3::new(10.0, 650.0, 0.0);
-const RIGHT_WALL_SIZE: Vec3 = Vec3::new(10.0, 650.0, 0.0);
-const TOP_WALL_SIZE: Vec3 = Vec3::new(875.0, 10.0, 0.0);
-const BOTTOM_WALL_SIZE: Vec3 = Vec3::new(875.0, 10.0, 0.0);
 const INITIAL_BALL_DIRECTION: Vec2 = Vec2::new(0.5, -0.5);
 
 // Paddle
-const PADDLE_WIDTH: f32 = 125.;
-const PADDLE_COLOR: Color = Color::ORANGE;
-const PADDLE_SPEED: f32 = 10.0;
-const PADDLE_SIZE: Vec3 = Vec3::new(120.0, 20.0, 0.0);
 const GAP_BETWEEN_PADDLE_AND_FLOOR: f32 = 20.0;
-const LEFT_BOUND_PADDLE: f32 = LEFT_WALL + WALL_SIZE + (PADDLE_WIDTH / 2.);
-const RIGHT_BOUND_PADDLE: f32 = RIGHT_WALL - WALL_SIZE - (PADDLE_WIDTH / 2.);
+// Paddles stop this far short of the walls, split between either side
+const PADDLE_CLEARANCE: f32 = 5.;
+
+// Players
+const COOP_PLAYER_COUNT: usize = 2;
+const PLAYER_ONE_KEYS: (KeyCode, KeyCode, KeyCode) = (KeyCode::Left, KeyCode::Right, KeyCode::Up);
+const PLAYER_TWO_KEYS: (KeyCode, KeyCode, KeyCode) = (KeyCode::A, KeyCode::D, KeyCode::W);
 
 // Scoreboard
 const SCOREBOARD_FONT_SIZE: f32 = 40.0;
@@ -70,6 +84,7 @@ const INFO_TEXT_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
 const START_GAME_VERTICAL_PADDING: Val = Val::Px(300.0);
 const START_GAME_LEFT_PADDING: Val = Val::Px(475.0);
 const START_GAME_FONT_SIZE: f32 = 50.0;
+const START_GAME_OPTIONS_FONT_SIZE: f32 = 25.0;
 const START_GAME_TEXT_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
 const START_OVERLAY_SIZE: Vec3 = Vec3::new(1500.0, 1500.0, 0.0);
 const START_OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.95);
@@ -90,36 +105,147 @@ const GAMEOVER_OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.95);
 const GAMEOVER_VERTICAL_PADDING: Val = Val::Px(300.0);
 const GAMEOVER_LEFT_PADDING: Val = Val::Px(475.0);
 
+// Assets
+// How long after a file changes it is loaded again
+const ASSET_WATCH_DELAY: Duration = Duration::from_millis(200);
+
+// Sounds
+// Each brick in a combo sounds a semitone above the last, up to an octave
+const COMBO_MAX_SEMITONES: u32 = 12;
+
 #[derive(Component)]
 struct Paddle;
 
+// Which player a paddle belongs to, 0 being player one
+#[derive(Component, Clone, Copy)]
+struct Player(usize);
+
+// The keys a paddle is bound to
+#[derive(Component)]
+struct PaddleControls {
+    left: KeyCode,
+    right: KeyCode,
+    fire: KeyCode,
+}
+
+// How far a paddle may travel along the floor
+#[derive(Component)]
+struct PaddleBounds {
+    left: f32,
+    right: f32,
+}
+
 #[derive(Component)]
 struct Ball;
 
+// The player whose paddle last touched the ball, if any
+#[derive(Component, Default)]
+struct LastTouchedBy(Option<usize>);
+
 #[derive(Component, Deref, DerefMut)]
 struct Velocity(Vec2);
 
 #[derive(Component)]
 struct Collider;
 
-#[derive(Event, Default)]
-struct CollisionEvent;
+#[derive(Component)]
+struct Wall;
 
-#[derive(Event, Default)]
-struct ExplosionEvent;
+// Sent each time the ball hits something, with what it hit and where. A boss shot that costs a
+// life is sent as the floor under the paddle it hit.
+#[derive(Event)]
+struct CollisionEvent {
+    position: Vec2,
+    surface: Surface,
+    // Whether hitting the floor cost a life, which it doesn't for a spare ball or in god mode
+    life_lost: bool,
+}
 
-#[derive(Resource)]
-struct CollisionSound(Handle<AudioSource>);
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Surface {
+    Wall,
+    Floor,
+    Paddle,
+    Brick,
+    Boss,
+}
 
-#[derive(Resource)]
-struct ExplosionSound(Handle<AudioSource>);
+// Sent when a brick breaks, with the grid row it was on, or when the boss is beaten
+#[derive(Event)]
+struct ExplosionEvent {
+    row: Option<u32>,
+}
+
+// Sent for each brick that breaks, wherever it was
+#[derive(Event)]
+struct BrickBrokenEvent {
+    translation: Vec3,
+    color: Color,
+}
+
+// Sent each time the ball bounces off a paddle
+#[derive(Event, Default)]
+struct PaddleHitEvent;
 
 #[derive(Component)]
 struct Brick;
 
+// A brick knocked out of play, left only so the effects can flash it before it is gone
+#[derive(Component)]
+struct Broken;
+
+// Hits left before a brick breaks. Unbreakable bricks don't have any.
+#[derive(Component)]
+struct HitPoints(u32);
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+enum BrickKind {
+    #[default]
+    Normal,
+    Unbreakable,
+}
+
+// What a player asks of their paddle for one gameplay tick
+#[derive(Clone, Copy, Default)]
+struct PaddleInput {
+    direction: f32,
+    fire: bool,
+}
+
+impl PaddleInput {
+    // Packs the input into a byte for the network: the direction, plus 4 when firing
+    fn to_byte(self) -> i8 {
+        self.direction as i8 + if self.fire { 4 } else { 0 }
+    }
+
+    fn from_byte(byte: i8) -> Self {
+        let fire = byte >= 3;
+        PaddleInput {
+            direction: (byte - if fire { 4 } else { 0 }) as f32,
+            fire,
+        }
+    }
+}
+
+// Each player's paddle input for the next gameplay tick, indexed by player
+#[derive(Resource, Default)]
+struct PaddleInputs(Vec<PaddleInput>);
+
+// One fixed step of gameplay. Local play runs it once per frame, online play may run it several
+// times in a frame to roll back and resimulate.
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct GameplayTick;
+
+// Local play's part of the frame: this tick's input is gathered, then the tick is run
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct LocalTick;
+
 #[derive(Component)]
 struct StartGameOverlay;
 
+#[derive(Component)]
+struct StartMenuText;
+
 #[derive(Component)]
 struct PauseGameOverlay;
 
@@ -142,12 +268,71 @@ struct BottomWall;
 #[derive(Resource)]
 struct Scoreboard {
     score: usize,
+    player_scores: Vec<usize>,
 }
 
-// This resource tracks the game's score
+impl Scoreboard {
+    fn new(players: usize) -> Self {
+        Scoreboard {
+            score: 0,
+            player_scores: vec![0; players],
+        }
+    }
+}
+
+// This resource tracks the lives left, one pool when lives are shared or one per player
 #[derive(Resource)]
 struct Lives {
-    lives_left: usize,
+    lives_left: Vec<usize>,
+}
+
+impl Lives {
+    fn new(pools: usize, starting_lives: usize) -> Self {
+        Lives {
+            lives_left: vec![starting_lives; pools],
+        }
+    }
+
+    fn all_lost(&self) -> bool {
+        self.lives_left.iter().all(|lives| *lives == 0)
+    }
+}
+
+// How many walls, bosses included, have been beaten in this game
+#[derive(Resource, Default)]
+struct Level {
+    index: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+enum LivesMode {
+    #[default]
+    Shared,
+    Separate,
+}
+
+// Local co-op options, picked on the start screen
+#[derive(Resource, Default, Clone, Serialize, Deserialize)]
+struct CoopSettings {
+    enabled: bool,
+    lives_mode: LivesMode,
+}
+
+impl CoopSettings {
+    fn player_count(&self) -> usize {
+        if self.enabled {
+            COOP_PLAYER_COUNT
+        } else {
+            1
+        }
+    }
+
+    fn lives_pools(&self) -> usize {
+        match self.lives_mode {
+            LivesMode::Separate => self.player_count(),
+            LivesMode::Shared => 1,
+        }
+    }
 }
 
 // Game State
@@ -158,105 +343,196 @@ enum GameState {
     InGame,
     Paused,
     GameOver,
+    Lobby,
+    Editor,
+    Settings,
+    Stats,
+    Achievements,
 }
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_state::<GameState>()
-        .add_systems(Startup, setup)
-        .insert_resource(ClearColor(BACKGROUND_COLOR))
-        .add_systems(
-            Update,
-            (check_for_state).run_if(in_state(GameState::NewGame)),
-        )
-        .add_systems(
-            Update,
-            (
-                check_for_state,
-                apply_velocity.before(check_for_collisions),
-                move_paddle
-                    .before(check_for_collisions)
-                    .after(apply_velocity),
-                check_for_collisions,
-                play_collision_sound.after(check_for_collisions),
-                play_explosion_sound.after(check_for_collisions),
-                update_scoreboard,
-                update_lives,
-            )
-                .run_if(in_state(GameState::InGame)),
-        )
-        .add_systems(
-            Update,
-            (check_for_state).run_if(in_state(GameState::Paused)),
-        )
-        .add_systems(
-            Update,
-            (check_for_state).run_if(in_state(GameState::GameOver)),
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("error: {error}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    let current_level = match options.level() {
+        Ok(level) => CurrentLevel(level),
+        Err(error) => {
+            eprintln!("error: {error}");
+            std::process::exit(2);
+        }
+    };
+    let playback = match &options.replay {
+        Some(path) => match replay::ReplayFile::load(path) {
+            Ok(replay) => Some(replay::Playback::new(replay)),
+            Err(error) => {
+                eprintln!("error: could not read {}: {error}", path.display());
+                std::process::exit(2);
+            }
+        },
+        None => None,
+    };
+    let recording = options.record.clone().map(replay::Recording::new);
+
+    let mut app = App::new();
+    // Assets are watched, so sound banks and themes can be edited while the game runs
+    app.add_plugins(options.configure_plugins(DefaultPlugins.set(AssetPlugin {
+        watch_for_changes: ChangeWatcher::with_delay(ASSET_WATCH_DELAY),
+        ..default()
+    })));
+    add_game(&mut app);
+    app.insert_resource(current_level).insert_resource(options);
+    if let Some(playback) = playback {
+        app.insert_resource(playback);
+    }
+    if let Some(recording) = recording {
+        app.insert_resource(recording);
+    }
+    app.run();
+}
+
+// Everything the game adds on top of the engine's plugins, shared with the tests
+fn add_game(app: &mut App) {
+    app.add_plugins((
+        net::NetPlugin,
+        save::SavePlugin,
+        editor::EditorPlugin,
+        generator::GeneratorPlugin,
+        daily::DailyPlugin,
+        modes::ModesPlugin,
+        behaviour::BehaviourPlugin,
+        boss::BossPlugin,
+        laser::LaserPlugin,
+        powerups::PowerUpsPlugin,
+        catch::CatchPlugin,
+        multiball::MultiBallPlugin,
+    ))
+    // Player settings, and how the game looks and feels, none of which touches the simulation
+    .add_plugins((
+        settings::SettingsPlugin,
+        audio::MixerPlugin,
+        soundbank::SoundBankPlugin,
+        particles::ParticlesPlugin,
+        effects::EffectsPlugin,
+        theme::ThemePlugin,
+        palette::PalettePlugin,
+        config::ConfigPlugin,
+        cli::CliPlugin,
+        replay::ReplayPlugin,
+        debug::DebugPlugin,
+        stats::StatsPlugin,
+        achievements::AchievementsPlugin,
+    ))
+    .add_state::<GameState>()
+    .add_systems(Startup, setup)
+    .insert_resource(ClearColor(GameConfig::default().background_color))
+    .add_systems(
+        Update,
+        (check_for_state, configure_coop).run_if(in_state(GameState::NewGame)),
+    )
+    .add_systems(
+        Update,
+        (
+            check_for_state,
+            (read_local_input, run_gameplay_tick)
+                .chain()
+                .in_set(LocalTick),
+            play_collision_sound.after(run_gameplay_tick),
+            play_explosion_sound.after(run_gameplay_tick),
+            update_scoreboard,
+            update_lives,
         )
-        .add_event::<CollisionEvent>()
-        .add_event::<ExplosionEvent>()
-        .insert_resource(Scoreboard { score: 0 })
-        .insert_resource(Lives { lives_left: 3 })
-        .insert_resource(FixedTime::new_from_secs(1.0 / 60.0))
-        .add_systems(Update, bevy::window::close_on_esc)
-        .run();
+            .run_if(in_state(GameState::InGame)),
+    )
+    .add_systems(
+        GameplayTick,
+        (
+            advance_wall.before(apply_velocity),
+            apply_velocity.before(check_for_collisions),
+            move_paddle
+                .before(check_for_collisions)
+                .after(apply_velocity),
+            check_for_collisions,
+        ),
+    )
+    .add_systems(
+        Update,
+        (check_for_state).run_if(in_state(GameState::Paused)),
+    )
+    .add_systems(
+        Update,
+        (check_for_state).run_if(in_state(GameState::GameOver)),
+    )
+    .add_event::<CollisionEvent>()
+    .add_event::<ExplosionEvent>()
+    .add_event::<PaddleHitEvent>()
+    .add_event::<BrickBrokenEvent>()
+    .init_resource::<CoopSettings>()
+    .init_resource::<PaddleInputs>()
+    .insert_resource(Scoreboard::new(1))
+    .insert_resource(Lives::new(1, GameConfig::default().starting_lives))
+    .init_resource::<Level>()
+    .init_resource::<CurrentLevel>()
+    .insert_resource(FixedTime::new_from_secs(1.0 / 60.0))
+    .configure_set(
+        Update,
+        LocalTick
+            .run_if(in_state(GameState::InGame))
+            .run_if(not(resource_exists::<net::NetSession>()))
+            // Online games never stop for hit-stop, since both sides have to keep the same pace.
+            // They tick outside this set.
+            .run_if(effects::simulation_running),
+    )
+    .add_systems(Update, bevy::window::close_on_esc);
 }
 
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
     game_state: Res<State<GameState>>,
+    current_level: Res<CurrentLevel>,
+    config: Res<GameConfig>,
 ) {
     commands.spawn(Camera2dBundle::default());
 
-    spawn_walls(&mut commands);
-    spawn_bricks(&mut commands);
-
-    // Load collision sounds
-    let ball_collision_sound = asset_server.load("sounds/breakout_collision.ogg");
-    commands.insert_resource(CollisionSound(ball_collision_sound));
-
-    let brick_explosion_sound = asset_server.load("sounds/breakout_brick_explosion.ogg");
-    commands.insert_resource(ExplosionSound(brick_explosion_sound));
+    spawn_walls(&mut commands, &config);
+    spawn_bricks(&mut commands, &current_level.0, &config.layout);
 
     // Draw Paddle
-    let paddle_y = BOTTOM_WALL + GAP_BETWEEN_PADDLE_AND_FLOOR;
-    commands.spawn((
-        SpriteBundle {
-            transform: Transform {
-                translation: Vec3::new(0.0, paddle_y, 0.0),
-                scale: PADDLE_SIZE,
-                ..default()
-            },
-            sprite: Sprite {
-                color: PADDLE_COLOR,
-                ..default()
-            },
-            ..default()
-        },
-        Paddle,
-        Collider,
-    ));
+    spawn_paddles(&mut commands, 1, &config);
 
     // Draw Ball
+    let ball_assets = multiball::BallAssets {
+        mesh: meshes.add(shape::Circle::default().into()).into(),
+        material: materials.add(ColorMaterial::from(config.ball_color)),
+        scale: config.ball_scale(),
+    };
     commands.spawn((
         MaterialMesh2dBundle {
-            mesh: meshes.add(shape::Circle::default().into()).into(),
-            material: materials.add(ColorMaterial::from(BALL_COLOR)),
-            transform: Transform::from_translation(BALL_STARTING_POSITION).with_scale(BALL_SIZE),
+            mesh: ball_assets.mesh.clone(),
+            material: ball_assets.material.clone(),
+            transform: Transform::from_translation(BALL_STARTING_POSITION)
+                .with_scale(ball_assets.scale),
             ..default()
         },
         Ball,
-        Velocity(INITIAL_BALL_DIRECTION.normalize() * BALL_SPEED),
+        LastTouchedBy::default(),
+        Velocity(INITIAL_BALL_DIRECTION.normalize() * config.ball_speed),
     ));
+    commands.insert_resource(ball_assets);
 
     // Draw Info text
     commands.spawn((
         TextBundle::from_section(
-            "Keys:\nLeft/Right arrow to move\nEnter to Pause\nEscape to Quit.",
+            "Keys:\nLeft/Right arrow to move, Up to fire\nA/D to move, W to fire (player 2)\nEnter to Pause\nEscape to Quit.",
             TextStyle {
                 font_size: INFO_FONT_SIZE,
                 color: INFO_TEXT_COLOR,
@@ -326,164 +602,288 @@ fn setup(
 
     // Draw "ENTER to start" if Game has not yet been started
     if game_state.get() == &GameState::NewGame {
-        commands.spawn((
-            SpriteBundle {
-                transform: Transform {
-                    translation: Vec3::new(0.0, 0.0, 1.0),
-                    scale: START_OVERLAY_SIZE,
-                    ..default()
-                },
-                sprite: Sprite {
-                    color: START_OVERLAY_COLOR,
-                    ..default()
-                },
-                ..default()
-            },
-            StartGameOverlay,
-        ));
-        commands.spawn((
-            TextBundle::from_section(
-                "ENTER to start",
-                TextStyle {
-                    font_size: START_GAME_FONT_SIZE,
-                    color: START_GAME_TEXT_COLOR,
-                    ..default()
-                },
-            )
-            .with_style(Style {
-                position_type: PositionType::Absolute,
-                top: START_GAME_VERTICAL_PADDING,
-                left: START_GAME_LEFT_PADDING,
-                ..default()
-            }),
-            StartGameOverlay,
-        ));
+        spawn_start_overlay(&mut commands, &CoopSettings::default());
     }
 }
 
-fn spawn_walls(commands: &mut Commands) {
-    // Draw walls
-    // Left
-    let left_x = LEFT_WALL;
-    let left_y = 0.0;
+fn spawn_start_overlay(commands: &mut Commands, coop: &CoopSettings) {
     commands.spawn((
         SpriteBundle {
             transform: Transform {
-                translation: Vec3::new(left_x, left_y, 0.0),
-                scale: LEFT_WALL_SIZE,
+                translation: Vec3::new(0.0, 0.0, 1.0),
+                scale: START_OVERLAY_SIZE,
                 ..default()
             },
             sprite: Sprite {
-                color: WALL_COLOR,
+                color: START_OVERLAY_COLOR,
                 ..default()
             },
             ..default()
         },
-        Collider,
+        StartGameOverlay,
     ));
-    // Right
-    let right_x = RIGHT_WALL;
-    let right_y = 0.0;
     commands.spawn((
-        SpriteBundle {
-            transform: Transform {
-                translation: Vec3::new(right_x, right_y, 0.0),
-                scale: RIGHT_WALL_SIZE,
-                ..default()
-            },
-            sprite: Sprite {
-                color: WALL_COLOR,
-                ..default()
-            },
+        TextBundle::from_sections([
+            TextSection::new(
+                "ENTER to start\n",
+                TextStyle {
+                    font_size: START_GAME_FONT_SIZE,
+                    color: START_GAME_TEXT_COLOR,
+                    ..default()
+                },
+            ),
+            TextSection::new(
+                start_menu_options(coop),
+                TextStyle {
+                    font_size: START_GAME_OPTIONS_FONT_SIZE,
+                    color: START_GAME_TEXT_COLOR,
+                    ..default()
+                },
+            ),
+            // Filled in by the game mode picker
+            TextSection::new(
+                "",
+                TextStyle {
+                    font_size: START_GAME_OPTIONS_FONT_SIZE,
+                    color: START_GAME_TEXT_COLOR,
+                    ..default()
+                },
+            ),
+            // Filled in by the catch option
+            TextSection::new(
+                "",
+                TextStyle {
+                    font_size: START_GAME_OPTIONS_FONT_SIZE,
+                    color: START_GAME_TEXT_COLOR,
+                    ..default()
+                },
+            ),
+        ])
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: START_GAME_VERTICAL_PADDING,
+            left: START_GAME_LEFT_PADDING,
             ..default()
-        },
-        Collider,
+        }),
+        StartGameOverlay,
+        StartMenuText,
     ));
-    // Top
-    let top_x = 0.0 + TB_WALL_ADJUST;
-    let top_y = TOP_WALL;
-    commands.spawn((
+}
+
+fn start_menu_options(coop: &CoopSettings) -> String {
+    let players = if coop.enabled {
+        "2 players"
+    } else {
+        "1 player"
+    };
+    let lives = match coop.lives_mode {
+        LivesMode::Shared => "shared",
+        LivesMode::Separate => "separate",
+    };
+    let mut text = format!("2: {players}\nL: {lives} lives\nH/J: host/join online");
+    text.push_str(
+        "\nE: level editor\nG: random board\nS: settings\nT: statistics\nA: achievements\n",
+    );
+    text.push_str(&daily::menu_option());
+    if save::save_exists() {
+        text.push_str("\nC: continue");
+    }
+    text
+}
+
+fn spawn_paddles(commands: &mut Commands, players: usize, config: &GameConfig) {
+    for player in 0..players {
+        commands.spawn(paddle_bundle(player, players, config));
+    }
+}
+
+// Where a paddle `width` across can travel between two edges of the floor
+fn floor_bounds(left_edge: f32, right_edge: f32, width: f32) -> PaddleBounds {
+    let reach = (width + PADDLE_CLEARANCE) / 2.;
+    PaddleBounds {
+        left: left_edge + reach,
+        right: right_edge - reach,
+    }
+}
+
+fn full_floor_bounds(layout: &BoardLayout, width: f32) -> PaddleBounds {
+    let (left_edge, right_edge) = layout.floor_edges();
+    floor_bounds(left_edge, right_edge, width)
+}
+
+// Where a paddle `width` across can travel on the part of the floor its `bounds` were on, with
+// the floor laid out as `was` then and as `layout` now
+fn refit_floor_bounds(
+    bounds: &PaddleBounds,
+    was: &BoardLayout,
+    layout: &BoardLayout,
+    width: f32,
+) -> PaddleBounds {
+    let (left_edge, right_edge) = layout.floor_edges();
+    if bounds.left >= was.floor_middle() {
+        floor_bounds(layout.floor_middle(), right_edge, width)
+    } else if bounds.right <= was.floor_middle() {
+        floor_bounds(left_edge, layout.floor_middle(), width)
+    } else {
+        floor_bounds(left_edge, right_edge, width)
+    }
+}
+
+fn paddle_bundle(player: usize, players: usize, config: &GameConfig) -> impl Bundle {
+    let layout = &config.layout;
+    let paddle_y = layout.paddle_y();
+    let width = config.paddle_size.x;
+    let (left_edge, right_edge) = layout.floor_edges();
+    // With two players, player one takes the right half of the floor and player two the left
+    let (bounds, keys, color) = match (players, player) {
+        (1, _) => (
+            full_floor_bounds(layout, width),
+            PLAYER_ONE_KEYS,
+            config.paddle_colors[0],
+        ),
+        (_, 0) => (
+            floor_bounds(layout.floor_middle(), right_edge, width),
+            PLAYER_ONE_KEYS,
+            config.paddle_colors[0],
+        ),
+        _ => (
+            floor_bounds(left_edge, layout.floor_middle(), width),
+            PLAYER_TWO_KEYS,
+            config.paddle_colors[1],
+        ),
+    };
+    let paddle_x = (bounds.left + bounds.right) / 2.;
+    (
         SpriteBundle {
             transform: Transform {
-                translation: Vec3::new(top_x, top_y, 0.0),
-                scale: TOP_WALL_SIZE,
-                ..default()
-            },
-            sprite: Sprite {
-                color: WALL_COLOR,
+                translation: Vec3::new(paddle_x, paddle_y, 0.0),
+                scale: config.paddle_scale(),
                 ..default()
             },
+            sprite: Sprite { color, ..default() },
             ..default()
         },
+        Paddle,
+        Player(player),
+        PaddleControls {
+            left: keys.0,
+            right: keys.1,
+            fire: keys.2,
+        },
+        bounds,
         Collider,
-    ));
-    // Bottom
-    let bottom_x = 0.0 + TB_WALL_ADJUST;
-    let bottom_y = BOTTOM_WALL;
-    commands.spawn((
-        SpriteBundle {
-            transform: Transform {
-                translation: Vec3::new(bottom_x, bottom_y, 0.0),
-                scale: BOTTOM_WALL_SIZE,
+    )
+}
+
+fn spawn_walls(commands: &mut Commands, config: &GameConfig) {
+    // Draw walls: left, right, top, then the bottom one the ball is lost through
+    let [left, right, top, bottom] = config.layout.walls();
+    for (index, (middle, size)) in [left, right, top, bottom].into_iter().enumerate() {
+        let mut wall = commands.spawn((
+            SpriteBundle {
+                transform: Transform {
+                    translation: middle.extend(0.0),
+                    scale: size,
+                    ..default()
+                },
+                sprite: Sprite {
+                    color: config.wall_color,
+                    ..default()
+                },
                 ..default()
             },
-            sprite: Sprite {
-                color: WALL_COLOR,
+            Wall,
+            Collider,
+        ));
+        if index == 3 {
+            wall.insert(BottomWall);
+        }
+    }
+}
+
+fn spawn_bricks(commands: &mut Commands, level: &LevelData, layout: &BoardLayout) {
+    // Draw Grid
+    for brick in &level.bricks {
+        let translation = cell_translation(layout, brick.column, brick.row);
+        let mut brick_ent = commands.spawn(brick_bundle(translation, brick.color, layout));
+        let hit_points = (brick.kind == BrickKind::Normal).then_some(brick.hit_points);
+        if let Some(hit_points) = hit_points {
+            brick_ent.insert(HitPoints(hit_points));
+        }
+        behaviour::insert_behaviour(
+            &mut brick_ent,
+            brick.behaviour,
+            translation,
+            hit_points,
+            layout,
+        );
+    }
+}
+
+fn brick_bundle(translation: Vec3, color: Color, layout: &BoardLayout) -> impl Bundle {
+    (
+        SpriteBundle {
+            transform: Transform {
+                translation,
+                scale: layout.brick_size(),
                 ..default()
             },
+            sprite: Sprite { color, ..default() },
             ..default()
         },
-        BottomWall,
+        Brick,
         Collider,
-    ));
+    )
 }
 
-fn spawn_bricks(commands: &mut Commands) {
-    // Draw Grid
-    let mut i = 0.;
-    while i < GRID_HEIGHT {
-        let mut i2 = 0.;
-        while i2 < GRID_WIDTH {
-            let grid_cell_top = GRID_CELL_TOP - (i * GRID_CELL_HEIGHT) - (i * GRID_CELL_SPACE);
-            let grid_cell_left = GRID_CELL_LEFT + (i2 * GRID_CELL_WIDTH) + (i2 * GRID_CELL_SPACE);
-            commands.spawn((
-                SpriteBundle {
-                    transform: Transform {
-                        translation: Vec3::new(grid_cell_left, grid_cell_top, 0.0),
-                        scale: BRICK_SIZE,
-                        ..default()
-                    },
-                    sprite: Sprite {
-                        color: Color::rgb(0.25 + (i / 10.), 0.75, 0.25 + (i / 10.)),
-                        ..default()
-                    },
-                    ..default()
-                },
-                Brick,
-                Collider,
-            ));
-            i2 += 1.;
+// Fills in the paddle directions for this tick from each paddle's own keys
+fn read_local_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut inputs: ResMut<PaddleInputs>,
+    query: Query<(&Player, &PaddleControls), With<Paddle>>,
+) {
+    inputs.0.clear();
+    for (player, controls) in &query {
+        if inputs.0.len() <= player.0 {
+            inputs.0.resize(player.0 + 1, PaddleInput::default());
         }
-        i += 1.;
+        inputs.0[player.0] = paddle_input(&keyboard_input, controls);
     }
 }
 
-fn move_paddle(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut query: Query<&mut Transform, With<Paddle>>,
-) {
+fn paddle_input(keyboard_input: &Input<KeyCode>, controls: &PaddleControls) -> PaddleInput {
     let mut direction = 0.;
-    if keyboard_input.pressed(KeyCode::Left) {
+    if keyboard_input.pressed(controls.left) {
         direction = -1.0;
     }
-    if keyboard_input.pressed(KeyCode::Right) {
+    if keyboard_input.pressed(controls.right) {
         direction = 1.0;
     }
-    let mut paddle_transform = query.single_mut();
-    let new_paddle_position = paddle_transform.translation.x + (direction * PADDLE_SPEED);
+    PaddleInput {
+        direction,
+        fire: keyboard_input.pressed(controls.fire),
+    }
+}
 
-    paddle_transform.translation.x =
-        new_paddle_position.clamp(LEFT_BOUND_PADDLE, RIGHT_BOUND_PADDLE);
+fn run_gameplay_tick(world: &mut World) {
+    let ticks = world.resource_mut::<debug::TimeScale>().ticks_this_frame();
+    for _ in 0..ticks {
+        world.run_schedule(GameplayTick);
+    }
+}
+
+fn move_paddle(
+    inputs: Res<PaddleInputs>,
+    config: Res<GameConfig>,
+    mut query: Query<(&mut Transform, &Player, &PaddleBounds), With<Paddle>>,
+) {
+    for (mut paddle_transform, player, bounds) in &mut query {
+        let direction = inputs.0.get(player.0).map_or(0., |input| input.direction);
+        let new_paddle_position =
+            paddle_transform.translation.x + (direction * config.paddle_speed);
+
+        paddle_transform.translation.x = new_paddle_position.clamp(bounds.left, bounds.right);
+    }
 }
 
 fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>, time_step: Res<FixedTime>) {
@@ -498,22 +898,91 @@ fn update_scoreboard(
     mut query: Query<&mut Text, With<ScoreboardText>>,
 ) {
     let mut text = query.single_mut();
-    text.sections[1].value = scoreboard.score.to_string();
+    text.sections[1].value = match scoreboard.player_scores.as_slice() {
+        [_] => scoreboard.score.to_string(),
+        [p1, p2] => format!("{} (P1 {p1} / P2 {p2})", scoreboard.score),
+        _ => scoreboard.score.to_string(),
+    };
 }
 
 fn update_lives(lives: Res<Lives>, mut query: Query<&mut Text, With<LivesText>>) {
     let mut text = query.single_mut();
-    text.sections[1].value = lives.lives_left.to_string();
+    text.sections[1].value = match lives.lives_left.as_slice() {
+        [p1, p2] => format!("P1 {p1} / P2 {p2}"),
+        pools => pools.iter().sum::<usize>().to_string(),
+    };
+}
+
+fn configure_coop(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut coop: ResMut<CoopSettings>,
+    mut query: Query<&mut Text, With<StartMenuText>>,
+) {
+    if keyboard_input.just_released(KeyCode::Key2) {
+        coop.enabled = !coop.enabled;
+    }
+    if keyboard_input.just_released(KeyCode::L) {
+        coop.lives_mode = match coop.lives_mode {
+            LivesMode::Shared => LivesMode::Separate,
+            LivesMode::Separate => LivesMode::Shared,
+        };
+    }
+    if coop.is_changed() {
+        for mut text in &mut query {
+            text.sections[1].value = start_menu_options(&coop);
+        }
+    }
+}
+
+type BallStateQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut Velocity,
+        &'static mut LastTouchedBy,
+    ),
+    (With<Ball>, Without<multiball::ExtraBall>),
+>;
+
+// Puts the ball back where a new game starts it
+fn reset_ball(ball_query: &mut BallStateQuery, config: &GameConfig) {
+    let (mut ball_transform, mut ball_velocity, mut last_touched) = ball_query.single_mut();
+    ball_transform.translation = BALL_STARTING_POSITION;
+    ball_velocity.0 = INITIAL_BALL_DIRECTION.normalize() * config.ball_speed;
+    last_touched.0 = None;
 }
 
+// Puts fresh paddles, scores and lives in place for the chosen number of players
+fn reset_players(
+    commands: &mut Commands,
+    coop: &CoopSettings,
+    paddle_query: &Query<Entity, With<Paddle>>,
+    config: &GameConfig,
+) {
+    for paddle in paddle_query {
+        commands.entity(paddle).despawn();
+    }
+    spawn_paddles(commands, coop.player_count(), config);
+    commands.insert_resource(Scoreboard::new(coop.player_count()));
+    commands.insert_resource(Lives::new(coop.lives_pools(), config.starting_lives));
+    commands.insert_resource(Level::default());
+    commands.insert_resource(modes::ModeProgress::default());
+}
+
+#[allow(clippy::too_many_arguments)]
 fn check_for_state(
     mut commands: Commands,
     keyboard_input: Res<Input<KeyCode>>,
     start_query: Query<Entity, With<StartGameOverlay>>,
     pause_query: Query<Entity, With<PauseGameOverlay>>,
     gameover_query: Query<Entity, With<GameOverOverlay>>,
+    paddle_query: Query<Entity, With<Paddle>>,
+    coop: Res<CoopSettings>,
+    net_session: Option<Res<net::NetSession>>,
     game_state: Res<State<GameState>>,
     mut next_state: ResMut<NextState<GameState>>,
+    config: Res<GameConfig>,
 ) {
     match game_state.get() {
         GameState::NewGame => {
@@ -521,11 +990,13 @@ fn check_for_state(
                 for start_ent in &start_query {
                     commands.entity(start_ent).despawn();
                 }
+                reset_players(&mut commands, &coop, &paddle_query, &config);
                 next_state.set(GameState::InGame)
             }
         }
         GameState::InGame => {
-            if keyboard_input.just_released(KeyCode::Return) {
+            // Online games keep running on the other side, so they can't be paused
+            if keyboard_input.just_released(KeyCode::Return) && net_session.is_none() {
                 next_state.set(GameState::Paused)
             }
         }
@@ -536,8 +1007,8 @@ fn check_for_state(
                 }
                 next_state.set(GameState::InGame)
             }
-            false => {
-                next_state.set(GameState::Paused);
+            // The overlay is only put up once, not again every frame the game stays paused
+            false if pause_query.is_empty() => {
                 commands.spawn((
                     SpriteBundle {
                         transform: Transform {
@@ -571,15 +1042,19 @@ fn check_for_state(
                     PauseGameOverlay,
                 ));
             }
+            false => {}
         },
         GameState::GameOver => match keyboard_input.just_released(KeyCode::Return) {
             true => {
                 for gameover_ent in &gameover_query {
                     commands.entity(gameover_ent).despawn();
                 }
+                // An online match ends with the game, restarting goes back to local play
+                commands.remove_resource::<net::NetSession>();
+                reset_players(&mut commands, &coop, &paddle_query, &config);
                 next_state.set(GameState::InGame)
             }
-            false => {
+            false if gameover_query.is_empty() => {
                 commands.spawn((
                     SpriteBundle {
                         transform: Transform {
@@ -613,58 +1088,269 @@ fn check_for_state(
                     GameOverOverlay,
                 ));
             }
+            false => {}
         },
+        // The lobby, editor and the menu screens handle their own input
+        GameState::Lobby
+        | GameState::Editor
+        | GameState::Settings
+        | GameState::Stats
+        | GameState::Achievements => {}
     }
 }
 
+type ColliderQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        Option<&'static mut HitPoints>,
+        Option<&'static BottomWall>,
+        Option<&'static Player>,
+        Option<&'static Velocity>,
+        Option<&'static Regrows>,
+        Option<&'static BossPart>,
+        Option<&'static Brick>,
+        Option<&'static Sprite>,
+    ),
+    (With<Collider>, Without<Ball>),
+>;
+
+// Everything the ball can set off when it hits something
+#[derive(SystemParam)]
+struct CollisionEvents<'w> {
+    collisions: EventWriter<'w, CollisionEvent>,
+    explosions: EventWriter<'w, ExplosionEvent>,
+    paddle_hits: EventWriter<'w, PaddleHitEvent>,
+    boss_hits: EventWriter<'w, boss::BossHitEvent>,
+    bricks_broken: EventWriter<'w, BrickBrokenEvent>,
+}
+
+// Puts the next wall up once every breakable brick is gone, ignoring any unbreakable ones left
+// over: the next layout of the set, or the boss once the last layout of a classic set is beaten.
+// The endless wall refills itself instead.
+#[allow(clippy::too_many_arguments)]
+fn advance_wall(
+    mut commands: Commands,
+    breakable_query: Query<(), (With<Brick>, With<HitPoints>)>,
+    brick_query: Query<Entity, With<Brick>>,
+    boss_query: Query<(), With<boss::Boss>>,
+    mut level: ResMut<Level>,
+    mut current_level: ResMut<CurrentLevel>,
+    level_set: Option<Res<LevelSet>>,
+    mode: Res<modes::GameMode>,
+    mut next_state: ResMut<NextState<GameState>>,
+    config: Res<GameConfig>,
+) {
+    if !breakable_query.is_empty() || !boss_query.is_empty() || *mode == modes::GameMode::Endless {
+        return;
+    }
+    for brick in &brick_query {
+        commands.entity(brick).despawn();
+    }
+    level.index += 1;
+    let level_set = level_set.as_deref();
+    let stages = stages_per_set(level_set, *mode);
+    let stage = level.index % stages;
+    if stage == 0 {
+        start_next_set(
+            &mut commands,
+            &mut current_level,
+            level_set,
+            &mut next_state,
+            &config.layout,
+        );
+    } else if stage == walls_per_set(level_set) {
+        let encounter = level.index / stages + 1;
+        boss::spawn_boss(&mut commands, encounter as u32, &config.layout);
+    } else {
+        if let Some(level_set) = level_set {
+            current_level.0 = level_set.levels[stage].clone();
+        }
+        spawn_bricks(&mut commands, &current_level.0, &config.layout);
+    }
+}
+
+// Walls in each level set. Without a set the one layout is played this many times over.
+fn walls_per_set(level_set: Option<&LevelSet>) -> usize {
+    level_set.map_or(boss::BOSS_EVERY, |level_set| level_set.levels.len())
+}
+
+// Walls and bosses in each level set, as counted by `Level`. Only classic sets end in a boss.
+fn stages_per_set(level_set: Option<&LevelSet>, mode: modes::GameMode) -> usize {
+    walls_per_set(level_set) + usize::from(mode == modes::GameMode::Classic)
+}
+
+// Puts up the first wall of the next level set, or ends the game after a set that ends
+fn start_next_set(
+    commands: &mut Commands,
+    current_level: &mut CurrentLevel,
+    level_set: Option<&LevelSet>,
+    next_state: &mut NextState<GameState>,
+    layout: &BoardLayout,
+) {
+    if let Some(level_set) = level_set {
+        current_level.0 = level_set.levels[0].clone();
+        if level_set.ends {
+            next_state.set(GameState::GameOver);
+        }
+    }
+    spawn_bricks(commands, &current_level.0, layout);
+}
+
+type FreeBallQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Velocity,
+        &'static mut LastTouchedBy,
+        &'static mut Transform,
+        Option<&'static multiball::ExtraBall>,
+    ),
+    (With<Ball>, Without<catch::Caught>),
+>;
+
+#[allow(clippy::too_many_arguments)]
 fn check_for_collisions(
     mut commands: Commands,
-    mut ball_query: Query<(&mut Velocity, &Transform), With<Ball>>,
+    mut ball_query: FreeBallQuery,
+    all_balls_query: Query<(), With<Ball>>,
+    mut paddle_query: Query<(Entity, &Player, &mut PaddleBounds), With<Paddle>>,
     mut scoreboard: ResMut<Scoreboard>,
     mut lives: ResMut<Lives>,
-    collider_query: Query<
-        (Entity, &Transform, Option<&Brick>, Option<&BottomWall>),
-        With<Collider>,
-    >,
-    mut collision_events: EventWriter<CollisionEvent>,
-    mut explosion_events: EventWriter<ExplosionEvent>,
+    current_level: Res<CurrentLevel>,
+    coop: Res<CoopSettings>,
+    mut collider_query: ColliderQuery,
+    brick_query: Query<Entity, With<Brick>>,
+    mut events: CollisionEvents,
     mut next_state: ResMut<NextState<GameState>>,
+    config: Res<GameConfig>,
+    god_mode: Option<Res<debug::GodMode>>,
 ) {
-    let (mut ball_velocity, ball_transform) = ball_query.single_mut();
-    let ball_size = ball_transform.scale.truncate();
-
-    // check collision with walls
-    for (collider_entity, transform, maybe_brick, maybe_bottom) in &collider_query {
-        let collision = collide(
-            ball_transform.translation,
-            ball_size,
-            transform.translation,
-            transform.scale.truncate(),
-        );
-        if let Some(collision) = collision {
-            // Sends a collision event so that other systems can react to the collision
-            collision_events.send_default();
-
-            // Bricks should be despawned and increment the scoreboard on collision
-            if maybe_brick.is_some() {
-                explosion_events.send_default();
-                scoreboard.score += 1;
-                commands.entity(collider_entity).despawn();
-
-                if scoreboard.score % 50 == 0 {
-                    spawn_bricks(&mut commands)
+    // With several balls in play, only the last one through the floor costs a life
+    let mut balls_left = all_balls_query.iter().count();
+    let mut lost_balls = Vec::new();
+    let mut first_ball_lost = false;
+
+    for (ball, mut ball_velocity, mut last_touched, mut ball_transform, maybe_extra) in
+        &mut ball_query
+    {
+        let ball_size = ball_transform.scale.truncate();
+
+        // check collision with walls
+        for (
+            collider_entity,
+            transform,
+            maybe_hit_points,
+            maybe_bottom,
+            maybe_player,
+            maybe_velocity,
+            maybe_regrows,
+            maybe_boss_part,
+            maybe_brick,
+            maybe_sprite,
+        ) in &mut collider_query
+        {
+            let collision = collide(
+                ball_transform.translation,
+                ball_size,
+                transform.translation,
+                transform.scale.truncate(),
+            );
+            let Some(collision) = collision else {
+                continue;
+            };
+
+            // A spare ball through the floor is simply gone
+            if maybe_bottom.is_some() && balls_left > 1 {
+                balls_left -= 1;
+                lost_balls.push(ball);
+                match maybe_extra {
+                    Some(_) => commands.entity(ball).despawn(),
+                    None => first_ball_lost = true,
+                }
+                break;
+            }
+
+            // Sends a collision event so that other systems can react to the collision. It
+            // happened at the point of the collider nearest the ball.
+            let surface = if maybe_bottom.is_some() {
+                Surface::Floor
+            } else if maybe_player.is_some() {
+                Surface::Paddle
+            } else if maybe_boss_part.is_some() {
+                Surface::Boss
+            } else if maybe_brick.is_some() {
+                Surface::Brick
+            } else {
+                Surface::Wall
+            };
+            let half_size = transform.scale.truncate() / 2.;
+            let position = ball_transform.translation.truncate().clamp(
+                transform.translation.truncate() - half_size,
+                transform.translation.truncate() + half_size,
+            );
+            // The game may already have ended earlier in this tick, with no lives left to lose
+            let life_lost = maybe_bottom.is_some() && god_mode.is_none() && !lives.all_lost();
+            events.collisions.send(CollisionEvent {
+                position,
+                surface,
+                life_lost,
+            });
+
+            // Remember who hit the ball last so they get credit for the bricks it breaks
+            if let Some(player) = maybe_player {
+                last_touched.0 = Some(player.0);
+                events.paddle_hits.send_default();
+            }
+
+            if let Some(boss_part) = maybe_boss_part {
+                events.boss_hits.send(boss::BossHitEvent {
+                    damage: boss_part.damage,
+                    player: last_touched.0,
+                });
+            }
+
+            // Bricks should be damaged and increment the scoreboard when they break. A brick
+            // another ball broke earlier in this tick is already gone.
+            if let Some(mut hit_points) = maybe_hit_points.filter(|hit_points| hit_points.0 > 0) {
+                if damage_brick(
+                    &mut commands,
+                    collider_entity,
+                    &mut hit_points,
+                    maybe_regrows,
+                ) {
+                    events.explosions.send(ExplosionEvent {
+                        row: brick_row(transform.translation, &config.layout),
+                    });
+                    events.bricks_broken.send(BrickBrokenEvent {
+                        translation: transform.translation,
+                        color: maybe_sprite.map_or(Color::WHITE, |sprite| sprite.color),
+                    });
+                    score_brick(&mut scoreboard, last_touched.0);
                 }
             }
 
-            // Bricks should be despawned and increment the scoreboard on collision
-            if maybe_bottom.is_some() {
-                if lives.lives_left == 1 {
-                    spawn_bricks(&mut commands);
-                    scoreboard.score = 0;
+            // Hitting the floor costs a life from whoever guards that part of it
+            if life_lost {
+                let pool = lives_pool_for_landing(
+                    &coop,
+                    &lives,
+                    ball_transform.translation.x,
+                    &config.layout,
+                );
+                lives.lives_left[pool] -= 1;
+
+                if lives.all_lost() {
+                    for brick in &brick_query {
+                        commands.entity(brick).despawn();
+                    }
+                    spawn_bricks(&mut commands, &current_level.0, &config.layout);
                     next_state.set(GameState::GameOver);
-                    lives.lives_left = 3;
-                } else {
-                    lives.lives_left -= 1
+                } else if lives.lives_left[pool] == 0 {
+                    leave_floor_to_partner(&mut commands, pool, paddle_query.iter_mut(), &config);
                 }
             }
 
@@ -672,59 +1358,205 @@ fn check_for_collisions(
             let mut reflect_x = false;
             let mut reflect_y = false;
 
+            // Moving bricks are handled in their own frame of reference, so a brick catching up
+            // with the ball still knocks it away
+            let collider_velocity = maybe_velocity.map_or(Vec2::ZERO, |velocity| velocity.0);
+            let relative_velocity = ball_velocity.0 - collider_velocity;
+            let ball_speed = ball_velocity.length();
+
             // only reflect if the ball's velocity is going in the opposite direction of the
             // collision
             match collision {
-                Collision::Left => reflect_x = ball_velocity.x > 0.0,
-                Collision::Right => reflect_x = ball_velocity.x < 0.0,
-                Collision::Top => reflect_y = ball_velocity.y < 0.0,
-                Collision::Bottom => reflect_y = ball_velocity.y > 0.0,
+                Collision::Left => reflect_x = relative_velocity.x > 0.0,
+                Collision::Right => reflect_x = relative_velocity.x < 0.0,
+                Collision::Top => reflect_y = relative_velocity.y < 0.0,
+                Collision::Bottom => reflect_y = relative_velocity.y > 0.0,
                 Collision::Inside => { /* do nothing */ }
             }
 
             // reflect velocity on the x-axis if we hit something on the x-axis
             if reflect_x {
-                ball_velocity.x = -ball_velocity.x;
+                ball_velocity.x = collider_velocity.x - relative_velocity.x;
             }
 
             // reflect velocity on the y-axis if we hit something on the y-axis
             if reflect_y {
-                ball_velocity.y = -ball_velocity.y;
+                ball_velocity.y = collider_velocity.y - relative_velocity.y;
+            }
+
+            // A moving brick may have swept into the ball since the last tick, so the ball is
+            // pushed back out to the side it was hit from, keeping its speed
+            if maybe_velocity.is_some() && (reflect_x || reflect_y) {
+                let reach = (transform.scale.truncate() + ball_size) / 2.;
+                match collision {
+                    Collision::Left => {
+                        ball_transform.translation.x = transform.translation.x - reach.x
+                    }
+                    Collision::Right => {
+                        ball_transform.translation.x = transform.translation.x + reach.x
+                    }
+                    Collision::Top => {
+                        ball_transform.translation.y = transform.translation.y + reach.y
+                    }
+                    Collision::Bottom => {
+                        ball_transform.translation.y = transform.translation.y - reach.y
+                    }
+                    Collision::Inside => {}
+                }
+                ball_velocity.0 = ball_velocity.normalize_or_zero() * ball_speed;
+            }
+        }
+    }
+
+    // The first ball is never despawned, so when it is lost an extra ball takes its place
+    if first_ball_lost {
+        let replacement = ball_query
+            .iter()
+            .filter(|(ball, .., maybe_extra)| maybe_extra.is_some() && !lost_balls.contains(ball))
+            .map(|(ball, velocity, last_touched, transform, _)| {
+                (ball, velocity.0, last_touched.0, transform.translation)
+            })
+            .next();
+        if let Some((extra, velocity, last_touched_by, translation)) = replacement {
+            for (_, mut ball_velocity, mut last_touched, mut ball_transform, maybe_extra) in
+                &mut ball_query
+            {
+                if maybe_extra.is_none() {
+                    ball_velocity.0 = velocity;
+                    last_touched.0 = last_touched_by;
+                    ball_transform.translation = translation;
+                }
             }
+            commands.entity(extra).despawn();
         }
     }
 }
 
+// The grid row a brick is on, which moving and descending bricks may have left
+fn brick_row(translation: Vec3, layout: &BoardLayout) -> Option<u32> {
+    level::cell_at(layout, translation.truncate(), u32::MAX).map(|(_, row)| row)
+}
+
+// Credits a broken brick to the game and to the player who broke it
+fn score_brick(scoreboard: &mut Scoreboard, player: Option<usize>) {
+    scoreboard.score += 1;
+    if let Some(player_score) = player.and_then(|player| scoreboard.player_scores.get_mut(player)) {
+        *player_score += 1;
+    }
+}
+
+// Takes a hit point off a brick, despawning it when none are left, or putting it to sleep if
+// it grows back. Returns whether it broke.
+fn damage_brick(
+    commands: &mut Commands,
+    brick: Entity,
+    hit_points: &mut HitPoints,
+    regrows: Option<&Regrows>,
+) -> bool {
+    hit_points.0 = hit_points.0.saturating_sub(1);
+    if hit_points.0 == 0 {
+        match regrows {
+            Some(regrows) => behaviour::make_dormant(commands, brick, regrows),
+            None => {
+                commands
+                    .entity(brick)
+                    .remove::<(Brick, Collider, HitPoints, Velocity)>()
+                    .insert(Broken);
+            }
+        }
+        return true;
+    }
+    false
+}
+
+// The eliminated player's paddle leaves and their partner covers the whole floor
+fn leave_floor_to_partner<'a>(
+    commands: &mut Commands,
+    pool: usize,
+    paddles: impl Iterator<Item = (Entity, &'a Player, Mut<'a, PaddleBounds>)>,
+    config: &GameConfig,
+) {
+    for (paddle, player, mut bounds) in paddles {
+        if player.0 == pool {
+            commands.entity(paddle).despawn();
+        } else {
+            *bounds = full_floor_bounds(&config.layout, config.paddle_size.x);
+        }
+    }
+}
+
+// Picks which lives pool pays for a ball landing at `x`
+fn lives_pool_for_landing(
+    coop: &CoopSettings,
+    lives: &Lives,
+    x: f32,
+    layout: &BoardLayout,
+) -> usize {
+    if lives.lives_left.len() == 1 {
+        return 0;
+    }
+    let guard = if coop.enabled && x < layout.floor_middle() {
+        1
+    } else {
+        0
+    };
+    if lives.lives_left[guard] > 0 {
+        guard
+    } else {
+        lives
+            .lives_left
+            .iter()
+            .position(|lives| *lives > 0)
+            .unwrap_or(guard)
+    }
+}
+
+// Each surface has its own sounds in the sound bank. Bricks struck one after another without
+// the ball coming back to a paddle climb in pitch, and every sound is panned to where it
+// happened.
 fn play_collision_sound(
-    mut commands: Commands,
     mut collision_events: EventReader<CollisionEvent>,
-    sound: Res<CollisionSound>,
+    mut sound_effects: audio::SoundEffects,
+    mut combo: Local<u32>,
+    config: Res<GameConfig>,
+    resimulating: Option<Res<net::Resimulating>>,
 ) {
-    // Play a sound once per frame if a collision occurred.
-    if !collision_events.is_empty() {
-        // This prevents events staying active on the next frame.
-        collision_events.clear();
-        commands.spawn(AudioBundle {
-            source: sound.0.clone(),
-            // auto-despawn the entity when playback finishes
-            settings: PlaybackSettings::DESPAWN,
-        });
+    let replayed = resimulating.map_or(0, |replayed| replayed.collisions);
+    for collision in collision_events.iter().skip(replayed) {
+        let event = match collision.surface {
+            Surface::Wall => SoundEvent::Wall,
+            Surface::Paddle => SoundEvent::Paddle,
+            Surface::Brick => SoundEvent::Brick,
+            Surface::Boss => SoundEvent::Boss,
+            Surface::Floor => SoundEvent::Floor,
+        };
+        let speed = match collision.surface {
+            Surface::Brick | Surface::Boss => {
+                let semitones = (*combo).min(COMBO_MAX_SEMITONES) as f32;
+                *combo += 1;
+                2f32.powf(semitones / 12.0)
+            }
+            Surface::Paddle | Surface::Floor => {
+                *combo = 0;
+                1.0
+            }
+            Surface::Wall => 1.0,
+        };
+        let pan = collision.position.x / config.layout.right_wall;
+        sound_effects.play(event, speed, pan);
     }
 }
 
 fn play_explosion_sound(
-    mut commands: Commands,
     mut explosion_events: EventReader<ExplosionEvent>,
-    sound: Res<ExplosionSound>,
+    mut sound_effects: audio::SoundEffects,
+    resimulating: Option<Res<net::Resimulating>>,
 ) {
+    let replayed = resimulating.map_or(0, |replayed| replayed.explosions);
     // Play a sound once per frame if a explosion occurred.
-    if !explosion_events.is_empty() {
-        // This prevents events staying active on the next frame.
-        explosion_events.clear();
-        commands.spawn(AudioBundle {
-            source: sound.0.clone(),
-            // auto-despawn the entity when playback finishes
-            settings: PlaybackSettings::DESPAWN,
-        });
+    if explosion_events.len() > replayed {
+        sound_effects.play(SoundEvent::Explosion, 1.0, 0.0);
     }
+    // This prevents events staying active on the next frame.
+    explosion_events.clear();
 }
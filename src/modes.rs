@@ -0,0 +1,371 @@
+use bevy::{input::common_conditions::input_just_released, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::HeadlessRun;
+use crate::config::{BoardLayout, GameConfig};
+use crate::daily::{date_string, today, DailyRun};
+use crate::generator::{brick_color, Rng};
+use crate::level::CurrentLevel;
+use crate::net::NetSession;
+use crate::replay::Playback;
+use crate::storage::{load_ron, save_ron};
+use crate::{
+    brick_bundle, spawn_bricks, Brick, BrickKind, GameState, HitPoints, Scoreboard, StartMenuText,
+};
+
+// Time attack
+pub const TIME_ATTACK_SECONDS: f32 = 90.0;
+
+// Endless
+// How fast the wall creeps down, in pixels per second
+const ENDLESS_DESCENT_SPEED: f32 = 6.0;
+const ENDLESS_ROW_DENSITY: f32 = 0.8;
+// Every this many rows, new bricks can take one more hit
+const ENDLESS_ROWS_PER_TOUGHNESS: u32 = 10;
+const ENDLESS_MAX_HIT_POINTS: u32 = 4;
+
+// High scores
+const HIGH_SCORES_PATH: &str = "highscores.ron";
+const HIGH_SCORE_TABLE_SIZE: usize = 5;
+
+// Mode text
+const MODE_FONT_SIZE: f32 = 30.0;
+const MODE_TEXT_TOP: Val = Val::Px(5.0);
+const MODE_TEXT_LEFT: Val = Val::Px(560.0);
+const MODE_TEXT_COLOR: Color = Color::rgb(1.0, 0.5, 0.5);
+
+// High score text
+const HIGH_SCORE_FONT_SIZE: f32 = 22.0;
+const HIGH_SCORE_TEXT_COLOR: Color = Color::rgb(1.0, 0.8, 0.4);
+const HIGH_SCORE_VERTICAL_PADDING: Val = Val::Px(440.0);
+const HIGH_SCORE_LEFT_PADDING: Val = Val::Px(475.0);
+
+pub struct ModesPlugin;
+
+impl Plugin for ModesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameMode>()
+            .init_resource::<ModeProgress>()
+            .add_systems(Startup, spawn_mode_text)
+            .add_systems(
+                Update,
+                (
+                    cycle_game_mode.run_if(input_just_released(KeyCode::M)),
+                    update_mode_menu_text,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::NewGame)),
+            )
+            .add_systems(Update, update_mode_text)
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                record_high_score
+                    .run_if(not(resource_exists::<DailyRun>()))
+                    .run_if(not(resource_exists::<NetSession>()))
+                    // Scripted and replayed games aren't anybody's score
+                    .run_if(not(resource_exists::<HeadlessRun>()))
+                    .run_if(not(resource_exists::<Playback>())),
+            )
+            .add_systems(OnExit(GameState::GameOver), clear_high_score_text)
+            .add_systems(
+                crate::GameplayTick,
+                advance_game_mode.after(crate::check_for_collisions),
+            );
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    // Clear the wall to move on to the next one
+    #[default]
+    Classic,
+    // Break as many bricks as possible before the clock runs out
+    TimeAttack,
+    // The wall keeps growing downwards until it reaches the paddles
+    Endless,
+}
+
+impl GameMode {
+    fn name(self) -> &'static str {
+        match self {
+            GameMode::Classic => "classic",
+            GameMode::TimeAttack => "time attack",
+            GameMode::Endless => "endless",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            GameMode::Classic => GameMode::TimeAttack,
+            GameMode::TimeAttack => GameMode::Endless,
+            GameMode::Endless => GameMode::Classic,
+        }
+    }
+}
+
+// The part of a mode's rules that changes as the game is played
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct ModeProgress {
+    // Seconds of play so far
+    pub elapsed: f32,
+    // How far the endless wall has slid since its last new row
+    pub descended: f32,
+    pub rows_added: u32,
+}
+
+#[derive(Component)]
+struct ModeText;
+
+#[derive(Component)]
+struct HighScoreText;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct HighScore {
+    score: usize,
+    day: i64,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct HighScores {
+    classic: Vec<HighScore>,
+    time_attack: Vec<HighScore>,
+    endless: Vec<HighScore>,
+}
+
+impl HighScores {
+    fn load() -> Result<Self, String> {
+        load_ron(HIGH_SCORES_PATH)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        save_ron(HIGH_SCORES_PATH, self)
+    }
+
+    fn table(&self, mode: GameMode) -> &Vec<HighScore> {
+        match mode {
+            GameMode::Classic => &self.classic,
+            GameMode::TimeAttack => &self.time_attack,
+            GameMode::Endless => &self.endless,
+        }
+    }
+
+    // Adds a score to the mode's table, returning its place if it made the cut
+    fn insert(&mut self, mode: GameMode, entry: HighScore) -> Option<usize> {
+        let table = match mode {
+            GameMode::Classic => &mut self.classic,
+            GameMode::TimeAttack => &mut self.time_attack,
+            GameMode::Endless => &mut self.endless,
+        };
+        let place = table.partition_point(|other| other.score >= entry.score);
+        if place >= HIGH_SCORE_TABLE_SIZE {
+            return None;
+        }
+        table.insert(place, entry);
+        table.truncate(HIGH_SCORE_TABLE_SIZE);
+        Some(place)
+    }
+}
+
+fn cycle_game_mode(mut mode: ResMut<GameMode>) {
+    *mode = mode.next();
+}
+
+// The start screen's last section belongs to the mode picker
+fn update_mode_menu_text(mode: Res<GameMode>, mut query: Query<(Ref<StartMenuText>, &mut Text)>) {
+    for (menu, mut text) in &mut query {
+        if !mode.is_changed() && !menu.is_added() {
+            continue;
+        }
+        let best = HighScores::load()
+            .unwrap_or_default()
+            .table(*mode)
+            .first()
+            .map_or(String::new(), |best| format!(" (best {})", best.score));
+        text.sections[2].value = format!("\nM: {} mode{best}", mode.name());
+    }
+}
+
+fn spawn_mode_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: MODE_FONT_SIZE,
+                color: MODE_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: MODE_TEXT_TOP,
+            left: MODE_TEXT_LEFT,
+            ..default()
+        }),
+        ModeText,
+    ));
+}
+
+fn update_mode_text(
+    mode: Res<GameMode>,
+    progress: Res<ModeProgress>,
+    mut query: Query<&mut Text, With<ModeText>>,
+) {
+    let mut text = query.single_mut();
+    text.sections[0].value = match *mode {
+        GameMode::Classic => String::new(),
+        GameMode::TimeAttack => {
+            let left = (TIME_ATTACK_SECONDS - progress.elapsed).max(0.).ceil() as u32;
+            format!("Time: {}:{:02}", left / 60, left % 60)
+        }
+        GameMode::Endless => format!("Rows: {}", progress.rows_added),
+    };
+}
+
+type SlidingBrickQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static mut Transform, Option<&'static HitPoints>), With<Brick>>;
+
+// Runs the clock for timed modes and slides the endless wall down
+#[allow(clippy::too_many_arguments)]
+fn advance_game_mode(
+    mut commands: Commands,
+    mode: Res<GameMode>,
+    mut progress: ResMut<ModeProgress>,
+    time_step: Res<FixedTime>,
+    mut brick_query: SlidingBrickQuery,
+    current_level: Res<CurrentLevel>,
+    mut next_state: ResMut<NextState<GameState>>,
+    config: Res<GameConfig>,
+) {
+    let step = time_step.period.as_secs_f32();
+    progress.elapsed += step;
+
+    let game_over = match *mode {
+        GameMode::Classic => false,
+        GameMode::TimeAttack => progress.elapsed >= TIME_ATTACK_SECONDS,
+        GameMode::Endless => {
+            let layout = &config.layout;
+            let row_step = layout.cell_step().y;
+            // An empty wall would leave nothing to do until the next row, so it comes at once
+            let wall_cleared = brick_query
+                .iter()
+                .all(|(_, _, hit_points)| hit_points.is_none());
+            let slide = if wall_cleared {
+                row_step - progress.descended
+            } else {
+                ENDLESS_DESCENT_SPEED * step
+            };
+            for (_, mut transform, _) in &mut brick_query {
+                transform.translation.y -= slide;
+            }
+            progress.descended += slide;
+            if progress.descended >= row_step {
+                progress.descended -= row_step;
+                let seed = current_level.0.seed.unwrap_or_default();
+                let (rows_added, descended) = (progress.rows_added, progress.descended);
+                spawn_endless_row(&mut commands, seed, rows_added, descended, layout);
+                progress.rows_added += 1;
+            }
+            // Unbreakable bricks crumble at the paddle line, only breakable ones end the game.
+            // Bricks reaching it have overrun the paddles.
+            let paddle_line = layout.paddle_y() + (config.paddle_size.y / 2.);
+            let mut overrun = false;
+            for (brick, transform, hit_points) in &brick_query {
+                if transform.translation.y - (transform.scale.y / 2.) <= paddle_line {
+                    match hit_points {
+                        Some(_) => overrun = true,
+                        None => commands.entity(brick).despawn(),
+                    }
+                }
+            }
+            overrun
+        }
+    };
+
+    // The board is put back for the next game, as when the last life is lost, unless that
+    // already happened this tick
+    if game_over && next_state.0 != Some(GameState::GameOver) {
+        for (brick, _, _) in &brick_query {
+            commands.entity(brick).despawn();
+        }
+        spawn_bricks(&mut commands, &current_level.0, &config.layout);
+        next_state.set(GameState::GameOver);
+    }
+}
+
+// A fresh row along the top of the grid. Rows only depend on the seed and their number, so
+// they come out the same after a rollback or when a save is continued.
+fn spawn_endless_row(
+    commands: &mut Commands,
+    seed: u64,
+    row_number: u32,
+    descended: f32,
+    layout: &BoardLayout,
+) {
+    let mut rng = Rng::new(seed ^ (row_number as u64).wrapping_mul(0x9E37_79B9));
+    let hit_points_cap = (1 + row_number / ENDLESS_ROWS_PER_TOUGHNESS).min(ENDLESS_MAX_HIT_POINTS);
+    for column in 0..layout.grid_columns {
+        if rng.next_f32() >= ENDLESS_ROW_DENSITY {
+            continue;
+        }
+        let hit_points = 1 + rng.below(hit_points_cap);
+        let mut translation = crate::level::cell_translation(layout, column, 0);
+        translation.y -= descended;
+        let color = brick_color(BrickKind::Normal, hit_points, row_number % 5);
+        let brick = brick_bundle(translation, color, layout);
+        commands.spawn((brick, HitPoints(hit_points)));
+    }
+}
+
+fn record_high_score(mut commands: Commands, mode: Res<GameMode>, scoreboard: Res<Scoreboard>) {
+    let mut high_scores = HighScores::load().unwrap_or_else(|error| {
+        error!("Could not read the high scores: {error}");
+        HighScores::default()
+    });
+    let place = high_scores.insert(
+        *mode,
+        HighScore {
+            score: scoreboard.score,
+            day: today(),
+        },
+    );
+    if place.is_some() {
+        if let Err(error) = high_scores.save() {
+            error!("Could not write the high scores: {error}");
+        }
+    }
+
+    let mut lines = format!("Best {} scores\n", mode.name());
+    for (index, entry) in high_scores.table(*mode).iter().enumerate() {
+        let marker = if Some(index) == place { " <" } else { "" };
+        lines.push_str(&format!(
+            "{}. {}  {}{marker}\n",
+            index + 1,
+            entry.score,
+            date_string(entry.day)
+        ));
+    }
+    commands.spawn((
+        TextBundle::from_section(
+            lines,
+            TextStyle {
+                font_size: HIGH_SCORE_FONT_SIZE,
+                color: HIGH_SCORE_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: HIGH_SCORE_VERTICAL_PADDING,
+            left: HIGH_SCORE_LEFT_PADDING,
+            ..default()
+        }),
+        HighScoreText,
+    ));
+}
+
+fn clear_high_score_text(mut commands: Commands, query: Query<Entity, With<HighScoreText>>) {
+    for text in &query {
+        commands.entity(text).despawn();
+    }
+}
@@ -0,0 +1,97 @@
+use bevy::{prelude::*, sprite::Mesh2dHandle};
+
+use crate::catch::Caught;
+use crate::{Ball, GameState, LastTouchedBy, Velocity};
+
+// Multi-ball
+// Each free ball splits into itself and this many more
+const SPLIT_COUNT: usize = 2;
+// Angle between the directions of neighbouring balls of a split, in radians
+const SPLIT_ANGLE: f32 = 0.35;
+// No more balls than this are ever in play at once
+const MAX_BALLS: usize = 9;
+
+pub struct MultiBallPlugin;
+
+impl Plugin for MultiBallPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::GameOver), clear_extra_balls);
+    }
+}
+
+// Every ball after the first. The first ball is the one that is always there, so a lost
+// extra ball just goes away, while losing the first one makes an extra take its place.
+#[derive(Component)]
+pub struct ExtraBall;
+
+// The look every ball shares, so extra balls can be spawned at any time
+#[derive(Resource, Clone)]
+pub struct BallAssets {
+    pub mesh: Mesh2dHandle,
+    pub material: Handle<ColorMaterial>,
+    pub scale: Vec3,
+}
+
+pub fn extra_ball_bundle(
+    assets: &BallAssets,
+    translation: Vec3,
+    velocity: Vec2,
+    last_touched_by: Option<usize>,
+) -> impl Bundle {
+    (
+        ColorMesh2dBundle {
+            mesh: assets.mesh.clone(),
+            material: assets.material.clone(),
+            transform: Transform::from_translation(translation).with_scale(assets.scale),
+            ..default()
+        },
+        Ball,
+        ExtraBall,
+        LastTouchedBy(last_touched_by),
+        Velocity(velocity),
+    )
+}
+
+pub type SplitBallQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Transform,
+        &'static Velocity,
+        &'static LastTouchedBy,
+    ),
+    (With<Ball>, Without<Caught>),
+>;
+
+// Fans every free ball out into several, up to the limit
+pub fn split_balls(
+    commands: &mut Commands,
+    assets: &BallAssets,
+    ball_query: &SplitBallQuery,
+    balls_in_play: usize,
+) {
+    let mut balls = balls_in_play;
+    for (transform, velocity, last_touched) in ball_query {
+        for split in 1..=SPLIT_COUNT {
+            if balls >= MAX_BALLS {
+                return;
+            }
+            // Alternate sides, opening up further with each new ball
+            let side = if split % 2 == 1 { 1.0 } else { -1.0 };
+            let angle = side * SPLIT_ANGLE * split.div_ceil(2) as f32;
+            commands.spawn(extra_ball_bundle(
+                assets,
+                transform.translation,
+                Vec2::from_angle(angle).rotate(velocity.0),
+                last_touched.0,
+            ));
+            balls += 1;
+        }
+    }
+}
+
+fn clear_extra_balls(mut commands: Commands, ball_query: Query<Entity, With<ExtraBall>>) {
+    for ball in &ball_query {
+        commands.entity(ball).despawn();
+    }
+}
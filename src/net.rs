@@ -0,0 +1,721 @@
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use bevy::{prelude::*, window::ReceivedCharacter};
+
+use crate::catch::CatchOption;
+use crate::config::GameConfig;
+use crate::laser::LaserHitEvent;
+use crate::level::{CurrentLevel, LevelData};
+use crate::modes::GameMode;
+use crate::snapshot::GameSnapshot;
+use crate::{
+    paddle_input, reset_ball, reset_players, run_gameplay_tick, spawn_bricks, spawn_start_overlay,
+    BallStateQuery, Brick, BrickBrokenEvent, CollisionEvent, CoopSettings, ExplosionEvent,
+    GameState, GameplayTick, LivesMode, Paddle, PaddleControls, PaddleInput, PaddleInputs,
+    StartGameOverlay, PLAYER_ONE_KEYS,
+};
+
+// Networking
+const NET_DEFAULT_PORT: u16 = 7000;
+const NET_DEFAULT_ADDRESS: &str = "127.0.0.1:7000";
+// Local input is applied this many ticks late, which hides small amounts of latency
+const NET_INPUT_DELAY: u32 = 2;
+// How far ahead of the last confirmed remote input we may predict before waiting for the peer
+const NET_MAX_PREDICTION: u32 = 8;
+// How often the peers compare state hashes
+const NET_CHECKSUM_INTERVAL: u32 = 30;
+// Cap on how many inputs go into one packet
+const NET_MAX_INPUTS_PER_PACKET: usize = 64;
+// Room for the largest packet, the welcome with the host's tuning in it
+const NET_MAX_PACKET_SIZE: usize = 4096;
+
+// Lobby text and Overlay
+const LOBBY_VERTICAL_PADDING: Val = Val::Px(250.0);
+const LOBBY_LEFT_PADDING: Val = Val::Px(300.0);
+const LOBBY_FONT_SIZE: f32 = 40.0;
+const LOBBY_TEXT_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
+const LOBBY_OVERLAY_SIZE: Vec3 = Vec3::new(1500.0, 1500.0, 0.0);
+const LOBBY_OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.95);
+
+// Online status text
+const NET_STATUS_FONT_SIZE: f32 = 24.0;
+const NET_STATUS_PADDING: Val = Val::Px(8.0);
+const NET_STATUS_TEXT_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
+const NET_DESYNC_TEXT_COLOR: Color = Color::rgb(1.0, 0.3, 0.3);
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, enter_lobby.run_if(in_state(GameState::NewGame)))
+            .add_systems(
+                Update,
+                (edit_lobby_address, connect_lobby, update_lobby_text)
+                    .chain()
+                    .run_if(in_state(GameState::Lobby)),
+            )
+            // Ahead of everything that follows the tick, so the sounds and effects of the ticks
+            // simulated here come the same frame
+            .add_systems(
+                Update,
+                (advance_net_session, update_net_status)
+                    .chain()
+                    .before(run_gameplay_tick)
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(resource_exists::<NetSession>()),
+            )
+            .add_systems(
+                Last,
+                end_resimulation.run_if(resource_exists::<Resimulating>()),
+            )
+            .add_systems(
+                Update,
+                clear_net_status.run_if(not(resource_exists::<NetSession>())),
+            );
+    }
+}
+
+#[derive(Component)]
+struct LobbyOverlay;
+
+#[derive(Component)]
+struct LobbyText;
+
+#[derive(Component)]
+struct NetStatusText;
+
+enum LobbyRole {
+    Host,
+    Join,
+}
+
+// The host/join screen, alive while in `GameState::Lobby`
+#[derive(Resource)]
+struct Lobby {
+    role: LobbyRole,
+    address: String,
+    socket: Option<UdpSocket>,
+    status: String,
+}
+
+enum Message {
+    Hello,
+    // The host's tuning, which the joiner plays with too so both simulate the same game
+    Welcome {
+        config: Box<GameConfig>,
+    },
+    // `ack` is how many of the receiver's inputs the sender already has
+    Inputs {
+        ack: u32,
+        start: u32,
+        inputs: Vec<i8>,
+    },
+    Checksum {
+        frame: u32,
+        checksum: u64,
+    },
+}
+
+impl Message {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Message::Hello => bytes.push(0),
+            Message::Welcome { config } => {
+                bytes.push(1);
+                // The config always serializes, being plain numbers and colors
+                bytes.extend(ron::to_string(config).unwrap_or_default().into_bytes());
+            }
+            Message::Inputs { ack, start, inputs } => {
+                bytes.push(2);
+                bytes.extend_from_slice(&ack.to_le_bytes());
+                bytes.extend_from_slice(&start.to_le_bytes());
+                bytes.extend(inputs.iter().map(|input| *input as u8));
+            }
+            Message::Checksum { frame, checksum } => {
+                bytes.push(3);
+                bytes.extend_from_slice(&frame.to_le_bytes());
+                bytes.extend_from_slice(&checksum.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Message> {
+        let u32_at = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+        match bytes.first()? {
+            0 => Some(Message::Hello),
+            1 => {
+                let config: GameConfig = ron::de::from_bytes(bytes.get(1..)?).ok()?;
+                config.validate().ok()?;
+                Some(Message::Welcome {
+                    config: Box::new(config),
+                })
+            }
+            2 => Some(Message::Inputs {
+                ack: u32_at(1)?,
+                start: u32_at(5)?,
+                inputs: bytes.get(9..)?.iter().map(|input| *input as i8).collect(),
+            }),
+            3 => Some(Message::Checksum {
+                frame: u32_at(1)?,
+                checksum: u64::from_le_bytes(bytes.get(5..13)?.try_into().ok()?),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// How many of each event the ticks replayed after a rollback sent this frame. Those ticks were
+// seen and heard the first time they were played, so the sounds and effects skip their events,
+// which come ahead of the new tick's.
+#[derive(Resource, Clone, Copy, Default)]
+pub struct Resimulating {
+    pub collisions: usize,
+    pub explosions: usize,
+    pub bricks_broken: usize,
+    pub laser_hits: usize,
+}
+
+impl Resimulating {
+    fn count(world: &World) -> Self {
+        Resimulating {
+            collisions: world.resource::<Events<CollisionEvent>>().len(),
+            explosions: world.resource::<Events<ExplosionEvent>>().len(),
+            bricks_broken: world.resource::<Events<BrickBrokenEvent>>().len(),
+            laser_hits: world.resource::<Events<LaserHitEvent>>().len(),
+        }
+    }
+
+    // The events sent since `before` was counted
+    fn since(before: Self, world: &World) -> Self {
+        let after = Resimulating::count(world);
+        Resimulating {
+            collisions: after.collisions - before.collisions,
+            explosions: after.explosions - before.explosions,
+            bricks_broken: after.bricks_broken - before.bricks_broken,
+            laser_hits: after.laser_hits - before.laser_hits,
+        }
+    }
+}
+
+// A running online match. Both peers simulate every tick from the same inputs; the remote
+// player's input is predicted until it arrives, and a wrong guess rolls the game back to the
+// tick it was made on and replays from there.
+#[derive(Resource)]
+pub struct NetSession {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    local_player: usize,
+    // The tuning the game is played with, sent again if the joiner missed the welcome
+    config: GameConfig,
+    // The next tick to simulate
+    frame: u32,
+    local_inputs: Vec<i8>,
+    remote_inputs: Vec<Option<i8>>,
+    // What was assumed for the remote player on each simulated tick
+    predicted_inputs: Vec<i8>,
+    // How many of our inputs the peer has confirmed receiving
+    peer_ack: u32,
+    // The state at the start of each tick that might still be rolled back to
+    snapshots: VecDeque<(u32, GameSnapshot)>,
+    checked_frame: u32,
+    local_checksums: HashMap<u32, u64>,
+    remote_checksums: HashMap<u32, u64>,
+    desync_frame: Option<u32>,
+}
+
+impl NetSession {
+    fn new(socket: UdpSocket, peer: SocketAddr, local_player: usize, config: GameConfig) -> Self {
+        NetSession {
+            socket,
+            peer,
+            local_player,
+            config,
+            frame: 0,
+            local_inputs: vec![0; NET_INPUT_DELAY as usize],
+            remote_inputs: Vec::new(),
+            predicted_inputs: Vec::new(),
+            peer_ack: 0,
+            snapshots: VecDeque::new(),
+            checked_frame: 0,
+            local_checksums: HashMap::new(),
+            remote_checksums: HashMap::new(),
+            desync_frame: None,
+        }
+    }
+
+    // Number of leading ticks for which the remote input is known
+    fn confirmed_frames(&self) -> u32 {
+        self.remote_inputs
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.remote_inputs.len()) as u32
+    }
+
+    fn remote_input(&self, frame: u32) -> i8 {
+        match self.remote_inputs.get(frame as usize).copied().flatten() {
+            Some(input) => input,
+            // Guess that the remote player is still doing whatever they last did
+            None => self.remote_inputs[..self.confirmed_frames() as usize]
+                .last()
+                .copied()
+                .flatten()
+                .unwrap_or(0),
+        }
+    }
+
+    fn send(&self, message: Message) {
+        // Lost packets are covered by the next send, so errors are not fatal
+        let _ = self.socket.send_to(&message.encode(), self.peer);
+    }
+
+    fn send_inputs(&self) {
+        let start = self.peer_ack.min(self.local_inputs.len() as u32);
+        let end = self
+            .local_inputs
+            .len()
+            .min(start as usize + NET_MAX_INPUTS_PER_PACKET);
+        self.send(Message::Inputs {
+            ack: self.confirmed_frames(),
+            start,
+            inputs: self.local_inputs[start as usize..end].to_vec(),
+        });
+    }
+
+    // Reads every pending packet and returns the earliest tick that was simulated with a wrong
+    // prediction, if any
+    fn receive(&mut self) -> Option<u32> {
+        let mut rollback_to: Option<u32> = None;
+        let mut buffer = [0; NET_MAX_PACKET_SIZE];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) if transient(&error) => continue,
+                Err(error) => {
+                    error!("Could not read from the network: {error}");
+                    break;
+                }
+            };
+            if from != self.peer {
+                continue;
+            }
+            match Message::decode(&buffer[..len]) {
+                // The peer never got our welcome and is still knocking
+                Some(Message::Hello) => self.send(Message::Welcome {
+                    config: Box::new(self.config.clone()),
+                }),
+                Some(Message::Inputs { ack, start, inputs }) => {
+                    self.peer_ack = self.peer_ack.max(ack);
+                    for (offset, input) in inputs.into_iter().enumerate() {
+                        let frame = start + offset as u32;
+                        if self.remote_inputs.len() <= frame as usize {
+                            self.remote_inputs.resize(frame as usize + 1, None);
+                        }
+                        if self.remote_inputs[frame as usize].is_some() {
+                            continue;
+                        }
+                        self.remote_inputs[frame as usize] = Some(input);
+                        let mispredicted = self
+                            .predicted_inputs
+                            .get(frame as usize)
+                            .is_some_and(|predicted| *predicted != input);
+                        if mispredicted {
+                            rollback_to = Some(rollback_to.map_or(frame, |f| f.min(frame)));
+                        }
+                    }
+                }
+                Some(Message::Checksum { frame, checksum }) => {
+                    self.remote_checksums.insert(frame, checksum);
+                }
+                Some(Message::Welcome { .. }) | None => {}
+            }
+        }
+        rollback_to
+    }
+
+    fn snapshot(&self, frame: u32) -> Option<&GameSnapshot> {
+        self.snapshots
+            .iter()
+            .find(|(snapshot_frame, _)| *snapshot_frame == frame)
+            .map(|(_, snapshot)| snapshot)
+    }
+
+    // Hashes the state of ticks that can no longer change and compares them with the peer's
+    fn check_sync(&mut self) {
+        let confirmed = self.confirmed_frames();
+        while self.checked_frame <= confirmed && self.checked_frame < self.frame {
+            let frame = self.checked_frame;
+            if frame.is_multiple_of(NET_CHECKSUM_INTERVAL) {
+                if let Some(checksum) = self.snapshot(frame).map(GameSnapshot::checksum) {
+                    self.local_checksums.insert(frame, checksum);
+                    self.send(Message::Checksum { frame, checksum });
+                }
+            }
+            self.checked_frame += 1;
+        }
+        let compared: Vec<u32> = self
+            .remote_checksums
+            .keys()
+            .filter(|frame| self.local_checksums.contains_key(frame))
+            .copied()
+            .collect();
+        for frame in compared {
+            let local = self.local_checksums.remove(&frame);
+            let remote = self.remote_checksums.remove(&frame);
+            if local != remote && self.desync_frame.is_none() {
+                error!("Online game desynced at tick {frame}");
+                self.desync_frame = Some(frame);
+            }
+        }
+        // Nothing before the first unconfirmed tick can be rolled back to again
+        let oldest_needed = confirmed.min(self.checked_frame);
+        self.snapshots.retain(|(frame, _)| *frame >= oldest_needed);
+    }
+}
+
+// Simulates one tick with the inputs known or guessed for it
+fn simulate_frame(world: &mut World, session: &mut NetSession, frame: u32) {
+    let snapshot = GameSnapshot::capture(world);
+    session.snapshots.retain(|(saved, _)| *saved != frame);
+    session.snapshots.push_back((frame, snapshot));
+
+    let local = session.local_inputs[frame as usize];
+    let remote = session.remote_input(frame);
+    if session.predicted_inputs.len() <= frame as usize {
+        session.predicted_inputs.resize(frame as usize + 1, 0);
+    }
+    session.predicted_inputs[frame as usize] = remote;
+
+    let mut inputs = vec![PaddleInput::default(); 2];
+    inputs[session.local_player] = PaddleInput::from_byte(local);
+    inputs[1 - session.local_player] = PaddleInput::from_byte(remote);
+    world.resource_mut::<PaddleInputs>().0 = inputs;
+    world.run_schedule(GameplayTick);
+}
+
+fn advance_net_session(world: &mut World) {
+    let Some(mut session) = world.remove_resource::<NetSession>() else {
+        return;
+    };
+
+    // Online players both use player one's keys for their own paddle
+    let controls = PaddleControls {
+        left: PLAYER_ONE_KEYS.0,
+        right: PLAYER_ONE_KEYS.1,
+        fire: PLAYER_ONE_KEYS.2,
+    };
+    let input = paddle_input(world.resource::<Input<KeyCode>>(), &controls);
+    if session.local_inputs.len() <= (session.frame + NET_INPUT_DELAY) as usize {
+        session.local_inputs.push(input.to_byte());
+    }
+    session.send_inputs();
+
+    if let Some(rollback_to) = session.receive() {
+        if let Some(snapshot) = session.snapshot(rollback_to).cloned() {
+            let before = Resimulating::count(world);
+            snapshot.restore(world);
+            for frame in rollback_to..session.frame {
+                simulate_frame(world, &mut session, frame);
+            }
+            world.insert_resource(Resimulating::since(before, world));
+        } else {
+            // The misprediction can't be put right, so the games have parted ways
+            error!("Online game can't roll back to tick {rollback_to}, its snapshot is gone");
+            session.desync_frame.get_or_insert(rollback_to);
+        }
+    }
+    session.check_sync();
+
+    // Wait for the peer rather than guess too far ahead
+    if session.frame - session.confirmed_frames().min(session.frame) < NET_MAX_PREDICTION {
+        let frame = session.frame;
+        simulate_frame(world, &mut session, frame);
+        session.frame += 1;
+    }
+
+    world.insert_resource(session);
+}
+
+fn end_resimulation(mut commands: Commands) {
+    commands.remove_resource::<Resimulating>();
+}
+
+fn update_net_status(session: Res<NetSession>, mut query: Query<&mut Text, With<NetStatusText>>) {
+    for mut text in &mut query {
+        let section = &mut text.sections[0];
+        match session.desync_frame {
+            Some(frame) => {
+                section.value = format!("DESYNC at tick {frame}");
+                section.style.color = NET_DESYNC_TEXT_COLOR;
+            }
+            None => {
+                section.value = format!(
+                    "Online P{} | tick {} | waiting on {}",
+                    session.local_player + 1,
+                    session.frame,
+                    session.frame - session.confirmed_frames().min(session.frame)
+                );
+            }
+        }
+    }
+}
+
+fn clear_net_status(mut commands: Commands, query: Query<Entity, With<NetStatusText>>) {
+    for status in &query {
+        commands.entity(status).despawn();
+    }
+}
+
+fn enter_lobby(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    start_query: Query<Entity, With<StartGameOverlay>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let role = if keyboard_input.just_released(KeyCode::H) {
+        LobbyRole::Host
+    } else if keyboard_input.just_released(KeyCode::J) {
+        LobbyRole::Join
+    } else {
+        return;
+    };
+
+    for start_ent in &start_query {
+        commands.entity(start_ent).despawn();
+    }
+
+    let mut lobby = Lobby {
+        role,
+        address: NET_DEFAULT_ADDRESS.to_string(),
+        socket: None,
+        status: String::new(),
+    };
+    if let LobbyRole::Host = lobby.role {
+        match bind(&format!("0.0.0.0:{NET_DEFAULT_PORT}")) {
+            Ok(socket) => {
+                lobby.status = format!("Hosting on port {NET_DEFAULT_PORT}, waiting for a player");
+                lobby.socket = Some(socket);
+            }
+            Err(error) => lobby.status = format!("Could not host: {error}"),
+        }
+    }
+    commands.insert_resource(lobby);
+
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, 1.0),
+                scale: LOBBY_OVERLAY_SIZE,
+                ..default()
+            },
+            sprite: Sprite {
+                color: LOBBY_OVERLAY_COLOR,
+                ..default()
+            },
+            ..default()
+        },
+        LobbyOverlay,
+    ));
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: LOBBY_FONT_SIZE,
+                color: LOBBY_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: LOBBY_VERTICAL_PADDING,
+            left: LOBBY_LEFT_PADDING,
+            ..default()
+        }),
+        LobbyOverlay,
+        LobbyText,
+    ));
+
+    next_state.set(GameState::Lobby);
+}
+
+fn bind(address: &str) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(address)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+// Errors a read reports for one packet, such as a notice that an earlier send found nobody
+// listening, and that the next read is past. Any other error would only come back again.
+fn transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::Interrupted
+    )
+}
+
+fn edit_lobby_address(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut lobby: ResMut<Lobby>,
+    lobby_query: Query<Entity, With<LobbyOverlay>>,
+    coop: Res<CoopSettings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if keyboard_input.just_released(KeyCode::Tab) {
+        for lobby_ent in &lobby_query {
+            commands.entity(lobby_ent).despawn();
+        }
+        commands.remove_resource::<Lobby>();
+        spawn_start_overlay(&mut commands, &coop);
+        next_state.set(GameState::NewGame);
+        return;
+    }
+
+    if !matches!(lobby.role, LobbyRole::Join) || lobby.socket.is_some() {
+        characters.clear();
+        return;
+    }
+    for event in characters.iter() {
+        if event.char.is_ascii_alphanumeric() || matches!(event.char, '.' | ':') {
+            lobby.address.push(event.char);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        lobby.address.pop();
+    }
+    if keyboard_input.just_released(KeyCode::Return) {
+        match lobby.address.parse::<SocketAddr>() {
+            Ok(_) => match bind("0.0.0.0:0") {
+                Ok(socket) => {
+                    lobby.status = format!("Joining {}", lobby.address);
+                    lobby.socket = Some(socket);
+                }
+                Err(error) => lobby.status = format!("Could not join: {error}"),
+            },
+            Err(_) => lobby.status = format!("{} is not an ip:port address", lobby.address),
+        }
+    }
+}
+
+// Runs the handshake: the joiner says hello until the host welcomes it with its tuning
+#[allow(clippy::too_many_arguments)]
+fn connect_lobby(
+    mut commands: Commands,
+    mut lobby: ResMut<Lobby>,
+    mut coop: ResMut<CoopSettings>,
+    lobby_query: Query<Entity, With<LobbyOverlay>>,
+    paddle_query: Query<Entity, With<Paddle>>,
+    brick_query: Query<Entity, With<Brick>>,
+    mut ball_query: BallStateQuery,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut config: ResMut<GameConfig>,
+) {
+    let Some(socket) = &lobby.socket else {
+        return;
+    };
+    let joined_address = lobby.address.parse::<SocketAddr>().ok();
+    if let (LobbyRole::Join, Some(host)) = (&lobby.role, joined_address) {
+        let _ = socket.send_to(&Message::Hello.encode(), host);
+    }
+
+    let mut buffer = [0; NET_MAX_PACKET_SIZE];
+    let peer = loop {
+        let (len, from) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => return,
+            Err(error) if transient(&error) => continue,
+            Err(error) => {
+                error!("Could not read from the network: {error}");
+                return;
+            }
+        };
+        match (&lobby.role, Message::decode(&buffer[..len])) {
+            (LobbyRole::Host, Some(Message::Hello)) => {
+                let welcome = Message::Welcome {
+                    config: Box::new(config.clone()),
+                };
+                let _ = socket.send_to(&welcome.encode(), from);
+                break from;
+            }
+            (
+                LobbyRole::Join,
+                Some(Message::Welcome {
+                    config: host_config,
+                }),
+            ) if Some(from) == joined_address => {
+                if *config != *host_config {
+                    *config = *host_config;
+                }
+                break from;
+            }
+            _ => {}
+        }
+    };
+
+    let local_player = match lobby.role {
+        LobbyRole::Host => 0,
+        LobbyRole::Join => 1,
+    };
+    let socket = lobby.socket.take().unwrap();
+    commands.insert_resource(NetSession::new(socket, peer, local_player, config.clone()));
+    commands.remove_resource::<Lobby>();
+    for lobby_ent in &lobby_query {
+        commands.entity(lobby_ent).despawn();
+    }
+
+    // Both sides must start from exactly the same board
+    coop.enabled = true;
+    coop.lives_mode = LivesMode::Separate;
+    commands.insert_resource(GameMode::Classic);
+    commands.insert_resource(CatchOption::default());
+    reset_players(&mut commands, &coop, &paddle_query, &config);
+    for brick in &brick_query {
+        commands.entity(brick).despawn();
+    }
+    let level = LevelData::classic(&config.layout);
+    spawn_bricks(&mut commands, &level, &config.layout);
+    commands.insert_resource(CurrentLevel(level));
+    reset_ball(&mut ball_query, &config);
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: NET_STATUS_FONT_SIZE,
+                color: NET_STATUS_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: NET_STATUS_PADDING,
+            right: NET_STATUS_PADDING,
+            ..default()
+        }),
+        NetStatusText,
+    ));
+
+    next_state.set(GameState::InGame);
+}
+
+fn update_lobby_text(lobby: Option<Res<Lobby>>, mut query: Query<&mut Text, With<LobbyText>>) {
+    let Some(lobby) = lobby else {
+        return;
+    };
+    for mut text in &mut query {
+        text.sections[0].value = match (&lobby.role, &lobby.socket) {
+            (LobbyRole::Join, None) => format!(
+                "Join address: {}_\nENTER to connect\nTAB to go back\n{}",
+                lobby.address, lobby.status
+            ),
+            _ => format!("{}\nTAB to go back", lobby.status),
+        };
+    }
+}
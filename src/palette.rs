@@ -0,0 +1,327 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::boss::BossPart;
+use crate::config::GameConfig;
+use crate::level::cell_at;
+use crate::settings::Settings;
+use crate::{Brick, HitPoints};
+
+// Palettes
+// Colors chosen to stay apart for each kind of color vision. Single-hit bricks are colored by
+// the row they start in, tougher ones by the hits they have left.
+const PALETTE_ROWS: usize = 5;
+const TOUGH_COLORS: usize = 4;
+
+// Blues and yellows, which red-green color blindness leaves alone, at well spread lightness
+const DEUTERANOPIA: PaletteColors = PaletteColors {
+    rows: [
+        Color::rgb(0.0, 0.45, 0.7),
+        Color::rgb(0.34, 0.71, 0.91),
+        Color::rgb(0.8, 0.8, 0.8),
+        Color::rgb(0.94, 0.89, 0.26),
+        Color::rgb(0.9, 0.6, 0.0),
+    ],
+    tough: [
+        Color::rgb(0.8, 0.47, 0.65),
+        Color::rgb(0.6, 0.3, 0.5),
+        Color::rgb(0.45, 0.2, 0.4),
+        Color::rgb(0.3, 0.1, 0.3),
+    ],
+    unbreakable: Color::rgb(0.35, 0.35, 0.35),
+};
+// Much like deuteranopia, but reds look dark, so orange is left out
+const PROTANOPIA: PaletteColors = PaletteColors {
+    rows: [
+        Color::rgb(0.0, 0.3, 0.6),
+        Color::rgb(0.2, 0.55, 0.85),
+        Color::rgb(0.55, 0.8, 0.95),
+        Color::rgb(0.9, 0.9, 0.7),
+        Color::rgb(0.95, 0.85, 0.2),
+    ],
+    tough: [
+        Color::rgb(0.7, 0.6, 0.3),
+        Color::rgb(0.55, 0.45, 0.2),
+        Color::rgb(0.4, 0.32, 0.12),
+        Color::rgb(0.25, 0.2, 0.05),
+    ],
+    unbreakable: Color::rgb(0.45, 0.45, 0.5),
+};
+// Reds and teals, which blue-yellow color blindness leaves alone
+const TRITANOPIA: PaletteColors = PaletteColors {
+    rows: [
+        Color::rgb(0.0, 0.45, 0.45),
+        Color::rgb(0.3, 0.75, 0.75),
+        Color::rgb(0.9, 0.9, 0.9),
+        Color::rgb(0.95, 0.6, 0.65),
+        Color::rgb(0.85, 0.2, 0.25),
+    ],
+    tough: [
+        Color::rgb(0.6, 0.15, 0.2),
+        Color::rgb(0.45, 0.1, 0.15),
+        Color::rgb(0.3, 0.05, 0.1),
+        Color::rgb(0.2, 0.0, 0.05),
+    ],
+    unbreakable: Color::rgb(0.4, 0.4, 0.4),
+};
+// Pure, bright colors against the dark background
+const HIGH_CONTRAST: PaletteColors = PaletteColors {
+    rows: [
+        Color::WHITE,
+        Color::YELLOW,
+        Color::CYAN,
+        Color::rgb(0.4, 1.0, 0.4),
+        Color::FUCHSIA,
+    ],
+    tough: [
+        Color::rgb(1.0, 0.6, 0.0),
+        Color::rgb(1.0, 0.3, 0.0),
+        Color::RED,
+        Color::rgb(0.6, 0.0, 0.0),
+    ],
+    unbreakable: Color::rgb(0.25, 0.25, 0.25),
+};
+
+// Glyphs
+const GLYPH_FONT_SIZE: f32 = 20.0;
+// Above the bricks, under their flashes and particles
+const GLYPH_DEPTH: f32 = 0.2;
+const UNBREAKABLE_GLYPH: &str = "X";
+// Glyphs are dark on light bricks and light on dark ones
+const GLYPH_DARK: Color = Color::rgba(0.0, 0.0, 0.0, 0.75);
+const GLYPH_LIGHT: Color = Color::rgba(1.0, 1.0, 1.0, 0.85);
+const GLYPH_LIGHTNESS_SPLIT: f32 = 0.5;
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        // After the frame's despawns have gone through, so no brick is colored on its way out
+        app.add_systems(
+            PostUpdate,
+            (
+                remember_level_colors,
+                apply_deferred,
+                paint_bricks,
+                label_bricks,
+            )
+                .chain(),
+        );
+    }
+}
+
+// Which colors bricks are drawn in, picked in the settings
+#[derive(Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Palette {
+    // Each brick in the color its level gave it
+    #[default]
+    Level,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    HighContrast,
+}
+
+struct PaletteColors {
+    rows: [Color; PALETTE_ROWS],
+    // For 2, 3, 4 and 5 or more hits left
+    tough: [Color; TOUGH_COLORS],
+    unbreakable: Color,
+}
+
+impl Palette {
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Level => "level colors",
+            Palette::Deuteranopia => "deuteranopia",
+            Palette::Protanopia => "protanopia",
+            Palette::Tritanopia => "tritanopia",
+            Palette::HighContrast => "high contrast",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Palette::Level => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Protanopia,
+            Palette::Protanopia => Palette::Tritanopia,
+            Palette::Tritanopia => Palette::HighContrast,
+            Palette::HighContrast => Palette::Level,
+        }
+    }
+
+    fn colors(self) -> Option<&'static PaletteColors> {
+        match self {
+            Palette::Level => None,
+            Palette::Deuteranopia => Some(&DEUTERANOPIA),
+            Palette::Protanopia => Some(&PROTANOPIA),
+            Palette::Tritanopia => Some(&TRITANOPIA),
+            Palette::HighContrast => Some(&HIGH_CONTRAST),
+        }
+    }
+}
+
+// The color a brick's level gave it, kept while a palette draws it in another. Saves and the
+// network checksum use this, so the palette each player picks makes no difference to them.
+#[derive(Component)]
+pub struct LevelColor {
+    pub color: Color,
+    // The grid row the brick started in
+    row: usize,
+}
+
+// A brick's glyph, which follows it about
+#[derive(Component)]
+struct BrickGlyph {
+    brick: Entity,
+}
+
+type NewBrickQuery<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static Transform, &'static Sprite),
+    (With<Brick>, Without<LevelColor>, Without<BossPart>),
+>;
+
+fn remember_level_colors(
+    mut commands: Commands,
+    brick_query: NewBrickQuery,
+    config: Res<GameConfig>,
+) {
+    for (brick, transform, sprite) in &brick_query {
+        let position = transform.translation.truncate();
+        let row = cell_at(&config.layout, position, u32::MAX).map_or(0, |(_, row)| row);
+        commands.entity(brick).insert(LevelColor {
+            color: sprite.color,
+            row: row as usize,
+        });
+    }
+}
+
+fn paint_bricks(
+    settings: Res<Settings>,
+    mut brick_query: Query<(&LevelColor, Option<&HitPoints>, &mut Sprite), With<Brick>>,
+) {
+    let colors = settings.palette.colors();
+    for (level_color, hit_points, mut sprite) in &mut brick_query {
+        let color = match (colors, hit_points) {
+            (None, _) => level_color.color,
+            (Some(colors), None) => colors.unbreakable,
+            (Some(colors), Some(HitPoints(0 | 1))) => colors.rows[level_color.row % PALETTE_ROWS],
+            (Some(colors), Some(HitPoints(hit_points))) => {
+                colors.tough[(*hit_points as usize - 2).min(TOUGH_COLORS - 1)]
+            }
+        };
+        // Only set when it differs, so the sprite isn't marked changed every frame
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+type LabelledBrickQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static Sprite,
+        &'static Visibility,
+        Option<&'static HitPoints>,
+    ),
+    (With<LevelColor>, With<Brick>),
+>;
+
+type GlyphQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static BrickGlyph,
+        &'static mut Transform,
+        &'static mut Text,
+        &'static mut Visibility,
+    ),
+    Without<Brick>,
+>;
+
+// With glyphs on, unbreakable bricks are marked with a cross and the others with the hits they
+// have left, so neither needs telling apart by color
+fn label_bricks(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    brick_query: LabelledBrickQuery,
+    mut glyph_query: GlyphQuery,
+) {
+    let mut labelled = HashSet::new();
+    for (glyph, brick_glyph, mut transform, mut text, mut visibility) in &mut glyph_query {
+        let brick = brick_query.get(brick_glyph.brick);
+        let Ok((brick, brick_transform, sprite, brick_visibility, hit_points)) = brick else {
+            commands.entity(glyph).despawn();
+            continue;
+        };
+        if !settings.brick_glyphs {
+            commands.entity(glyph).despawn();
+            continue;
+        }
+        labelled.insert(brick);
+        transform.translation = brick_transform.translation.truncate().extend(GLYPH_DEPTH);
+        let section = &mut text.sections[0];
+        let value = glyph_text(hit_points);
+        if section.value != value {
+            section.value = value;
+        }
+        let color = glyph_color(sprite.color);
+        if section.style.color != color {
+            section.style.color = color;
+        }
+        if *visibility != *brick_visibility {
+            *visibility = *brick_visibility;
+        }
+    }
+    if !settings.brick_glyphs {
+        return;
+    }
+
+    for (brick, transform, sprite, _, hit_points) in &brick_query {
+        if labelled.contains(&brick) {
+            continue;
+        }
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section(
+                    glyph_text(hit_points),
+                    TextStyle {
+                        font_size: GLYPH_FONT_SIZE.min(transform.scale.y),
+                        color: glyph_color(sprite.color),
+                        ..default()
+                    },
+                ),
+                transform: Transform::from_translation(
+                    transform.translation.truncate().extend(GLYPH_DEPTH),
+                ),
+                ..default()
+            },
+            BrickGlyph { brick },
+        ));
+    }
+}
+
+fn glyph_text(hit_points: Option<&HitPoints>) -> String {
+    match hit_points {
+        Some(hit_points) => hit_points.0.to_string(),
+        None => UNBREAKABLE_GLYPH.to_string(),
+    }
+}
+
+fn glyph_color(brick_color: Color) -> Color {
+    let [red, green, blue, _] = brick_color.as_rgba_f32();
+    let lightness = 0.2126 * red + 0.7152 * green + 0.0722 * blue;
+    if lightness > GLYPH_LIGHTNESS_SPLIT {
+        GLYPH_DARK
+    } else {
+        GLYPH_LIGHT
+    }
+}
@@ -0,0 +1,272 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+
+use crate::config::GameConfig;
+use crate::generator::Rng;
+use crate::net::Resimulating;
+use crate::settings::Settings;
+use crate::{run_gameplay_tick, Ball, BrickBrokenEvent, CollisionEvent, GameState, Surface};
+
+// Particles
+// Every particle there can ever be is spawned up front and reused, so a big explosion costs no
+// allocations. When they are all in use new ones are skipped.
+const PARTICLE_POOL_SIZE: usize = 600;
+const PARTICLE_DEPTH: f32 = 0.5;
+
+// Brick shards
+const SHARD_COUNT: usize = 14;
+const REDUCED_SHARD_COUNT: usize = 4;
+const SHARD_SIZE: f32 = 9.0;
+const SHARD_SPEED: f32 = 260.0;
+const SHARD_LIFE: f32 = 0.7;
+const SHARD_GRAVITY: f32 = -700.0;
+
+// Impact sparks
+const SPARK_COUNT: usize = 6;
+const REDUCED_SPARK_COUNT: usize = 2;
+const SPARK_SIZE: f32 = 4.0;
+const SPARK_SPEED: f32 = 320.0;
+const SPARK_LIFE: f32 = 0.25;
+const SPARK_COLOR: Color = Color::rgb(1.0, 0.9, 0.5);
+
+// Ball trail
+const TRAIL_SIZE: f32 = 16.0;
+const TRAIL_LIFE: f32 = 0.2;
+const TRAIL_ALPHA: f32 = 0.5;
+
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, fill_particle_pool).add_systems(
+            Update,
+            (
+                (emit_brick_shards, emit_impact_sparks, emit_ball_trail)
+                    .after(run_gameplay_tick)
+                    .run_if(in_state(GameState::InGame)),
+                animate_particles.run_if(not(in_state(GameState::Paused))),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Component, Default)]
+struct Particle {
+    velocity: Vec2,
+    gravity: f32,
+    life: f32,
+    max_life: f32,
+    size: f32,
+    color: Color,
+}
+
+// Pooled particles that aren't showing
+#[derive(Resource)]
+struct ParticlePool {
+    free: Vec<Entity>,
+    // Particles are only for show, so they don't need the game's seeded randomness
+    rng: Rng,
+}
+
+type ParticleQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Particle,
+        &'static mut Transform,
+        &'static mut Sprite,
+        &'static mut Visibility,
+    ),
+>;
+
+impl ParticlePool {
+    fn emit(&mut self, particles: &mut ParticleQuery, position: Vec2, particle: Particle) {
+        let Some(entity) = self.free.pop() else {
+            return;
+        };
+        let Ok((mut slot, mut transform, mut sprite, mut visibility)) = particles.get_mut(entity)
+        else {
+            return;
+        };
+        transform.translation = position.extend(PARTICLE_DEPTH);
+        transform.scale = Vec3::new(particle.size, particle.size, 1.0);
+        sprite.color = particle.color;
+        *visibility = Visibility::Inherited;
+        *slot = particle;
+    }
+
+    // Sends `count` particles flying out in random directions at up to `speed`
+    #[allow(clippy::too_many_arguments)]
+    fn burst(
+        &mut self,
+        particles: &mut ParticleQuery,
+        position: Vec2,
+        count: usize,
+        speed: f32,
+        size: f32,
+        life: f32,
+        gravity: f32,
+        color: Color,
+    ) {
+        for _ in 0..count {
+            let angle = self.rng.next_f32() * std::f32::consts::TAU;
+            let speed = speed * (0.4 + 0.6 * self.rng.next_f32());
+            let life = life * (0.6 + 0.4 * self.rng.next_f32());
+            self.emit(
+                particles,
+                position,
+                Particle {
+                    velocity: Vec2::from_angle(angle) * speed,
+                    gravity,
+                    life,
+                    max_life: life,
+                    size,
+                    color,
+                },
+            );
+        }
+    }
+}
+
+fn fill_particle_pool(mut commands: Commands) {
+    let free = (0..PARTICLE_POOL_SIZE)
+        .map(|_| {
+            commands
+                .spawn((
+                    SpriteBundle {
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    Particle::default(),
+                ))
+                .id()
+        })
+        .collect();
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    commands.insert_resource(ParticlePool {
+        free,
+        rng: Rng::new(seed),
+    });
+}
+
+// Broken bricks burst into shards of their own color that fall away
+fn emit_brick_shards(
+    mut bricks_broken: EventReader<BrickBrokenEvent>,
+    settings: Res<Settings>,
+    mut pool: ResMut<ParticlePool>,
+    mut particles: ParticleQuery,
+    resimulating: Option<Res<Resimulating>>,
+) {
+    let count = if settings.reduced_effects {
+        REDUCED_SHARD_COUNT
+    } else {
+        SHARD_COUNT
+    };
+    let replayed = resimulating.map_or(0, |replayed| replayed.bricks_broken);
+    for broken in bricks_broken.iter().skip(replayed) {
+        pool.burst(
+            &mut particles,
+            broken.translation.truncate(),
+            count,
+            SHARD_SPEED,
+            SHARD_SIZE,
+            SHARD_LIFE,
+            SHARD_GRAVITY,
+            broken.color,
+        );
+    }
+}
+
+// The ball throws sparks where it strikes the walls and paddles
+fn emit_impact_sparks(
+    mut collisions: EventReader<CollisionEvent>,
+    settings: Res<Settings>,
+    mut pool: ResMut<ParticlePool>,
+    mut particles: ParticleQuery,
+    resimulating: Option<Res<Resimulating>>,
+) {
+    let count = if settings.reduced_effects {
+        REDUCED_SPARK_COUNT
+    } else {
+        SPARK_COUNT
+    };
+    let replayed = resimulating.map_or(0, |replayed| replayed.collisions);
+    for collision in collisions.iter().skip(replayed) {
+        if !matches!(collision.surface, Surface::Wall | Surface::Paddle) {
+            continue;
+        }
+        pool.burst(
+            &mut particles,
+            collision.position,
+            count,
+            SPARK_SPEED,
+            SPARK_SIZE,
+            SPARK_LIFE,
+            0.0,
+            SPARK_COLOR,
+        );
+    }
+}
+
+// Each ball leaves a short fading trail, left out with reduced effects
+fn emit_ball_trail(
+    settings: Res<Settings>,
+    config: Res<GameConfig>,
+    ball_query: Query<&Transform, (With<Ball>, Without<Particle>)>,
+    mut pool: ResMut<ParticlePool>,
+    mut particles: ParticleQuery,
+) {
+    if settings.reduced_effects {
+        return;
+    }
+    for transform in &ball_query {
+        pool.emit(
+            &mut particles,
+            transform.translation.truncate(),
+            Particle {
+                velocity: Vec2::ZERO,
+                gravity: 0.0,
+                life: TRAIL_LIFE,
+                max_life: TRAIL_LIFE,
+                size: TRAIL_SIZE,
+                color: config.ball_color.with_a(TRAIL_ALPHA),
+            },
+        );
+    }
+}
+
+// Particles drift, fade and shrink, and go back in the pool when they run out
+fn animate_particles(
+    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    mut particles: Query<(
+        Entity,
+        &mut Particle,
+        &mut Transform,
+        &mut Sprite,
+        &mut Visibility,
+    )>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite, mut visibility) in &mut particles {
+        if particle.life <= 0.0 {
+            continue;
+        }
+        particle.life -= dt;
+        if particle.life <= 0.0 {
+            *visibility = Visibility::Hidden;
+            pool.free.push(entity);
+            continue;
+        }
+        particle.velocity.y += particle.gravity * dt;
+        transform.translation += (particle.velocity * dt).extend(0.0);
+        let left = particle.life / particle.max_life;
+        let size = particle.size * (0.5 + 0.5 * left);
+        transform.scale = Vec3::new(size, size, 1.0);
+        sprite.color = particle.color.with_a(particle.color.a() * left);
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::{prelude::*, sprite::collide_aabb::collide};
+use serde::{Deserialize, Serialize};
+
+use crate::catch::{CatchPaddle, CATCH_DURATION};
+use crate::config::GameConfig;
+use crate::generator::Rng;
+use crate::laser::LaserCannons;
+use crate::multiball::{split_balls, BallAssets, SplitBallQuery};
+use crate::{
+    check_for_collisions, Ball, BrickBrokenEvent, GameState, GameplayTick, Paddle, Scoreboard,
+    Velocity,
+};
+
+// Power-ups
+// Share of broken bricks that drop a power-up
+const POWER_UP_CHANCE: f32 = 0.12;
+const POWER_UP_SIZE: Vec3 = Vec3::new(40.0, 16.0, 0.0);
+const POWER_UP_FALL_SPEED: f32 = 150.0;
+
+pub struct PowerUpsPlugin;
+
+impl Plugin for PowerUpsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            GameplayTick,
+            (
+                drop_power_ups.after(check_for_collisions),
+                catch_power_ups.after(check_for_collisions),
+            ),
+        )
+        .add_systems(OnEnter(GameState::GameOver), clear_power_ups);
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PowerUp {
+    Laser,
+    // The paddle holds on to the ball until the player launches it
+    Catch,
+    MultiBall,
+}
+
+impl PowerUp {
+    const ALL: [PowerUp; 3] = [PowerUp::Laser, PowerUp::Catch, PowerUp::MultiBall];
+
+    fn color(self) -> Color {
+        match self {
+            PowerUp::Laser => Color::rgb(1.0, 0.3, 0.3),
+            PowerUp::Catch => Color::rgb(0.3, 1.0, 0.4),
+            PowerUp::MultiBall => Color::rgb(0.6, 0.4, 1.0),
+        }
+    }
+}
+
+pub fn power_up_bundle(power_up: PowerUp, translation: Vec3) -> impl Bundle {
+    (
+        SpriteBundle {
+            transform: Transform {
+                translation,
+                scale: POWER_UP_SIZE,
+                ..default()
+            },
+            sprite: Sprite {
+                color: power_up.color(),
+                ..default()
+            },
+            ..default()
+        },
+        power_up,
+        Velocity(Vec2::new(0.0, -POWER_UP_FALL_SPEED)),
+    )
+}
+
+// Some broken bricks let a power-up fall. The roll is seeded from the brick's place and the
+// score, so peers in an online game and replays of a snapshot agree on every drop.
+fn drop_power_ups(
+    mut commands: Commands,
+    mut bricks_broken: EventReader<BrickBrokenEvent>,
+    scoreboard: Res<Scoreboard>,
+) {
+    for broken in bricks_broken.iter() {
+        let seed = ((broken.translation.x.to_bits() as u64) << 32)
+            ^ broken.translation.y.to_bits() as u64
+            ^ (scoreboard.score as u64).rotate_left(17);
+        let mut rng = Rng::new(seed);
+        if rng.next_f32() >= POWER_UP_CHANCE {
+            continue;
+        }
+        let power_up = PowerUp::ALL[rng.below(PowerUp::ALL.len() as u32) as usize];
+        commands.spawn(power_up_bundle(power_up, broken.translation));
+    }
+}
+
+// A paddle that catches a power-up gets it, topping it up if it already has it. Missed ones
+// are lost through the floor.
+fn catch_power_ups(
+    mut commands: Commands,
+    power_up_query: Query<(Entity, &Transform, &PowerUp)>,
+    paddle_query: Query<(Entity, &Transform), With<Paddle>>,
+    free_ball_query: SplitBallQuery,
+    all_balls_query: Query<(), With<Ball>>,
+    ball_assets: Res<BallAssets>,
+    config: Res<GameConfig>,
+) {
+    for (entity, transform, power_up) in &power_up_query {
+        if transform.translation.y < config.layout.bottom_wall {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let catcher = paddle_query.iter().find(|(_, paddle_transform)| {
+            collide(
+                transform.translation,
+                transform.scale.truncate(),
+                paddle_transform.translation,
+                paddle_transform.scale.truncate(),
+            )
+            .is_some()
+        });
+        let Some((paddle, _)) = catcher else {
+            continue;
+        };
+        commands.entity(entity).despawn();
+        match power_up {
+            PowerUp::Laser => {
+                commands.entity(paddle).insert(LaserCannons::new());
+            }
+            PowerUp::Catch => {
+                commands.entity(paddle).insert(CatchPaddle {
+                    time_left: CATCH_DURATION,
+                });
+            }
+            PowerUp::MultiBall => split_balls(
+                &mut commands,
+                &ball_assets,
+                &free_ball_query,
+                all_balls_query.iter().count(),
+            ),
+        }
+    }
+}
+
+fn clear_power_ups(mut commands: Commands, power_up_query: Query<Entity, With<PowerUp>>) {
+    for power_up in &power_up_query {
+        commands.entity(power_up).despawn();
+    }
+}
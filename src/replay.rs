@@ -0,0 +1,228 @@
+use std::path::{Path, PathBuf};
+
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::catch::CatchOption;
+use crate::config::GameConfig;
+use crate::level::{CurrentLevel, LevelData};
+use crate::modes::GameMode;
+use crate::snapshot::GameSnapshot;
+use crate::storage::{read_ron, save_ron};
+use crate::{
+    read_local_input, run_gameplay_tick, CoopSettings, GameState, LocalTick, PaddleInput,
+    PaddleInputs, StartGameOverlay,
+};
+
+// Bump this whenever `ReplayFile` changes. Old replays are refused rather than played wrongly.
+const REPLAY_VERSION: u32 = 1;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ReplayEnded>()
+            .add_systems(
+                Update,
+                start_replay
+                    .run_if(in_state(GameState::NewGame))
+                    .run_if(resource_exists::<Playback>()),
+            )
+            .add_systems(
+                Update,
+                (
+                    play_back_inputs.run_if(resource_exists::<Playback>()),
+                    record_inputs.run_if(resource_exists::<Recording>()),
+                )
+                    .in_set(LocalTick)
+                    .after(read_local_input)
+                    .before(run_gameplay_tick),
+            )
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                finish_recording.run_if(resource_exists::<Recording>()),
+            )
+            // Quitting mid-game keeps what was played so far
+            .add_systems(
+                Last,
+                finish_recording
+                    .run_if(resource_exists::<Recording>())
+                    .run_if(on_event::<AppExit>()),
+            );
+    }
+}
+
+// A recorded game: where it started, and what the players did on every tick after. The
+// gameplay tick is deterministic, so that is enough to play it again exactly.
+#[derive(Serialize, Deserialize)]
+pub struct ReplayFile {
+    version: u32,
+    coop: CoopSettings,
+    level: LevelData,
+    mode: GameMode,
+    catch: CatchOption,
+    // Tuning changed while recording isn't kept, only what the game started with
+    config: GameConfig,
+    start: GameSnapshot,
+    // Runs of ticks with the same inputs, as the number of ticks and each player's input as it
+    // is sent over the network
+    inputs: Vec<(u32, Vec<i8>)>,
+}
+
+impl ReplayFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let replay: ReplayFile = read_ron(path)?;
+        if replay.version != REPLAY_VERSION {
+            return Err(format!(
+                "the replay is version {}, but this game plays version {REPLAY_VERSION}",
+                replay.version
+            ));
+        }
+        Ok(replay)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        save_ron(path, self)
+    }
+}
+
+// A replay being played, and how far into its inputs it is
+#[derive(Resource)]
+pub struct Playback {
+    replay: ReplayFile,
+    run: usize,
+    tick_in_run: u32,
+}
+
+impl Playback {
+    pub fn new(replay: ReplayFile) -> Self {
+        Playback {
+            replay,
+            run: 0,
+            tick_in_run: 0,
+        }
+    }
+}
+
+// Sent on the last tick of a replay
+#[derive(Event)]
+pub struct ReplayEnded;
+
+// A game being recorded, started on its first tick
+#[derive(Resource)]
+pub struct Recording {
+    path: PathBuf,
+    replay: Option<ReplayFile>,
+}
+
+impl Recording {
+    pub fn new(path: PathBuf) -> Self {
+        Recording { path, replay: None }
+    }
+}
+
+// Puts the game back as the replay started, the way a save is continued
+fn start_replay(world: &mut World) {
+    // The recorded tuning goes in first and is applied at the end of the frame, so applying it
+    // doesn't rescale the balls put back below
+    let config = world.resource::<Playback>().replay.config.clone();
+    if *world.resource::<GameConfig>() != config {
+        world.insert_resource(config);
+        return;
+    }
+
+    let mut start_query = world.query_filtered::<Entity, With<StartGameOverlay>>();
+    let start_ents: Vec<Entity> = start_query.iter(world).collect();
+    for start_ent in start_ents {
+        world.despawn(start_ent);
+    }
+
+    let replay = &world.resource::<Playback>().replay;
+    let coop = replay.coop.clone();
+    let level = replay.level.clone();
+    let mode = replay.mode;
+    let catch = replay.catch;
+    let start = replay.start.clone();
+    world.insert_resource(coop);
+    world.insert_resource(CurrentLevel(level));
+    world.insert_resource(mode);
+    world.insert_resource(catch);
+    start.restore(world);
+    world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::InGame);
+}
+
+// Feeds the tick the recorded inputs in place of the keyboard's. Once they run out the players
+// take over.
+fn play_back_inputs(
+    mut commands: Commands,
+    mut playback: ResMut<Playback>,
+    mut inputs: ResMut<PaddleInputs>,
+    mut ended: EventWriter<ReplayEnded>,
+) {
+    let playback = &mut *playback;
+    let Some((ticks, bytes)) = playback.replay.inputs.get(playback.run) else {
+        commands.remove_resource::<Playback>();
+        ended.send(ReplayEnded);
+        return;
+    };
+    inputs.0 = bytes
+        .iter()
+        .map(|&byte| PaddleInput::from_byte(byte))
+        .collect();
+    playback.tick_in_run += 1;
+    if playback.tick_in_run >= *ticks {
+        playback.run += 1;
+        playback.tick_in_run = 0;
+    }
+    if playback.run >= playback.replay.inputs.len() {
+        info!("The replay has ended");
+        commands.remove_resource::<Playback>();
+        ended.send(ReplayEnded);
+    }
+}
+
+fn record_inputs(world: &mut World) {
+    let bytes: Vec<i8> = world
+        .resource::<PaddleInputs>()
+        .0
+        .iter()
+        .map(|input| input.to_byte())
+        .collect();
+    if world.resource::<Recording>().replay.is_none() {
+        let replay = ReplayFile {
+            version: REPLAY_VERSION,
+            coop: world.resource::<CoopSettings>().clone(),
+            level: world.resource::<CurrentLevel>().0.clone(),
+            mode: *world.resource::<GameMode>(),
+            catch: *world.resource::<CatchOption>(),
+            config: world.resource::<GameConfig>().clone(),
+            start: GameSnapshot::capture(world),
+            inputs: Vec::new(),
+        };
+        world.resource_mut::<Recording>().replay = Some(replay);
+    }
+
+    let mut recording = world.resource_mut::<Recording>();
+    let inputs = &mut recording.replay.as_mut().unwrap().inputs;
+    match inputs.last_mut() {
+        Some((ticks, last)) if *last == bytes => *ticks += 1,
+        _ => inputs.push((1, bytes)),
+    }
+}
+
+// Writes the recording out. Only one game is recorded, so it stops here.
+fn finish_recording(mut commands: Commands, recording: Res<Recording>) {
+    commands.remove_resource::<Recording>();
+    let Some(replay) = &recording.replay else {
+        return;
+    };
+    match replay.save(&recording.path) {
+        Ok(()) => info!("Recorded the game to {}", recording.path.display()),
+        Err(error) => error!(
+            "Could not write the replay to {}: {error}",
+            recording.path.display()
+        ),
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::Path;
+
+use bevy::{
+    input::common_conditions::{input_just_pressed, input_just_released},
+    prelude::*,
+    window::WindowCloseRequested,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::catch::CatchOption;
+use crate::daily::DailyRun;
+use crate::level::{CurrentLevel, LevelData};
+use crate::modes::GameMode;
+use crate::net::NetSession;
+use crate::snapshot::GameSnapshot;
+use crate::storage::save_ron;
+use crate::{CoopSettings, GameState, StartGameOverlay};
+
+const SAVE_PATH: &str = "savegame.ron";
+// Bump this whenever `SaveFile` changes, and teach `migrate` to upgrade the previous version
+const SAVE_VERSION: u32 = 7;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Paused), write_save)
+            .add_systems(
+                Update,
+                write_save
+                    .run_if(in_state(GameState::InGame).or_else(in_state(GameState::Paused)))
+                    .run_if(
+                        input_just_pressed(KeyCode::Escape)
+                            .or_else(on_event::<WindowCloseRequested>()),
+                    )
+                    .before(bevy::window::close_on_esc),
+            )
+            .add_systems(OnEnter(GameState::GameOver), delete_save)
+            .add_systems(
+                Update,
+                continue_game
+                    .run_if(in_state(GameState::NewGame))
+                    .run_if(input_just_released(KeyCode::C)),
+            );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveFile {
+    version: u32,
+    coop: CoopSettings,
+    game: GameSnapshot,
+    // The layout that comes back each time the wall is cleared
+    #[serde(default)]
+    level: LevelData,
+    #[serde(default)]
+    mode: GameMode,
+    #[serde(default)]
+    catch: CatchOption,
+}
+
+// Just enough of any save to tell which layout the rest of it has
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+impl SaveFile {
+    pub fn capture(world: &mut World) -> Self {
+        SaveFile {
+            version: SAVE_VERSION,
+            coop: world.resource::<CoopSettings>().clone(),
+            game: GameSnapshot::capture(world),
+            level: world.resource::<CurrentLevel>().0.clone(),
+            mode: *world.resource::<GameMode>(),
+            catch: *world.resource::<CatchOption>(),
+        }
+    }
+
+    // Puts the saved game back and plays on from where it was left
+    pub fn restore(self, world: &mut World) {
+        let mut start_query = world.query_filtered::<Entity, With<StartGameOverlay>>();
+        let start_ents: Vec<Entity> = start_query.iter(world).collect();
+        for start_ent in start_ents {
+            world.despawn(start_ent);
+        }
+
+        world.insert_resource(self.coop);
+        world.insert_resource(CurrentLevel(self.level));
+        world.insert_resource(self.mode);
+        world.insert_resource(self.catch);
+        self.game.restore(world);
+        world
+            .resource_mut::<NextState<GameState>>()
+            .set(GameState::InGame);
+    }
+}
+
+pub fn save_exists() -> bool {
+    Path::new(SAVE_PATH).exists()
+}
+
+fn write_save(world: &mut World) {
+    // Online games can't be resumed without the other player
+    if world.contains_resource::<NetSession>() {
+        return;
+    }
+    // A daily run is a single attempt, so it can't be saved and tried again
+    if world.contains_resource::<DailyRun>() {
+        return;
+    }
+    let save = SaveFile::capture(world);
+    if let Err(error) = save_ron(SAVE_PATH, &save) {
+        error!("Could not save the game: {error}");
+    }
+}
+
+// A lost game can't be continued
+fn delete_save() {
+    if save_exists() {
+        if let Err(error) = fs::remove_file(SAVE_PATH) {
+            error!("Could not delete the save: {error}");
+        }
+    }
+}
+
+fn read_save() -> Result<SaveFile, String> {
+    let contents = fs::read_to_string(SAVE_PATH).map_err(|error| error.to_string())?;
+    let header: SaveHeader = ron::from_str(&contents).map_err(|error| error.to_string())?;
+    migrate(header.version, &contents)
+}
+
+// Reads a save of any known version into the current layout
+pub fn migrate(version: u32, contents: &str) -> Result<SaveFile, String> {
+    match version {
+        // Version 1 had no brick kinds, hit points or level layout, which default to the
+        // classic wall. Version 2 had no game modes, so its games were classic ones. Version 3
+        // had no brick behaviours, so all its bricks were static. Version 4 had no bosses.
+        // Version 5 had no power-ups or lasers. Version 6 had no catch option or extra balls.
+        1..=6 | SAVE_VERSION => ron::from_str(contents).map_err(|error| error.to_string()),
+        newer if newer > SAVE_VERSION => Err(format!(
+            "the save is from a newer version of the game ({newer})"
+        )),
+        older => Err(format!("save version {older} is no longer supported")),
+    }
+}
+
+fn continue_game(world: &mut World) {
+    if !save_exists() {
+        return;
+    }
+    let save = match read_save() {
+        Ok(save) => save,
+        Err(error) => {
+            error!("Could not load the save: {error}");
+            return;
+        }
+    };
+    save.restore(world);
+}
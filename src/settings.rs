@@ -0,0 +1,300 @@
+use bevy::{input::common_conditions::input_just_released, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::palette::Palette;
+use crate::storage::{load_ron, save_ron};
+use crate::theme::{available_themes, FLAT_THEME};
+use crate::{spawn_start_overlay, CoopSettings, GameState, StartGameOverlay};
+
+const SETTINGS_PATH: &str = "settings.ron";
+
+// Settings screen
+const SETTINGS_FONT_SIZE: f32 = 25.0;
+const SETTINGS_TEXT_COLOR: Color = Color::rgb(0.5, 0.5, 1.0);
+const SETTINGS_VERTICAL_PADDING: Val = Val::Px(200.0);
+const SETTINGS_LEFT_PADDING: Val = Val::Px(400.0);
+const SETTINGS_OVERLAY_SIZE: Vec3 = Vec3::new(1500.0, 1500.0, 0.0);
+const SETTINGS_OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.95);
+// Volumes go up in these steps, then wrap around to silent
+const VOLUME_STEP: f32 = 0.25;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let settings = Settings::load().unwrap_or_else(|error| {
+            error!("Could not load the settings: {error}");
+            Settings::default()
+        });
+        app.insert_resource(settings)
+            .add_systems(
+                Update,
+                enter_settings
+                    .run_if(in_state(GameState::NewGame))
+                    .run_if(input_just_released(KeyCode::S)),
+            )
+            .add_systems(
+                Update,
+                (change_settings, update_settings_text, leave_settings)
+                    .chain()
+                    .run_if(in_state(GameState::Settings)),
+            );
+    }
+}
+
+// Player preferences, kept between runs of the game
+#[derive(Resource, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Fewer particles and no ball trail
+    pub reduced_effects: bool,
+    pub screen_shake: EffectLevel,
+    pub hit_stop: EffectLevel,
+    pub brick_flash: EffectLevel,
+    pub paddle_squash: EffectLevel,
+    // The folder under `assets/themes` to draw with, or the flat look
+    pub theme: String,
+    pub palette: Palette,
+    // Marks on bricks telling their kind and hits left, for when colors are hard to tell apart
+    pub brick_glyphs: bool,
+    // From 0 for silent to 1 for full
+    pub master_volume: f32,
+    pub sfx_volume: f32,
+    pub music_volume: f32,
+    // Each game's statistics added to a file, for players who want to look into how they play
+    pub session_log: bool,
+    // Silenced from the command line for this run only, so it is never saved
+    #[serde(skip)]
+    pub muted: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            reduced_effects: false,
+            screen_shake: EffectLevel::Full,
+            hit_stop: EffectLevel::Full,
+            brick_flash: EffectLevel::Full,
+            paddle_squash: EffectLevel::Full,
+            theme: FLAT_THEME.to_string(),
+            palette: Palette::Level,
+            brick_glyphs: false,
+            master_volume: 1.0,
+            sfx_volume: 1.0,
+            music_volume: 0.5,
+            session_log: false,
+            muted: false,
+        }
+    }
+}
+
+// How strongly an effect plays, down to not at all for players it bothers
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EffectLevel {
+    Off,
+    Low,
+    Full,
+}
+
+impl EffectLevel {
+    pub fn factor(self) -> f32 {
+        match self {
+            EffectLevel::Off => 0.0,
+            EffectLevel::Low => 0.5,
+            EffectLevel::Full => 1.0,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            EffectLevel::Off => "off",
+            EffectLevel::Low => "low",
+            EffectLevel::Full => "full",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            EffectLevel::Off => EffectLevel::Low,
+            EffectLevel::Low => EffectLevel::Full,
+            EffectLevel::Full => EffectLevel::Off,
+        }
+    }
+}
+
+impl Settings {
+    fn load() -> Result<Self, String> {
+        load_ron(SETTINGS_PATH)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        save_ron(SETTINGS_PATH, self)
+    }
+}
+
+#[derive(Component)]
+struct SettingsScreen;
+
+#[derive(Component)]
+struct SettingsText;
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn percent(volume: f32) -> String {
+    format!("{}%", (volume * 100.0).round())
+}
+
+fn next_volume(volume: f32) -> f32 {
+    if volume >= 1.0 {
+        0.0
+    } else {
+        (volume + VOLUME_STEP).min(1.0)
+    }
+}
+
+fn settings_text(settings: &Settings) -> String {
+    format!(
+        "Settings\n\n1: reduced effects {}\n2: screen shake {}\n3: hit-stop {}\n\
+         4: brick flash {}\n5: paddle squash {}\n6: theme {}\n7: palette {}\n8: brick glyphs {}\n\
+         9: master volume {}\n0: effects volume {}\n-: music volume {}\n=: session log {}\n\n\
+         Tab: back",
+        on_off(settings.reduced_effects),
+        settings.screen_shake.name(),
+        settings.hit_stop.name(),
+        settings.brick_flash.name(),
+        settings.paddle_squash.name(),
+        settings.theme,
+        settings.palette.name(),
+        on_off(settings.brick_glyphs),
+        percent(settings.master_volume),
+        percent(settings.sfx_volume),
+        percent(settings.music_volume),
+        on_off(settings.session_log),
+    )
+}
+
+// S on the start screen opens the settings
+fn enter_settings(
+    mut commands: Commands,
+    start_query: Query<Entity, With<StartGameOverlay>>,
+    settings: Res<Settings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for start_ent in &start_query {
+        commands.entity(start_ent).despawn();
+    }
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, 1.0),
+                scale: SETTINGS_OVERLAY_SIZE,
+                ..default()
+            },
+            sprite: Sprite {
+                color: SETTINGS_OVERLAY_COLOR,
+                ..default()
+            },
+            ..default()
+        },
+        SettingsScreen,
+    ));
+    commands.spawn((
+        TextBundle::from_section(
+            settings_text(&settings),
+            TextStyle {
+                font_size: SETTINGS_FONT_SIZE,
+                color: SETTINGS_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: SETTINGS_VERTICAL_PADDING,
+            left: SETTINGS_LEFT_PADDING,
+            ..default()
+        }),
+        SettingsScreen,
+        SettingsText,
+    ));
+    next_state.set(GameState::Settings);
+}
+
+fn change_settings(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard_input.just_released(KeyCode::Key1) {
+        settings.reduced_effects = !settings.reduced_effects;
+    }
+    if keyboard_input.just_released(KeyCode::Key2) {
+        settings.screen_shake = settings.screen_shake.next();
+    }
+    if keyboard_input.just_released(KeyCode::Key3) {
+        settings.hit_stop = settings.hit_stop.next();
+    }
+    if keyboard_input.just_released(KeyCode::Key4) {
+        settings.brick_flash = settings.brick_flash.next();
+    }
+    if keyboard_input.just_released(KeyCode::Key5) {
+        settings.paddle_squash = settings.paddle_squash.next();
+    }
+    if keyboard_input.just_released(KeyCode::Key6) {
+        // Themes are looked for each time, so new ones show up without a restart
+        let themes = available_themes();
+        let current = themes.iter().position(|theme| *theme == settings.theme);
+        let next = current.map_or(0, |index| (index + 1) % themes.len());
+        settings.theme = themes[next].clone();
+    }
+    if keyboard_input.just_released(KeyCode::Key7) {
+        settings.palette = settings.palette.next();
+    }
+    if keyboard_input.just_released(KeyCode::Key8) {
+        settings.brick_glyphs = !settings.brick_glyphs;
+    }
+    if keyboard_input.just_released(KeyCode::Key9) {
+        settings.master_volume = next_volume(settings.master_volume);
+    }
+    if keyboard_input.just_released(KeyCode::Key0) {
+        settings.sfx_volume = next_volume(settings.sfx_volume);
+    }
+    if keyboard_input.just_released(KeyCode::Minus) {
+        settings.music_volume = next_volume(settings.music_volume);
+    }
+    if keyboard_input.just_released(KeyCode::Equals) {
+        settings.session_log = !settings.session_log;
+    }
+}
+
+fn update_settings_text(settings: Res<Settings>, mut query: Query<&mut Text, With<SettingsText>>) {
+    if !settings.is_changed() {
+        return;
+    }
+    for mut text in &mut query {
+        text.sections[0].value = settings_text(&settings);
+    }
+}
+
+// Tab saves the settings and goes back to the start screen
+fn leave_settings(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    coop: Res<CoopSettings>,
+    screen_query: Query<Entity, With<SettingsScreen>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !keyboard_input.just_released(KeyCode::Tab) {
+        return;
+    }
+    if let Err(error) = settings.save() {
+        error!("Could not save the settings: {error}");
+    }
+    for screen_ent in &screen_query {
+        commands.entity(screen_ent).despawn();
+    }
+    spawn_start_overlay(&mut commands, &coop);
+    next_state.set(GameState::NewGame);
+}
@@ -0,0 +1,477 @@
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::behaviour::{BrickPath, Descends, Dormant, Regrows};
+use crate::boss::{projectile_bundle, restore_boss, Boss, BossPart, BossProjectile};
+use crate::catch::{CatchPaddle, Caught};
+use crate::config::GameConfig;
+use crate::laser::{laser_bolt_bundle, LaserBolt, LaserCannons};
+use crate::modes::ModeProgress;
+use crate::multiball::{extra_ball_bundle, BallAssets, ExtraBall};
+use crate::palette::LevelColor;
+use crate::powerups::{power_up_bundle, PowerUp};
+use crate::{
+    brick_bundle, paddle_bundle, Ball, Brick, BrickKind, Broken, Collider, GameState, HitPoints,
+    LastTouchedBy, Level, Lives, Paddle, PaddleBounds, Player, Scoreboard, Velocity,
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BallState {
+    pub translation: Vec3,
+    pub velocity: Vec2,
+    pub last_touched_by: Option<usize>,
+    // Held on a paddle, waiting to be launched
+    #[serde(default)]
+    pub caught: Option<Caught>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PaddleState {
+    pub player: usize,
+    pub translation: Vec3,
+    pub bounds: (f32, f32),
+    // Boss projectiles shrink paddles. Saves from before bosses existed had full-size ones.
+    #[serde(default = "full_width")]
+    pub width: f32,
+    // Cannons picked up from a power-up, with the time they have left
+    #[serde(default)]
+    pub laser: Option<LaserCannons>,
+    #[serde(default)]
+    pub catch: Option<CatchPaddle>,
+}
+
+// Those saves were all made with the built-in paddle size
+fn full_width() -> f32 {
+    GameConfig::default().paddle_size.x
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BrickState {
+    pub translation: Vec3,
+    pub color: Color,
+    // Saves from before brick kinds existed only had single-hit bricks
+    #[serde(default)]
+    pub kind: BrickKind,
+    #[serde(default = "single_hit")]
+    pub hit_points: u32,
+    // Behaviour state. Saves from before brick behaviours existed only had static bricks.
+    #[serde(default)]
+    pub velocity: Vec2,
+    #[serde(default)]
+    pub path: Option<BrickPath>,
+    #[serde(default)]
+    pub descends: Option<Descends>,
+    #[serde(default)]
+    pub regrows: Option<Regrows>,
+    #[serde(default)]
+    pub dormant: Option<Dormant>,
+}
+
+fn single_hit() -> u32 {
+    1
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BossState {
+    pub boss: Boss,
+    pub translation: Vec3,
+    pub velocity: Vec2,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProjectileState {
+    pub translation: Vec3,
+    pub velocity: Vec2,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PowerUpState {
+    pub power_up: PowerUp,
+    pub translation: Vec3,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LaserBoltState {
+    pub translation: Vec3,
+    pub player: usize,
+}
+
+// Everything the gameplay tick reads or writes, so a game can be put back exactly as it was
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameSnapshot {
+    pub ball: BallState,
+    // Snapshots from before multi-ball existed only had the one ball
+    #[serde(default)]
+    pub extra_balls: Vec<BallState>,
+    pub paddles: Vec<PaddleState>,
+    pub bricks: Vec<BrickState>,
+    pub score: usize,
+    pub player_scores: Vec<usize>,
+    pub lives_left: Vec<usize>,
+    pub level_index: usize,
+    // Snapshots from before game modes existed were all classic games
+    #[serde(default)]
+    pub mode_progress: ModeProgress,
+    #[serde(default)]
+    pub boss: Option<BossState>,
+    #[serde(default)]
+    pub projectiles: Vec<ProjectileState>,
+    // Snapshots from before power-ups existed had none falling and no shots in the air
+    #[serde(default)]
+    pub power_ups: Vec<PowerUpState>,
+    #[serde(default)]
+    pub laser_bolts: Vec<LaserBoltState>,
+}
+
+impl GameSnapshot {
+    pub fn capture(world: &mut World) -> Self {
+        let mut ball_query = world.query_filtered::<(
+            &Transform,
+            &Velocity,
+            &LastTouchedBy,
+            Option<&Caught>,
+            Option<&ExtraBall>,
+        ), With<Ball>>();
+        let mut ball = None;
+        let mut extra_balls = Vec::new();
+        for (transform, velocity, last_touched, caught, extra) in ball_query.iter(world) {
+            let state = BallState {
+                translation: transform.translation,
+                velocity: velocity.0,
+                last_touched_by: last_touched.0,
+                caught: caught.copied(),
+            };
+            match extra {
+                Some(_) => extra_balls.push(state),
+                None => ball = Some(state),
+            }
+        }
+        let ball = ball.expect("the first ball is never despawned");
+        extra_balls.sort_by(|a, b| by_position(a.translation, b.translation));
+
+        let mut paddle_query = world.query_filtered::<(
+            &Transform,
+            &Player,
+            &PaddleBounds,
+            Option<&LaserCannons>,
+            Option<&CatchPaddle>,
+        ), With<Paddle>>();
+        let mut paddles: Vec<PaddleState> = paddle_query
+            .iter(world)
+            .map(|(transform, player, bounds, laser, catch)| PaddleState {
+                player: player.0,
+                translation: transform.translation,
+                bounds: (bounds.left, bounds.right),
+                width: transform.scale.x,
+                laser: laser.copied(),
+                catch: catch.copied(),
+            })
+            .collect();
+        paddles.sort_by_key(|paddle| paddle.player);
+
+        // Boss parts count as bricks in play, but are rebuilt from the boss alone
+        let mut brick_query = world.query_filtered::<Entity, (With<Brick>, Without<BossPart>)>();
+        let brick_ents: Vec<Entity> = brick_query.iter(world).collect();
+        let mut bricks: Vec<BrickState> = brick_ents
+            .into_iter()
+            .map(|brick| {
+                let brick = world.entity(brick);
+                let hit_points = brick.get::<HitPoints>();
+                BrickState {
+                    translation: brick.get::<Transform>().unwrap().translation,
+                    // The palette only changes how bricks are drawn
+                    color: brick.get::<LevelColor>().map_or_else(
+                        || brick.get::<Sprite>().unwrap().color,
+                        |level_color| level_color.color,
+                    ),
+                    kind: match hit_points {
+                        Some(_) => BrickKind::Normal,
+                        None => BrickKind::Unbreakable,
+                    },
+                    hit_points: hit_points.map_or(0, |hit_points| hit_points.0),
+                    velocity: brick
+                        .get::<Velocity>()
+                        .map_or(Vec2::ZERO, |velocity| velocity.0),
+                    path: brick.get::<BrickPath>().copied(),
+                    descends: brick.get::<Descends>().copied(),
+                    regrows: brick.get::<Regrows>().copied(),
+                    dormant: brick.get::<Dormant>().copied(),
+                }
+            })
+            .collect();
+        bricks.sort_by(|a, b| by_position(a.translation, b.translation));
+
+        let mut boss_query = world.query::<(&Boss, &Transform, &Velocity)>();
+        let boss = boss_query
+            .get_single(world)
+            .ok()
+            .map(|(boss, transform, velocity)| BossState {
+                boss: *boss,
+                translation: transform.translation,
+                velocity: velocity.0,
+            });
+        let mut projectile_query =
+            world.query_filtered::<(&Transform, &Velocity), With<BossProjectile>>();
+        let mut projectiles: Vec<ProjectileState> = projectile_query
+            .iter(world)
+            .map(|(transform, velocity)| ProjectileState {
+                translation: transform.translation,
+                velocity: velocity.0,
+            })
+            .collect();
+        projectiles.sort_by(|a, b| by_position(a.translation, b.translation));
+        let mut power_up_query = world.query::<(&PowerUp, &Transform)>();
+        let mut power_ups: Vec<PowerUpState> = power_up_query
+            .iter(world)
+            .map(|(power_up, transform)| PowerUpState {
+                power_up: *power_up,
+                translation: transform.translation,
+            })
+            .collect();
+        power_ups.sort_by(|a, b| by_position(a.translation, b.translation));
+        let mut bolt_query = world.query::<(&LaserBolt, &Transform)>();
+        let mut laser_bolts: Vec<LaserBoltState> = bolt_query
+            .iter(world)
+            .map(|(bolt, transform)| LaserBoltState {
+                translation: transform.translation,
+                player: bolt.player,
+            })
+            .collect();
+        laser_bolts.sort_by(|a, b| by_position(a.translation, b.translation));
+
+        let scoreboard = world.resource::<Scoreboard>();
+        let lives = world.resource::<Lives>();
+        let level = world.resource::<Level>();
+        GameSnapshot {
+            ball,
+            extra_balls,
+            paddles,
+            bricks,
+            score: scoreboard.score,
+            player_scores: scoreboard.player_scores.clone(),
+            lives_left: lives.lives_left.clone(),
+            level_index: level.index,
+            mode_progress: world.resource::<ModeProgress>().clone(),
+            boss,
+            projectiles,
+            power_ups,
+            laser_bolts,
+        }
+    }
+
+    pub fn restore(&self, world: &mut World) {
+        let mut ball_query = world.query_filtered::<(
+            Entity,
+            &mut Transform,
+            &mut Velocity,
+            &mut LastTouchedBy,
+        ), (With<Ball>, Without<ExtraBall>)>();
+        let (ball, mut ball_transform, mut ball_velocity, mut last_touched) =
+            ball_query.single_mut(world);
+        ball_transform.translation = self.ball.translation;
+        ball_velocity.0 = self.ball.velocity;
+        last_touched.0 = self.ball.last_touched_by;
+        let mut ball = world.entity_mut(ball);
+        ball.remove::<Caught>();
+        if let Some(caught) = self.ball.caught {
+            ball.insert(caught);
+        }
+
+        // Paddles and bricks may have been despawned since, so they are rebuilt from scratch. A
+        // brick still flashing from a break is the snapshot's to bring back, or to break again.
+        let mut stale_query = world.query_filtered::<Entity, Or<(
+            With<Paddle>,
+            With<Brick>,
+            With<Broken>,
+            With<BossProjectile>,
+            With<PowerUp>,
+            With<LaserBolt>,
+            With<ExtraBall>,
+        )>>();
+        let stale: Vec<Entity> = stale_query.iter(world).collect();
+        for entity in stale {
+            world.despawn(entity);
+        }
+
+        let players = self.player_scores.len();
+        let config = world.resource::<GameConfig>().clone();
+        for paddle in &self.paddles {
+            let mut paddle_ent = world.spawn(paddle_bundle(paddle.player, players, &config));
+            paddle_ent.insert(PaddleBounds {
+                left: paddle.bounds.0,
+                right: paddle.bounds.1,
+            });
+            let mut transform = paddle_ent.get_mut::<Transform>().unwrap();
+            transform.translation = paddle.translation;
+            transform.scale.x = paddle.width;
+            if let Some(laser) = paddle.laser {
+                paddle_ent.insert(laser);
+            }
+            if let Some(catch) = paddle.catch {
+                paddle_ent.insert(catch);
+            }
+        }
+        let layout = world.resource::<GameConfig>().layout.clone();
+        for brick in &self.bricks {
+            let bundle = brick_bundle(brick.translation, brick.color, &layout);
+            let mut brick_ent = world.spawn(bundle);
+            if brick.kind == BrickKind::Normal {
+                brick_ent.insert(HitPoints(brick.hit_points));
+            }
+            if let Some(path) = brick.path {
+                brick_ent.insert((path, Velocity(brick.velocity)));
+            }
+            if let Some(descends) = brick.descends {
+                brick_ent.insert(descends);
+            }
+            if let Some(regrows) = brick.regrows {
+                brick_ent.insert(regrows);
+            }
+            if let Some(dormant) = brick.dormant {
+                brick_ent
+                    .remove::<Collider>()
+                    .insert((dormant, Visibility::Hidden));
+            }
+        }
+
+        if let Some(boss) = &self.boss {
+            restore_boss(world, boss.boss, boss.translation, boss.velocity);
+        }
+        for projectile in &self.projectiles {
+            world.spawn(projectile_bundle(
+                projectile.translation,
+                projectile.velocity,
+            ));
+        }
+        let ball_assets = world.resource::<BallAssets>().clone();
+        for extra in &self.extra_balls {
+            let mut ball = world.spawn(extra_ball_bundle(
+                &ball_assets,
+                extra.translation,
+                extra.velocity,
+                extra.last_touched_by,
+            ));
+            if let Some(caught) = extra.caught {
+                ball.insert(caught);
+            }
+        }
+        for power_up in &self.power_ups {
+            world.spawn(power_up_bundle(power_up.power_up, power_up.translation));
+        }
+        for bolt in &self.laser_bolts {
+            world.spawn(laser_bolt_bundle(bolt.translation, bolt.player));
+        }
+
+        let mut scoreboard = world.resource_mut::<Scoreboard>();
+        scoreboard.score = self.score;
+        scoreboard.player_scores = self.player_scores.clone();
+        world.resource_mut::<Lives>().lives_left = self.lives_left.clone();
+        world.resource_mut::<Level>().index = self.level_index;
+        world.insert_resource(self.mode_progress.clone());
+
+        // A game over reached on a timeline that is being thrown away no longer applies
+        world.resource_mut::<NextState<GameState>>().0 = None;
+    }
+
+    // Hash of the exact bits of the state, for peers to check they still agree
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for ball in std::iter::once(&self.ball).chain(&self.extra_balls) {
+            hash_vec3(&mut hasher, ball.translation);
+            ball.velocity.x.to_bits().hash(&mut hasher);
+            ball.velocity.y.to_bits().hash(&mut hasher);
+            ball.last_touched_by.hash(&mut hasher);
+            if let Some(caught) = ball.caught {
+                caught.player.hash(&mut hasher);
+                caught.offset.to_bits().hash(&mut hasher);
+                caught.speed.to_bits().hash(&mut hasher);
+                caught.time_left.to_bits().hash(&mut hasher);
+            }
+        }
+        for paddle in &self.paddles {
+            paddle.player.hash(&mut hasher);
+            hash_vec3(&mut hasher, paddle.translation);
+            paddle.bounds.0.to_bits().hash(&mut hasher);
+            paddle.bounds.1.to_bits().hash(&mut hasher);
+            paddle.width.to_bits().hash(&mut hasher);
+            if let Some(laser) = paddle.laser {
+                laser.time_left.to_bits().hash(&mut hasher);
+                laser.cooldown.to_bits().hash(&mut hasher);
+            }
+            if let Some(catch) = paddle.catch {
+                catch.time_left.to_bits().hash(&mut hasher);
+            }
+        }
+        for brick in &self.bricks {
+            hash_vec3(&mut hasher, brick.translation);
+            for channel in brick.color.as_rgba_f32() {
+                channel.to_bits().hash(&mut hasher);
+            }
+            brick.kind.hash(&mut hasher);
+            brick.hit_points.hash(&mut hasher);
+            brick.velocity.x.to_bits().hash(&mut hasher);
+            brick.velocity.y.to_bits().hash(&mut hasher);
+            if let Some(path) = brick.path {
+                path.left.to_bits().hash(&mut hasher);
+                path.right.to_bits().hash(&mut hasher);
+            }
+            if let Some(descends) = brick.descends {
+                descends.every.hash(&mut hasher);
+                descends.hits.hash(&mut hasher);
+            }
+            if let Some(regrows) = brick.regrows {
+                regrows.delay.to_bits().hash(&mut hasher);
+                regrows.hit_points.hash(&mut hasher);
+            }
+            if let Some(dormant) = brick.dormant {
+                dormant.time_left.to_bits().hash(&mut hasher);
+            }
+        }
+        self.score.hash(&mut hasher);
+        self.player_scores.hash(&mut hasher);
+        self.lives_left.hash(&mut hasher);
+        self.level_index.hash(&mut hasher);
+        self.mode_progress.elapsed.to_bits().hash(&mut hasher);
+        self.mode_progress.descended.to_bits().hash(&mut hasher);
+        self.mode_progress.rows_added.hash(&mut hasher);
+        if let Some(boss) = &self.boss {
+            boss.boss.encounter.hash(&mut hasher);
+            boss.boss.health.hash(&mut hasher);
+            boss.boss.phase.hash(&mut hasher);
+            boss.boss.reload.to_bits().hash(&mut hasher);
+            hash_vec3(&mut hasher, boss.translation);
+            boss.velocity.x.to_bits().hash(&mut hasher);
+            boss.velocity.y.to_bits().hash(&mut hasher);
+        }
+        for projectile in &self.projectiles {
+            hash_vec3(&mut hasher, projectile.translation);
+            projectile.velocity.x.to_bits().hash(&mut hasher);
+            projectile.velocity.y.to_bits().hash(&mut hasher);
+        }
+        for power_up in &self.power_ups {
+            power_up.power_up.hash(&mut hasher);
+            hash_vec3(&mut hasher, power_up.translation);
+        }
+        for bolt in &self.laser_bolts {
+            hash_vec3(&mut hasher, bolt.translation);
+            bolt.player.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+// Orders things by height, then from left to right. Query order depends on spawn history, so
+// everything is sorted to keep snapshots comparable.
+fn by_position(a: Vec3, b: Vec3) -> Ordering {
+    a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x))
+}
+
+fn hash_vec3(hasher: &mut DefaultHasher, value: Vec3) {
+    for component in value.to_array() {
+        component.to_bits().hash(hasher);
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::generator::Rng;
+
+// The bank the game plays from. Edits to it, or to any sound it names, are picked up while the
+// game runs.
+const SOUND_BANK_PATH: &str = "sounds/default.soundbank.ron";
+
+pub struct SoundBankPlugin;
+
+impl Plugin for SoundBankPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<SoundBank>()
+            .init_asset_loader::<SoundBankLoader>()
+            .add_systems(Startup, load_sound_bank)
+            .add_systems(Update, report_sound_bank_changes);
+    }
+}
+
+// Things in the game that make a sound
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum SoundEvent {
+    Wall,
+    Paddle,
+    Brick,
+    Boss,
+    Floor,
+    Explosion,
+    LaserHit,
+}
+
+// The sounds each event picks between, loaded from a `.soundbank.ron` file
+#[derive(TypeUuid, TypePath)]
+#[uuid = "0f3d8a52-6b1e-4c97-a2d4-5e9b7c1f3a86"]
+pub struct SoundBank {
+    events: HashMap<SoundEvent, Vec<SoundVariant>>,
+}
+
+struct SoundVariant {
+    sound: Handle<AudioSource>,
+    weight: f32,
+    volume: (f32, f32),
+    pitch: (f32, f32),
+}
+
+// A sound picked for one event, with its volume and pitch already rolled
+pub struct ChosenSound {
+    pub sound: Handle<AudioSource>,
+    pub volume: f32,
+    pub speed: f32,
+}
+
+impl SoundBank {
+    // Picks one of the event's sounds by weight, or none if the bank has nothing for it
+    pub fn choose(&self, event: SoundEvent, rng: &mut Rng) -> Option<ChosenSound> {
+        let variants = self.events.get(&event)?;
+        let total: f32 = variants.iter().map(|variant| variant.weight).sum();
+        let mut roll = rng.next_f32() * total;
+        let variant = variants
+            .iter()
+            .find(|variant| {
+                roll -= variant.weight;
+                roll < 0.0
+            })
+            .or(variants.last())?;
+        let between = |(low, high): (f32, f32), t: f32| low + (high - low) * t;
+        Some(ChosenSound {
+            sound: variant.sound.clone(),
+            volume: between(variant.volume, rng.next_f32()),
+            speed: between(variant.pitch, rng.next_f32()),
+        })
+    }
+}
+
+// The bank as it is written in the file
+#[derive(Deserialize)]
+struct SoundBankManifest {
+    events: HashMap<SoundEvent, Vec<VariantManifest>>,
+}
+
+#[derive(Deserialize)]
+struct VariantManifest {
+    // Relative to the bank's own folder
+    sound: String,
+    #[serde(default = "VariantManifest::default_weight")]
+    weight: f32,
+    // Lowest and highest, picked between at random each time the sound plays
+    #[serde(default = "VariantManifest::default_range")]
+    volume: (f32, f32),
+    // As a playback speed, so 2 is an octave up
+    #[serde(default = "VariantManifest::default_range")]
+    pitch: (f32, f32),
+}
+
+impl VariantManifest {
+    fn default_weight() -> f32 {
+        1.0
+    }
+
+    fn default_range() -> (f32, f32) {
+        (1.0, 1.0)
+    }
+
+    fn check(&self, event: SoundEvent) -> Result<(), String> {
+        let ranges = [("volume", self.volume), ("pitch", self.pitch)];
+        if self.weight <= 0.0 || !self.weight.is_finite() {
+            return Err(format!(
+                "{event:?}: {} has a weight of {}",
+                self.sound, self.weight
+            ));
+        }
+        for (name, (low, high)) in ranges {
+            if !(0.0..=high).contains(&low) || !high.is_finite() {
+                return Err(format!(
+                    "{event:?}: {} has a {name} range of {low} to {high}",
+                    self.sound
+                ));
+            }
+        }
+        // A playback speed of zero never finishes
+        if self.pitch.0 <= 0.0 {
+            return Err(format!("{event:?}: {} has a pitch of zero", self.sound));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct SoundBankLoader;
+
+impl AssetLoader for SoundBankLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let manifest: SoundBankManifest = ron::de::from_bytes(bytes)?;
+            let folder = load_context.path().parent().unwrap_or(load_context.path());
+            let folder = folder.to_path_buf();
+            let mut dependencies = Vec::new();
+            let mut events = HashMap::new();
+            for (event, variants) in manifest.events {
+                if variants.is_empty() {
+                    return Err(bevy::asset::Error::msg(format!("{event:?} has no sounds")));
+                }
+                let mut loaded = Vec::new();
+                for variant in variants {
+                    variant.check(event).map_err(bevy::asset::Error::msg)?;
+                    let path = AssetPath::new(folder.join(&variant.sound), None);
+                    loaded.push(SoundVariant {
+                        sound: load_context.get_handle(path.get_id()),
+                        weight: variant.weight,
+                        volume: variant.volume,
+                        pitch: variant.pitch,
+                    });
+                    dependencies.push(path);
+                }
+                events.insert(event, loaded);
+            }
+            load_context.set_default_asset(
+                LoadedAsset::new(SoundBank { events }).with_dependencies(dependencies),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["soundbank.ron"]
+    }
+}
+
+// The bank in use
+#[derive(Resource)]
+pub struct SoundBankHandle(pub Handle<SoundBank>);
+
+fn load_sound_bank(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(SoundBankHandle(asset_server.load(SOUND_BANK_PATH)));
+}
+
+// A bank that fails to load is logged by the asset server, and the last good one keeps playing
+fn report_sound_bank_changes(mut bank_events: EventReader<AssetEvent<SoundBank>>) {
+    for bank_event in bank_events.iter() {
+        if let AssetEvent::Modified { .. } = bank_event {
+            info!("Reloaded the sound bank");
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::{app::AppExit, input::common_conditions::input_just_released, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::cli::HeadlessRun;
+use crate::config::{BoardLayout, GameConfig};
+use crate::editor::Playtest;
+use crate::modes::GameMode;
+use crate::net::NetSession;
+use crate::replay::Playback;
+use crate::settings::Settings;
+use crate::storage::{load_ron, save_ron};
+use crate::{
+    run_gameplay_tick, spawn_start_overlay, CollisionEvent, CoopSettings, ExplosionEvent,
+    GameState, Level, Scoreboard, StartGameOverlay, Surface,
+};
+
+// Lifetime totals, kept whether or not sessions are logged
+const STATS_PATH: &str = "stats.ron";
+// One line of JSON per game, for players who turn the session log on
+const SESSION_LOG_PATH: &str = "stats.jsonl";
+// Lives lost are told apart by the left, middle and right of the floor
+const FLOOR_SECTIONS: usize = 3;
+const FLOOR_SECTION_NAMES: [&str; FLOOR_SECTIONS] = ["left", "middle", "right"];
+
+// Statistics screen
+const STATS_FONT_SIZE: f32 = 25.0;
+const STATS_TEXT_COLOR: Color = Color::rgb(0.5, 1.0, 0.5);
+const STATS_VERTICAL_PADDING: Val = Val::Px(150.0);
+const STATS_LEFT_PADDING: Val = Val::Px(350.0);
+const STATS_OVERLAY_SIZE: Vec3 = Vec3::new(1500.0, 1500.0, 0.0);
+const STATS_OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.95);
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        let lifetime = LifetimeStats::load().unwrap_or_else(|error| {
+            error!("Could not load the statistics: {error}");
+            LifetimeStats::default()
+        });
+        app.insert_resource(lifetime)
+            .add_systems(
+                Update,
+                // The session is in place before the first tick's events are counted
+                (
+                    start_session,
+                    apply_deferred,
+                    (count_session_events, time_levels),
+                )
+                    .chain()
+                    .after(run_gameplay_tick)
+                    .run_if(in_state(GameState::InGame))
+                    .run_if(play_counts),
+            )
+            .add_systems(
+                OnEnter(GameState::Paused),
+                count_pause.run_if(resource_exists::<Session>()),
+            )
+            .add_systems(
+                OnEnter(GameState::GameOver),
+                finish_session.run_if(resource_exists::<Session>()),
+            )
+            // Quitting mid-game still counts what was played
+            .add_systems(
+                Last,
+                finish_session
+                    .run_if(resource_exists::<Session>())
+                    .run_if(on_event::<AppExit>()),
+            )
+            .add_systems(
+                Update,
+                enter_stats
+                    .run_if(in_state(GameState::NewGame))
+                    .run_if(input_just_released(KeyCode::T)),
+            )
+            .add_systems(
+                Update,
+                leave_stats
+                    .run_if(in_state(GameState::Stats))
+                    .run_if(input_just_released(KeyCode::Tab)),
+            );
+    }
+}
+
+// Whether the game being played is anybody's own play, to be counted. Online games resimulate
+// ticks and would count them twice, and scripted, replayed and playtested games aren't anybody's
+// play.
+pub fn play_counts(
+    net_session: Option<Res<NetSession>>,
+    headless: Option<Res<HeadlessRun>>,
+    playback: Option<Res<Playback>>,
+    playtest: Option<Res<Playtest>>,
+) -> bool {
+    net_session.is_none() && headless.is_none() && playback.is_none() && playtest.is_none()
+}
+
+// One game as it was played, from its first tick to game over
+#[derive(Resource, Serialize)]
+pub struct Session {
+    // Seconds since 1970 when the game started
+    started: u64,
+    mode: GameMode,
+    players: usize,
+    score: usize,
+    // Bricks broken on each row of the grid, from the top
+    bricks_by_row: Vec<u32>,
+    paddle_hits: u32,
+    // How far along the floor each life was lost
+    pub lives_lost_at: Vec<f32>,
+    // Paddle hits in each rally, which ends when a life is lost or the game does
+    pub rallies: Vec<u32>,
+    average_rally: f32,
+    // Seconds spent on each wall, in the order they came up
+    level_seconds: Vec<f32>,
+    pauses: u32,
+    #[serde(skip)]
+    rally: u32,
+}
+
+// Every game's statistics added up, shown on the statistics screen
+#[derive(Resource, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct LifetimeStats {
+    games: u32,
+    bricks_by_row: Vec<u32>,
+    paddle_hits: u32,
+    rallies: u32,
+    lives_lost: [u32; FLOOR_SECTIONS],
+    levels: u32,
+    level_seconds: f32,
+    pauses: u32,
+}
+
+impl LifetimeStats {
+    fn load() -> Result<Self, String> {
+        load_ron(STATS_PATH)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        save_ron(STATS_PATH, self)
+    }
+
+    fn add(&mut self, session: &Session, layout: &BoardLayout) {
+        self.games += 1;
+        add_counts(&mut self.bricks_by_row, &session.bricks_by_row);
+        self.paddle_hits += session.paddle_hits;
+        self.rallies += session.rallies.len() as u32;
+        for x in &session.lives_lost_at {
+            self.lives_lost[floor_section(*x, layout)] += 1;
+        }
+        self.levels += session.level_seconds.len() as u32;
+        self.level_seconds += session.level_seconds.iter().sum::<f32>();
+        self.pauses += session.pauses;
+    }
+}
+
+// Which part of the floor a point along it is on
+fn floor_section(x: f32, layout: &BoardLayout) -> usize {
+    let share = (x - layout.left_wall) / (layout.right_wall - layout.left_wall);
+    ((share * FLOOR_SECTIONS as f32) as usize).min(FLOOR_SECTIONS - 1)
+}
+
+// Adds one list of per-row counts into another, making room for rows it hasn't seen
+fn add_counts(totals: &mut Vec<u32>, counts: &[u32]) {
+    if totals.len() < counts.len() {
+        totals.resize(counts.len(), 0);
+    }
+    for (total, count) in totals.iter_mut().zip(counts) {
+        *total += count;
+    }
+}
+
+fn average(total: u32, count: u32) -> f32 {
+    if count == 0 {
+        0.0
+    } else {
+        total as f32 / count as f32
+    }
+}
+
+fn start_session(
+    mut commands: Commands,
+    session: Option<Res<Session>>,
+    mode: Res<GameMode>,
+    coop: Res<CoopSettings>,
+) {
+    if session.is_some() {
+        return;
+    }
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    commands.insert_resource(Session {
+        started,
+        mode: *mode,
+        players: coop.player_count(),
+        score: 0,
+        bricks_by_row: Vec::new(),
+        paddle_hits: 0,
+        lives_lost_at: Vec::new(),
+        rallies: Vec::new(),
+        average_rally: 0.0,
+        level_seconds: Vec::new(),
+        pauses: 0,
+        rally: 0,
+    });
+}
+
+fn count_session_events(
+    mut collisions: EventReader<CollisionEvent>,
+    mut explosions: EventReader<ExplosionEvent>,
+    mut session: ResMut<Session>,
+) {
+    for collision in collisions.iter() {
+        match collision.surface {
+            Surface::Paddle => {
+                session.paddle_hits += 1;
+                session.rally += 1;
+            }
+            Surface::Floor if collision.life_lost => {
+                session.lives_lost_at.push(collision.position.x);
+                let rally = std::mem::take(&mut session.rally);
+                session.rallies.push(rally);
+            }
+            _ => {}
+        }
+    }
+    // The boss isn't on any row
+    for row in explosions.iter().filter_map(|explosion| explosion.row) {
+        let row = row as usize;
+        if session.bricks_by_row.len() <= row {
+            session.bricks_by_row.resize(row + 1, 0);
+        }
+        session.bricks_by_row[row] += 1;
+    }
+}
+
+fn time_levels(time: Res<Time>, level: Res<Level>, mut session: ResMut<Session>) {
+    if session.level_seconds.len() <= level.index {
+        session.level_seconds.resize(level.index + 1, 0.0);
+    }
+    session.level_seconds[level.index] += time.delta_seconds();
+}
+
+fn count_pause(mut session: ResMut<Session>) {
+    session.pauses += 1;
+}
+
+// Adds the game to the lifetime totals, and to the session log if it is on
+fn finish_session(
+    mut commands: Commands,
+    mut session: ResMut<Session>,
+    mut lifetime: ResMut<LifetimeStats>,
+    scoreboard: Res<Scoreboard>,
+    settings: Res<Settings>,
+    config: Res<GameConfig>,
+) {
+    commands.remove_resource::<Session>();
+    // A game won or run out of time ends mid-rally
+    if session.rally > 0 {
+        let rally = session.rally;
+        session.rallies.push(rally);
+    }
+    session.score = scoreboard.score;
+    session.average_rally = average(session.paddle_hits, session.rallies.len() as u32);
+
+    lifetime.add(&session, &config.layout);
+    if let Err(error) = lifetime.save() {
+        error!("Could not write the statistics: {error}");
+    }
+    if settings.session_log {
+        if let Err(error) = log_session(&session) {
+            error!("Could not write to {SESSION_LOG_PATH}: {error}");
+        }
+    }
+}
+
+fn log_session(session: &Session) -> Result<(), String> {
+    let line = serde_json::to_string(session).map_err(|error| error.to_string())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(SESSION_LOG_PATH)
+        .map_err(|error| error.to_string())?;
+    writeln!(file, "{line}").map_err(|error| error.to_string())
+}
+
+#[derive(Component)]
+struct StatsScreen;
+
+fn stats_text(lifetime: &LifetimeStats, settings: &Settings) -> String {
+    let bricks: u32 = lifetime.bricks_by_row.iter().sum();
+    let rows: Vec<String> = lifetime
+        .bricks_by_row
+        .iter()
+        .map(|count| count.to_string())
+        .collect();
+    let lives_lost: u32 = lifetime.lives_lost.iter().sum();
+    let floor: Vec<String> = FLOOR_SECTION_NAMES
+        .iter()
+        .zip(lifetime.lives_lost)
+        .map(|(name, count)| format!("{name} {count}"))
+        .collect();
+    let session_log = if settings.session_log {
+        format!("on, written to {SESSION_LOG_PATH}")
+    } else {
+        "off, turned on in the settings".to_string()
+    };
+    format!(
+        "Statistics\n\nGames played: {}\nBricks broken: {bricks}\n  by row, from the top: {}\n\
+         Paddle hits: {}\nAverage rally: {:.1} paddle hits\nLives lost: {lives_lost} ({})\n\
+         Walls played: {}, {:.0}s each on average\nPauses: {}\n\nSession log: {session_log}\n\n\
+         Tab: back",
+        lifetime.games,
+        rows.join(", "),
+        lifetime.paddle_hits,
+        average(lifetime.paddle_hits, lifetime.rallies),
+        floor.join(", "),
+        lifetime.levels,
+        if lifetime.levels == 0 {
+            0.0
+        } else {
+            lifetime.level_seconds / lifetime.levels as f32
+        },
+        lifetime.pauses,
+    )
+}
+
+// T on the start screen shows the lifetime statistics
+fn enter_stats(
+    mut commands: Commands,
+    start_query: Query<Entity, With<StartGameOverlay>>,
+    lifetime: Res<LifetimeStats>,
+    settings: Res<Settings>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for start_ent in &start_query {
+        commands.entity(start_ent).despawn();
+    }
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform {
+                translation: Vec3::new(0.0, 0.0, 1.0),
+                scale: STATS_OVERLAY_SIZE,
+                ..default()
+            },
+            sprite: Sprite {
+                color: STATS_OVERLAY_COLOR,
+                ..default()
+            },
+            ..default()
+        },
+        StatsScreen,
+    ));
+    commands.spawn((
+        TextBundle::from_section(
+            stats_text(&lifetime, &settings),
+            TextStyle {
+                font_size: STATS_FONT_SIZE,
+                color: STATS_TEXT_COLOR,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: STATS_VERTICAL_PADDING,
+            left: STATS_LEFT_PADDING,
+            ..default()
+        }),
+        StatsScreen,
+    ));
+    next_state.set(GameState::Stats);
+}
+
+// Tab goes back to the start screen
+fn leave_stats(
+    mut commands: Commands,
+    coop: Res<CoopSettings>,
+    screen_query: Query<Entity, With<StatsScreen>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for screen_ent in &screen_query {
+        commands.entity(screen_ent).despawn();
+    }
+    spawn_start_overlay(&mut commands, &coop);
+    next_state.set(GameState::NewGame);
+}
@@ -0,0 +1,27 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+// Reads a RON file that has to be there
+pub fn read_ron<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, String> {
+    let contents = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    ron::from_str(&contents).map_err(|error| error.to_string())
+}
+
+// Reads a RON file the game keeps between runs. One that hasn't been written yet reads as the
+// default.
+pub fn load_ron<T: DeserializeOwned + Default>(path: impl AsRef<Path>) -> Result<T, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => ron::from_str(&contents).map_err(|error| error.to_string()),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(T::default()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+pub fn save_ron<T: Serialize>(path: impl AsRef<Path>, value: &T) -> Result<(), String> {
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|error| error.to_string())?;
+    fs::write(path, contents).map_err(|error| error.to_string())
+}
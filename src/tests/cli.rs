@@ -0,0 +1,117 @@
+use crate::cli::{parse, Options};
+use crate::config::BoardLayout;
+use crate::level::LevelData;
+
+use super::TEST_DIR;
+
+fn parse_args(args: &[&str]) -> Result<Options, String> {
+    parse(args.iter().map(|arg| arg.to_string()))
+}
+
+// Checks the arguments are turned down, with an error naming each of `mentions`
+fn assert_rejected(args: &[&str], mentions: &[&str]) {
+    let error = match parse_args(args) {
+        Ok(_) => panic!("{args:?} was accepted"),
+        Err(error) => error,
+    };
+    for mention in mentions {
+        assert!(
+            error.contains(mention),
+            "{args:?} was turned down with \"{error}\", which doesn't mention {mention}"
+        );
+    }
+}
+
+#[test]
+fn options_that_work_together_are_accepted() {
+    let options = parse_args(&["--seed", "42", "--difficulty", "5", "--mode", "endless"]).unwrap();
+    assert_eq!(options.seed, Some(42));
+    assert_eq!(options.difficulty, Some(5));
+
+    parse_args(&["--replay", "game.ron", "--headless", "--mute"]).unwrap();
+    parse_args(&[
+        "--level",
+        "level.ron",
+        "--record",
+        "game.ron",
+        "--fps-cap",
+        "30",
+    ])
+    .unwrap();
+    parse_args(&["--window-size", "1280x720"]).unwrap();
+}
+
+#[test]
+fn level_and_seed_are_rejected_together() {
+    assert_rejected(
+        &["--level", "level.ron", "--seed", "1"],
+        &["--level", "--seed"],
+    );
+}
+
+#[test]
+fn difficulty_without_seed_is_rejected() {
+    assert_rejected(&["--difficulty", "3"], &["--difficulty", "--seed"]);
+    assert_rejected(
+        &["--level", "level.ron", "--difficulty", "3"],
+        &["--difficulty", "--seed"],
+    );
+}
+
+#[test]
+fn replay_is_rejected_with_anything_that_sets_up_the_game() {
+    let setups: [&[&str]; 4] = [
+        &["--level", "level.ron"],
+        &["--seed", "1"],
+        &["--mode", "classic"],
+        &["--record", "other.ron"],
+    ];
+    for setup in setups {
+        let mut args = vec!["--replay", "game.ron"];
+        args.extend_from_slice(setup);
+        assert_rejected(&args, &["--replay", setup[0]]);
+    }
+}
+
+#[test]
+fn headless_is_rejected_with_window_options() {
+    assert_rejected(
+        &["--headless", "--window-size", "800x600"],
+        &["--headless", "--window-size"],
+    );
+    assert_rejected(
+        &["--headless", "--fps-cap", "60"],
+        &["--headless", "--fps-cap"],
+    );
+}
+
+#[test]
+fn bad_values_are_rejected() {
+    assert_rejected(
+        &["--difficulty", "6", "--seed", "1"],
+        &["--difficulty", "6"],
+    );
+    assert_rejected(&["--seed", "many"], &["--seed", "many"]);
+    assert_rejected(&["--mode", "hard"], &["--mode", "hard"]);
+    assert_rejected(&["--window-size", "0x600"], &["--window-size", "0x600"]);
+    assert_rejected(&["--fps-cap", "0"], &["--fps-cap", "0"]);
+    assert_rejected(&["--seed"], &["--seed"]);
+    assert_rejected(&["--level", "--mute"], &["--level"]);
+    assert_rejected(&["--fast"], &["--fast"]);
+}
+
+#[test]
+fn level_too_wide_for_the_board_is_rejected() {
+    let layout = BoardLayout::default();
+    let mut level = LevelData::classic(&layout);
+    level.bricks[0].column = layout.grid_columns;
+    let path = std::env::temp_dir().join(TEST_DIR).join("too-wide.ron");
+    level.save(&path).unwrap();
+
+    let path = path.to_str().unwrap();
+    let error = match parse_args(&["--level", path]).unwrap().level() {
+        Ok(_) => panic!("a level wider than the grid was accepted"),
+        Err(error) => error,
+    };
+    assert!(error.contains(path) && error.contains("columns"), "{error}");
+}
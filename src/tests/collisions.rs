@@ -0,0 +1,314 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    ecs::{event::ManualEventReader, system::CommandQueue},
+    prelude::*,
+    sprite::collide_aabb::collide,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::GameConfig;
+use crate::debug::GodMode;
+use crate::generator::Rng;
+use crate::level::{cell_translation, CurrentLevel};
+use crate::{
+    apply_velocity, boss, brick_bundle, check_for_collisions, spawn_paddles, spawn_walls, Ball,
+    Brick, BrickBrokenEvent, CollisionEvent, CoopSettings, ExplosionEvent, GameState, HitPoints,
+    LastTouchedBy, Lives, Paddle, PaddleHitEvent, Scoreboard, Velocity,
+};
+
+// Fuzzing
+// How many random scenarios a run plays, and how long each one lasts. Every run plays the same
+// ones unless `COLLISION_FUZZ_SEED` picks others, and `COLLISION_FUZZ_CASES` plays more.
+const FUZZ_SEED: u64 = 0x0b0c_c0de_5eed_0001;
+const FUZZ_CASES: u32 = 64;
+const FUZZ_TICKS: u32 = 2000;
+// Rows of the grid bricks are put on, deeper than a real wall so the ball meets more of them
+const FUZZ_ROWS: u32 = 12;
+const MAX_HIT_POINTS: u32 = 3;
+// Shrunk failures are saved here when `COLLISION_FUZZ_SAVE` is set, and played before anything
+// new. Otherwise a failure only prints its scenario, so a test run leaves the tree as it was.
+const REGRESSIONS_DIR: &str = "src/tests/regressions";
+
+// A ball let loose among bricks, with a paddle standing still on the floor
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Scenario {
+    seed: u64,
+    ticks: u32,
+    ball: Vec2,
+    velocity: Vec2,
+    paddle_x: f32,
+    // Column, row and hit points of each brick
+    bricks: Vec<(u32, u32, u32)>,
+}
+
+impl Scenario {
+    fn random(seed: u64, config: &GameConfig) -> Self {
+        let layout = &config.layout;
+        let mut rng = Rng::new(seed);
+        let density = 0.1 + rng.next_f32() * 0.7;
+        let mut bricks = Vec::new();
+        for row in 0..FUZZ_ROWS {
+            for column in 0..layout.grid_columns {
+                if rng.next_f32() < density {
+                    bricks.push((column, row, 1 + rng.below(MAX_HIT_POINTS)));
+                }
+            }
+        }
+        let paddle_x = lerp(&mut rng, layout.left_wall, layout.right_wall);
+        let paddle_y = layout.paddle_y();
+
+        // Anywhere between the walls that isn't already inside a brick or the paddle
+        let reach = layout.wall_size + config.ball_size;
+        let ball = loop {
+            let ball = Vec2::new(
+                lerp(
+                    &mut rng,
+                    layout.left_wall + reach,
+                    layout.right_wall - reach,
+                ),
+                lerp(
+                    &mut rng,
+                    layout.bottom_wall + reach,
+                    layout.top_wall - reach,
+                ),
+            );
+            let overlaps = |center: Vec3, size: Vec2| {
+                collide(
+                    ball.extend(0.0),
+                    config.ball_scale().truncate(),
+                    center,
+                    size,
+                )
+                .is_some()
+            };
+            let in_brick = bricks.iter().any(|&(column, row, _)| {
+                let center = cell_translation(layout, column, row);
+                overlaps(center, layout.brick_size().truncate())
+            });
+            let in_paddle = overlaps(Vec3::new(paddle_x, paddle_y, 0.0), config.paddle_size);
+            if !in_brick && !in_paddle {
+                break ball;
+            }
+        };
+        let angle = rng.next_f32() * std::f32::consts::TAU;
+        Scenario {
+            seed,
+            ticks: FUZZ_TICKS,
+            ball,
+            velocity: Vec2::from_angle(angle) * config.ball_speed,
+            paddle_x,
+            bricks,
+        }
+    }
+
+    fn load(path: &Path) -> Self {
+        let contents = fs::read_to_string(path).unwrap();
+        ron::from_str(&contents).unwrap_or_else(|error| panic!("{}: {error}", path.display()))
+    }
+
+    fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap()
+    }
+
+    fn save(&self) -> PathBuf {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(REGRESSIONS_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("collisions-{:016x}.ron", self.seed));
+        fs::write(&path, self.to_ron()).unwrap();
+        path
+    }
+
+    // Plays the scenario through, stopping at the first tick that breaks a rule
+    fn play(&self) -> Result<(), Failure> {
+        let config = GameConfig::default();
+        let mut world = World::new();
+        world.insert_resource(config.clone());
+        world.insert_resource(Scoreboard::new(1));
+        world.insert_resource(Lives::new(1, config.starting_lives));
+        world.insert_resource(FixedTime::new_from_secs(1.0 / 60.0));
+        // The floor bounces the ball back like any other wall, so the wall stays as it is
+        world.insert_resource(GodMode);
+        world.init_resource::<CurrentLevel>();
+        world.init_resource::<CoopSettings>();
+        world.init_resource::<NextState<GameState>>();
+        world.init_resource::<Events<CollisionEvent>>();
+        world.init_resource::<Events<ExplosionEvent>>();
+        world.init_resource::<Events<PaddleHitEvent>>();
+        world.init_resource::<Events<boss::BossHitEvent>>();
+        world.init_resource::<Events<BrickBrokenEvent>>();
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &world);
+        spawn_walls(&mut commands, &config);
+        spawn_paddles(&mut commands, 1, &config);
+        queue.apply(&mut world);
+        let mut paddle_query = world.query_filtered::<&mut Transform, With<Paddle>>();
+        paddle_query.single_mut(&mut world).translation.x = self.paddle_x;
+        for &(column, row, hit_points) in &self.bricks {
+            let translation = cell_translation(&config.layout, column, row);
+            world.spawn((
+                brick_bundle(translation, Color::WHITE, &config.layout),
+                HitPoints(hit_points),
+            ));
+        }
+        let ball = world
+            .spawn((
+                Transform::from_translation(self.ball.extend(1.0)).with_scale(config.ball_scale()),
+                Ball,
+                LastTouchedBy::default(),
+                Velocity(self.velocity),
+            ))
+            .id();
+
+        let mut schedule = Schedule::new();
+        schedule.add_systems((apply_velocity, check_for_collisions).chain());
+        let mut broken_reader = ManualEventReader::<BrickBrokenEvent>::default();
+        let mut broken = HashSet::new();
+        let mut brick_query = world.query_filtered::<(), With<Brick>>();
+
+        for tick in 1..=self.ticks {
+            schedule.run(&mut world);
+            let fail = |rule: String| Err(Failure { tick, rule });
+
+            let position = world.get::<Transform>(ball).unwrap().translation;
+            let layout = &config.layout;
+            if !(layout.left_wall < position.x
+                && position.x < layout.right_wall
+                && layout.bottom_wall < position.y
+                && position.y < layout.top_wall)
+            {
+                return fail(format!("the ball got out of the walls, to {position}"));
+            }
+
+            let speed = world.get::<Velocity>(ball).unwrap().length();
+            if (speed - config.ball_speed).abs() > config.ball_speed * 1e-3 {
+                return fail(format!(
+                    "the ball's speed is {speed}, not {}",
+                    config.ball_speed
+                ));
+            }
+
+            let events = world.resource::<Events<BrickBrokenEvent>>();
+            for event in broken_reader.iter(events) {
+                let cell = (event.translation.x as i32, event.translation.y as i32);
+                if !broken.insert(cell) {
+                    return fail(format!(
+                        "the brick at {} was broken twice",
+                        event.translation
+                    ));
+                }
+            }
+
+            let destroyed = self.bricks.len() - brick_query.iter(&world).count();
+            let score = world.resource::<Scoreboard>().score;
+            if score != destroyed || broken.len() != destroyed {
+                return fail(format!(
+                    "the score is {score} and {} bricks were reported broken, but {destroyed} are gone",
+                    broken.len()
+                ));
+            }
+
+            world.resource_mut::<Events<CollisionEvent>>().update();
+            world.resource_mut::<Events<BrickBrokenEvent>>().update();
+        }
+        Ok(())
+    }
+
+    // The smallest scenario found that still fails: played only up to where it fails, with as
+    // few bricks and hit points as will do
+    fn shrink(mut self, mut failure: Failure) -> (Self, Failure) {
+        self.ticks = failure.tick;
+        let mut shrunk = true;
+        while shrunk {
+            shrunk = false;
+            let mut candidates = Vec::new();
+            for index in 0..self.bricks.len() {
+                let mut fewer = self.clone();
+                fewer.bricks.remove(index);
+                candidates.push(fewer);
+                if self.bricks[index].2 > 1 {
+                    let mut weaker = self.clone();
+                    weaker.bricks[index].2 -= 1;
+                    candidates.push(weaker);
+                }
+            }
+            for mut candidate in candidates {
+                if let Err(candidate_failure) = candidate.play() {
+                    candidate.ticks = candidate_failure.tick;
+                    self = candidate;
+                    failure = candidate_failure;
+                    shrunk = true;
+                    break;
+                }
+            }
+        }
+        (self, failure)
+    }
+}
+
+#[derive(Debug)]
+struct Failure {
+    tick: u32,
+    rule: String,
+}
+
+fn lerp(rng: &mut Rng, from: f32, to: f32) -> f32 {
+    from + rng.next_f32() * (to - from)
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[test]
+fn saved_collision_regressions_pass() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(REGRESSIONS_DIR);
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return;
+    };
+    for entry in entries {
+        let path = entry.unwrap().path();
+        if let Err(failure) = Scenario::load(&path).play() {
+            panic!(
+                "{} fails on tick {}: {}",
+                path.display(),
+                failure.tick,
+                failure.rule
+            );
+        }
+    }
+}
+
+#[test]
+fn random_collisions_keep_the_rules() {
+    let run_seed = env_or("COLLISION_FUZZ_SEED", FUZZ_SEED);
+    let cases = env_or("COLLISION_FUZZ_CASES", FUZZ_CASES);
+    let config = GameConfig::default();
+    let mut seeds = Rng::new(run_seed);
+    for _ in 0..cases {
+        let scenario = Scenario::random(seeds.next_u64(), &config);
+        if let Err(failure) = scenario.play() {
+            let (scenario, failure) = scenario.shrink(failure);
+            let kept = if std::env::var_os("COLLISION_FUZZ_SAVE").is_some() {
+                format!("Saved to {}", scenario.save().display())
+            } else {
+                format!(
+                    "Set COLLISION_FUZZ_SAVE to keep it as a regression:\n{}",
+                    scenario.to_ron()
+                )
+            };
+            panic!(
+                "with COLLISION_FUZZ_SEED={run_seed}, {} bricks fail on tick {}: {}. {kept}",
+                scenario.bricks.len(),
+                failure.tick,
+                failure.rule,
+            );
+        }
+    }
+}
@@ -0,0 +1,431 @@
+use bevy::prelude::*;
+
+use super::Harness;
+use crate::achievements::{AchievementRecord, AchievementUnlocked};
+use crate::boss::{
+    new_boss, projectile_bundle, restore_boss, Boss, BossHitEvent, BossPart, BOSS_BONUS,
+};
+use crate::catch::{Caught, MAX_LAUNCH_ANGLE};
+use crate::config::GameConfig;
+use crate::level::{CurrentLevel, LevelData, LevelSet};
+use crate::modes::{GameMode, ModeProgress, TIME_ATTACK_SECONDS};
+use crate::multiball::{extra_ball_bundle, BallAssets, ExtraBall};
+use crate::save::SaveFile;
+use crate::stats::Session;
+use crate::{
+    brick_bundle, Ball, Brick, BrickBrokenEvent, GameState, Level, Lives, Paddle, Scoreboard,
+    Velocity,
+};
+
+#[test]
+fn ball_breaks_brick_and_scores() {
+    let mut harness = Harness::new();
+    harness
+        .start_game()
+        .track::<BrickBrokenEvent>()
+        .clear_bricks();
+    let brick = harness.spawn_brick(Vec2::new(0.0, 100.0), 1);
+    // Another brick out of the ball's way, so the wall isn't cleared and put back up
+    let layout = harness.resource::<GameConfig>().layout.clone();
+    let spare = harness.spawn_brick(Vec2::new(layout.cell_left, layout.cell_top), 1);
+    harness
+        .place_ball(Vec2::new(0.0, 40.0), Vec2::new(0.0, 400.0))
+        .tick(10);
+
+    assert!(!harness.exists(brick));
+    assert!(harness.exists(spare));
+    assert_eq!(harness.resource::<Scoreboard>().score, 1);
+    assert_eq!(harness.sent::<BrickBrokenEvent>(), 1);
+}
+
+#[test]
+fn brick_with_hit_points_left_survives() {
+    let mut harness = Harness::new();
+    harness.start_game().clear_bricks();
+    let brick = harness.spawn_brick(Vec2::new(0.0, 100.0), 2);
+    harness
+        .place_ball(Vec2::new(0.0, 40.0), Vec2::new(0.0, 400.0))
+        .tick(10);
+
+    assert!(harness.exists(brick));
+    assert_eq!(harness.resource::<Scoreboard>().score, 0);
+}
+
+#[test]
+fn losing_the_last_life_ends_the_game() {
+    let mut harness = Harness::new();
+    let bottom_wall = harness.resource::<GameConfig>().layout.bottom_wall;
+    harness
+        .start_game()
+        .set_lives(1)
+        .place_paddle(0, -300.0)
+        .place_ball(Vec2::new(300.0, bottom_wall + 40.0), Vec2::new(0.0, -400.0))
+        .tick(10);
+
+    assert_eq!(harness.resource::<Lives>().lives_left, vec![0]);
+    assert_eq!(harness.state(), GameState::GameOver);
+}
+
+#[test]
+fn losing_a_life_with_more_left_plays_on() {
+    let mut harness = Harness::new();
+    let bottom_wall = harness.resource::<GameConfig>().layout.bottom_wall;
+    harness
+        .start_game()
+        .set_lives(2)
+        .place_paddle(0, -300.0)
+        .place_ball(Vec2::new(300.0, bottom_wall + 40.0), Vec2::new(0.0, -400.0))
+        .tick(10);
+
+    assert_eq!(harness.resource::<Lives>().lives_left, vec![1]);
+    assert_eq!(harness.state(), GameState::InGame);
+}
+
+#[test]
+fn return_pauses_and_resumes() {
+    let mut harness = Harness::new();
+    harness.start_game().press(KeyCode::Return);
+    assert_eq!(harness.state(), GameState::Paused);
+
+    harness.press(KeyCode::Return);
+    assert_eq!(harness.state(), GameState::InGame);
+}
+
+#[test]
+fn continuing_a_save_partway_through_unlocks_nothing() {
+    let mut saved = Harness::new();
+    saved.start_game();
+    saved.app.world.resource_mut::<Level>().index = 2;
+    let save = SaveFile::capture(&mut saved.app.world);
+
+    let mut harness = Harness::new();
+    harness
+        .app
+        .world
+        .insert_resource(AchievementRecord::default());
+    harness.track::<AchievementUnlocked>();
+    save.restore(&mut harness.app.world);
+    harness.tick(10);
+
+    assert_eq!(harness.state(), GameState::InGame);
+    assert_eq!(harness.resource::<Level>().index, 2);
+    assert_eq!(harness.sent::<AchievementUnlocked>(), 0);
+}
+
+// Clears a one-brick wall of a generated board made at `difficulty`, and counts the unlocks
+fn unlocks_clearing_generated_wall(difficulty: u32) -> usize {
+    let mut harness = Harness::new();
+    harness
+        .app
+        .world
+        .insert_resource(AchievementRecord::default());
+    harness
+        .app
+        .world
+        .resource_mut::<CurrentLevel>()
+        .0
+        .difficulty = Some(difficulty);
+    harness
+        .track::<AchievementUnlocked>()
+        .start_game()
+        .clear_bricks();
+    harness.spawn_brick(Vec2::new(0.0, 100.0), 1);
+    harness
+        .place_ball(Vec2::new(0.0, 40.0), Vec2::new(0.0, 400.0))
+        .tick(10);
+
+    assert_eq!(harness.resource::<Level>().index, 1);
+    harness.sent::<AchievementUnlocked>()
+}
+
+#[test]
+fn clearing_a_wall_on_the_hardest_difficulty_unlocks_hard_won() {
+    // Flawless, either way
+    assert_eq!(unlocks_clearing_generated_wall(4), 1);
+    assert_eq!(unlocks_clearing_generated_wall(5), 2);
+}
+
+// Puts the first boss up in place of the first wall
+fn fight_boss(harness: &mut Harness) {
+    harness.start_game().clear_bricks();
+    let core = Vec3::new(0.0, 200.0, 0.0);
+    restore_boss(&mut harness.app.world, new_boss(1), core, Vec2::ZERO);
+}
+
+fn boss(harness: &mut Harness) -> Option<Boss> {
+    let mut boss_query = harness.app.world.query::<&Boss>();
+    boss_query.get_single(&harness.app.world).ok().copied()
+}
+
+fn hit_boss(harness: &mut Harness, damage: u32) {
+    harness.app.world.send_event(BossHitEvent {
+        damage,
+        player: Some(0),
+    });
+    harness.tick(1);
+}
+
+#[test]
+fn boss_comes_out_after_the_last_wall_of_a_set() {
+    let mut harness = Harness::new();
+    harness.start_game().clear_bricks();
+    let layout = harness.resource::<GameConfig>().layout.clone();
+    harness.app.world.insert_resource(LevelSet {
+        levels: vec![LevelData::classic(&layout); 2],
+        ends: false,
+    });
+    harness.app.world.resource_mut::<Level>().index = 1;
+    harness.spawn_brick(Vec2::new(0.0, 100.0), 1);
+    harness
+        .place_ball(Vec2::new(0.0, 40.0), Vec2::new(0.0, 400.0))
+        .tick(10);
+
+    assert_eq!(harness.resource::<Level>().index, 2);
+    assert_eq!(boss(&mut harness).map(|boss| boss.encounter), Some(1));
+}
+
+#[test]
+fn boss_changes_phase_as_it_is_damaged() {
+    let mut harness = Harness::new();
+    fight_boss(&mut harness);
+    let max_health = boss(&mut harness).unwrap().max_health;
+
+    hit_boss(&mut harness, max_health / 2);
+    let boss_after = boss(&mut harness).unwrap();
+    assert_eq!(boss_after.health, max_health - max_health / 2);
+    assert_eq!(boss_after.phase, 1);
+
+    hit_boss(&mut harness, max_health / 4);
+    assert_eq!(boss(&mut harness).unwrap().phase, 2);
+    assert_eq!(harness.resource::<Scoreboard>().score, 0);
+}
+
+#[test]
+fn beating_the_boss_pays_a_bonus_and_starts_the_next_set() {
+    let mut harness = Harness::new();
+    fight_boss(&mut harness);
+    let max_health = boss(&mut harness).unwrap().max_health;
+    hit_boss(&mut harness, max_health);
+    harness.tick(1);
+
+    assert!(boss(&mut harness).is_none());
+    let mut part_query = harness.app.world.query::<&BossPart>();
+    assert_eq!(part_query.iter(&harness.app.world).count(), 0);
+    assert_eq!(harness.resource::<Scoreboard>().score, BOSS_BONUS);
+    assert_eq!(harness.resource::<Level>().index, 1);
+    let mut brick_query = harness.app.world.query_filtered::<(), With<Brick>>();
+    assert!(brick_query.iter(&harness.app.world).count() > 0);
+    assert_eq!(harness.state(), GameState::InGame);
+}
+
+// Fires a boss shot straight into the paddle, and says how wide the paddle is after
+fn shoot_paddle(harness: &mut Harness) -> f32 {
+    let mut paddle_query = harness
+        .app
+        .world
+        .query_filtered::<&Transform, With<Paddle>>();
+    let paddle = *paddle_query.single(&harness.app.world);
+    harness
+        .app
+        .world
+        .spawn(projectile_bundle(paddle.translation, Vec2::ZERO));
+    harness.tick(1);
+    paddle_query.single(&harness.app.world).scale.x
+}
+
+#[test]
+fn boss_shots_shrink_the_paddle_then_cost_a_life() {
+    let mut harness = Harness::new();
+    fight_boss(&mut harness);
+    harness.set_lives(2);
+
+    // Down to the smallest width a shot at a time, with every life kept
+    let mut width = harness.resource::<GameConfig>().paddle_size.x;
+    for _ in 0..3 {
+        let narrower = shoot_paddle(&mut harness);
+        assert!(narrower < width);
+        width = narrower;
+    }
+    assert_eq!(harness.resource::<Lives>().lives_left, vec![2]);
+
+    assert_eq!(shoot_paddle(&mut harness), width);
+    assert_eq!(harness.resource::<Lives>().lives_left, vec![1]);
+}
+
+#[test]
+fn beating_a_boss_after_losing_a_life_to_it_is_not_flawless() {
+    let mut harness = Harness::new();
+    harness
+        .app
+        .world
+        .insert_resource(AchievementRecord::default());
+    harness.track::<AchievementUnlocked>();
+    fight_boss(&mut harness);
+    harness.set_lives(2);
+    for _ in 0..4 {
+        shoot_paddle(&mut harness);
+    }
+    let max_health = boss(&mut harness).unwrap().max_health;
+    hit_boss(&mut harness, max_health);
+    harness.tick(1);
+
+    assert_eq!(harness.resource::<Level>().index, 1);
+    assert_eq!(harness.resource::<Lives>().lives_left, vec![1]);
+    assert_eq!(harness.sent::<AchievementUnlocked>(), 0);
+}
+
+#[test]
+fn lives_lost_to_boss_shots_are_in_the_statistics() {
+    let mut harness = Harness::new();
+    fight_boss(&mut harness);
+    harness.set_lives(2);
+    for _ in 0..4 {
+        shoot_paddle(&mut harness);
+    }
+
+    let session = harness.resource::<Session>();
+    assert_eq!(session.lives_lost_at.len(), 1);
+    assert_eq!(session.rallies.len(), 1);
+}
+
+#[test]
+fn time_attack_ends_when_the_clock_runs_out() {
+    let mut harness = Harness::new();
+    harness.app.world.insert_resource(GameMode::TimeAttack);
+    harness.start_game();
+    // Two ticks left on the clock
+    harness.app.world.resource_mut::<ModeProgress>().elapsed = TIME_ATTACK_SECONDS - 2.5 / 60.0;
+
+    harness.tick(1);
+    assert_eq!(harness.state(), GameState::InGame);
+    harness.tick(3);
+    assert_eq!(harness.state(), GameState::GameOver);
+}
+
+// Where a brick sits with its bottom edge just above the paddles' top edge
+fn just_above_paddle_line(harness: &Harness) -> Vec2 {
+    let config = harness.resource::<GameConfig>();
+    let layout = &config.layout;
+    let paddle_line = layout.paddle_y() + config.paddle_size.y / 2.0;
+    Vec2::new(
+        layout.cell_left,
+        paddle_line + layout.cell_size.y / 2.0 + 0.05,
+    )
+}
+
+#[test]
+fn endless_wall_reaching_the_paddles_ends_the_game() {
+    let mut harness = Harness::new();
+    harness.app.world.insert_resource(GameMode::Endless);
+    harness.start_game().clear_bricks();
+    let position = just_above_paddle_line(&harness);
+    harness.spawn_brick(position, 1);
+
+    harness.tick(3);
+    assert_eq!(harness.state(), GameState::GameOver);
+}
+
+#[test]
+fn unbreakable_bricks_crumble_at_the_paddle_line_in_endless() {
+    let mut harness = Harness::new();
+    harness.app.world.insert_resource(GameMode::Endless);
+    harness.start_game().clear_bricks();
+    let layout = harness.resource::<GameConfig>().layout.clone();
+    // A breakable brick high up, so the wall isn't cleared
+    harness.spawn_brick(Vec2::new(layout.cell_left, layout.cell_top), 1);
+    let position = just_above_paddle_line(&harness);
+    let unbreakable = harness
+        .app
+        .world
+        .spawn(brick_bundle(position.extend(0.0), Color::WHITE, &layout))
+        .id();
+
+    harness.tick(3);
+    assert!(!harness.exists(unbreakable));
+    assert_eq!(harness.state(), GameState::InGame);
+}
+
+const CATCH_SPEED: f32 = 400.0;
+
+// Holds a ball on player 1's paddle, `offset` from its middle, for `hold` more seconds
+fn catch_ball(harness: &mut Harness, ball: Entity, offset: f32, hold: f32) {
+    harness.app.world.entity_mut(ball).insert((
+        Caught {
+            player: 0,
+            offset,
+            speed: CATCH_SPEED,
+            time_left: hold,
+        },
+        Velocity(Vec2::ZERO),
+    ));
+}
+
+fn first_ball(harness: &mut Harness) -> Entity {
+    let mut ball_query = harness
+        .app
+        .world
+        .query_filtered::<Entity, (With<Ball>, Without<ExtraBall>)>();
+    ball_query.single(&harness.app.world)
+}
+
+fn ball_state(harness: &Harness, ball: Entity) -> (Vec3, Vec2, bool) {
+    let ball = harness.app.world.entity(ball);
+    (
+        ball.get::<Transform>().unwrap().translation,
+        ball.get::<Velocity>().unwrap().0,
+        ball.contains::<Caught>(),
+    )
+}
+
+#[test]
+fn caught_ball_rides_the_paddle_and_launches_by_its_offset() {
+    let mut harness = Harness::new();
+    harness.start_game().clear_bricks();
+    let width = harness.resource::<GameConfig>().paddle_size.x;
+    let offset = width / 4.0;
+    let ball = first_ball(&mut harness);
+    // Launches on the second tick
+    catch_ball(&mut harness, ball, offset, 1.5 / 60.0);
+
+    harness.place_paddle(0, 100.0).tick(1);
+    let (translation, velocity, caught) = ball_state(&harness, ball);
+    assert!(caught);
+    assert_eq!(translation.x, 100.0 + offset);
+    assert_eq!(velocity, Vec2::ZERO);
+
+    harness.tick(1);
+    let (_, velocity, caught) = ball_state(&harness, ball);
+    assert!(!caught);
+    let angle = 0.5 * MAX_LAUNCH_ANGLE;
+    let expected = Vec2::new(angle.sin(), angle.cos()) * CATCH_SPEED;
+    assert!(
+        velocity.distance(expected) < 0.001,
+        "launched at {velocity}, not {expected}"
+    );
+}
+
+#[test]
+fn balls_caught_on_one_paddle_launch_together() {
+    let mut harness = Harness::new();
+    harness.start_game().clear_bricks();
+    let width = harness.resource::<GameConfig>().paddle_size.x;
+    let assets = harness.resource::<BallAssets>().clone();
+    let first = first_ball(&mut harness);
+    let extra = harness
+        .app
+        .world
+        .spawn(extra_ball_bundle(&assets, Vec3::ZERO, Vec2::ZERO, None))
+        .id();
+    // Only the first ball's hold runs out, but both go
+    catch_ball(&mut harness, first, -width / 4.0, 0.5 / 60.0);
+    catch_ball(&mut harness, extra, width / 4.0, 10.0);
+
+    harness.tick(1);
+    let (_, first_velocity, first_caught) = ball_state(&harness, first);
+    let (_, extra_velocity, extra_caught) = ball_state(&harness, extra);
+    assert!(!first_caught && !extra_caught);
+    assert!(first_velocity.y > 0.0 && extra_velocity.y > 0.0);
+    // Mirrored offsets leave at mirrored angles
+    assert!(first_velocity.x < 0.0);
+    assert!((first_velocity.x + extra_velocity.x).abs() < 0.001);
+}
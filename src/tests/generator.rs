@@ -0,0 +1,104 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::config::BoardLayout;
+use crate::generator::{generate, GeneratorParams, Symmetry};
+use crate::level::LevelData;
+use crate::BrickKind;
+
+const SEEDS: u64 = 500;
+
+// Every breakable brick the ball can get to, coming up from under the grid through empty cells
+// and other breakable bricks
+fn unreachable_bricks(level: &LevelData, columns: u32) -> Vec<(u32, u32)> {
+    let floor = level
+        .bricks
+        .iter()
+        .map(|brick| brick.row + 1)
+        .max()
+        .unwrap_or(0);
+    let walls: HashSet<(u32, u32)> = level
+        .bricks
+        .iter()
+        .filter(|brick| brick.kind == BrickKind::Unbreakable)
+        .map(|brick| (brick.column, brick.row))
+        .collect();
+    let mut reached = HashSet::new();
+    let mut queue: VecDeque<(u32, u32)> = (0..columns).map(|column| (column, floor)).collect();
+    while let Some((column, row)) = queue.pop_front() {
+        if walls.contains(&(column, row)) || !reached.insert((column, row)) {
+            continue;
+        }
+        if column > 0 {
+            queue.push_back((column - 1, row));
+        }
+        if column + 1 < columns {
+            queue.push_back((column + 1, row));
+        }
+        if row > 0 {
+            queue.push_back((column, row - 1));
+        }
+        if row < floor {
+            queue.push_back((column, row + 1));
+        }
+    }
+    level
+        .bricks
+        .iter()
+        .filter(|brick| brick.kind == BrickKind::Normal)
+        .map(|brick| (brick.column, brick.row))
+        .filter(|cell| !reached.contains(cell))
+        .collect()
+}
+
+fn check_completable(seed: u64, params: &GeneratorParams) {
+    let level = generate(seed, params);
+    let problems = level.validate();
+    assert!(problems.is_empty(), "seed {seed}, {params:?}: {problems:?}");
+    let stranded = unreachable_bricks(&level, params.columns);
+    assert!(
+        stranded.is_empty(),
+        "seed {seed}, {params:?}: bricks walled off at {stranded:?}"
+    );
+    assert!(
+        level
+            .bricks
+            .iter()
+            .all(|brick| brick.column < params.columns),
+        "seed {seed}, {params:?}: bricks off the side of the grid"
+    );
+    if let Err(problem) = BoardLayout::default().check_level(&level) {
+        panic!("seed {seed}, {params:?}: {problem}");
+    }
+}
+
+#[test]
+fn boards_from_any_seed_can_be_cleared() {
+    for seed in 0..SEEDS {
+        check_completable(seed, &GeneratorParams::for_seed(seed));
+    }
+}
+
+// Lots of unbreakable bricks are the likeliest to wall the others off
+#[test]
+fn boards_full_of_unbreakable_bricks_can_be_cleared() {
+    for seed in 0..SEEDS {
+        for symmetry in [Symmetry::None, Symmetry::Mirror, Symmetry::Radial] {
+            let params = GeneratorParams {
+                density: 1.0,
+                symmetry,
+                unbreakable_share: 0.6,
+                difficulty: 1 + (seed % 5) as u32,
+                ..GeneratorParams::default()
+            };
+            check_completable(seed, &params);
+        }
+    }
+}
+
+#[test]
+fn a_seed_always_makes_the_same_board() {
+    for seed in 0..SEEDS / 10 {
+        let params = GeneratorParams::for_seed(seed);
+        assert!(generate(seed, &params) == generate(seed, &params));
+    }
+}
@@ -0,0 +1,189 @@
+use std::marker::PhantomData;
+use std::sync::OnceLock;
+
+use bevy::{
+    input::{keyboard::KeyboardInput, ButtonState},
+    log::LogPlugin,
+    prelude::*,
+};
+
+use crate::cli::Options;
+use crate::config::GameConfig;
+use crate::multiball::ExtraBall;
+use crate::settings::{EffectLevel, Settings};
+use crate::{
+    add_game, brick_bundle, Ball, Brick, GameState, HitPoints, Lives, Paddle, Player, Velocity,
+};
+
+mod cli;
+mod collisions;
+mod gameplay;
+mod generator;
+mod save;
+mod snapshot;
+
+// Where the tests run, so the high scores, settings and saves they write stay out of the repo.
+// Assets are still found through the crate's own directory.
+const TEST_DIR: &str = "rust-breakout-tests";
+
+// A whole game with no window, stepped by hand
+pub struct Harness {
+    pub app: App,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        static CHDIR: OnceLock<()> = OnceLock::new();
+        CHDIR.get_or_init(|| {
+            let dir = std::env::temp_dir().join(TEST_DIR);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::env::set_current_dir(&dir).unwrap();
+        });
+
+        let headless = Options {
+            headless: true,
+            ..default()
+        };
+        let mut app = App::new();
+        // Logging can only be set up once in a process, and tests share one
+        app.add_plugins(headless.configure_plugins(DefaultPlugins.build().disable::<LogPlugin>()));
+        add_game(&mut app);
+        // Not a headless run as far as the game knows, so nothing starts or ends by itself
+        app.insert_resource(Options::default());
+        app.update();
+        // Hit stop holds the simulation still for a few frames after a hit, so a tick would no
+        // longer be a frame
+        app.world.resource_mut::<Settings>().hit_stop = EffectLevel::Off;
+        // A broken brick would otherwise stay a few frames to flash
+        app.world.resource_mut::<Settings>().brick_flash = EffectLevel::Off;
+        Harness { app }
+    }
+
+    // Starts a game from the start screen the way a player does
+    pub fn start_game(&mut self) -> &mut Self {
+        assert_eq!(self.state(), GameState::NewGame);
+        self.press(KeyCode::Return);
+        assert_eq!(self.state(), GameState::InGame);
+        self
+    }
+
+    // Steps the game. Local play runs one fixed tick a frame while in game.
+    pub fn tick(&mut self, ticks: usize) -> &mut Self {
+        for _ in 0..ticks {
+            self.app.update();
+        }
+        self
+    }
+
+    // Presses and lets go of a key, then runs one more frame so any state it asked for is entered
+    pub fn press(&mut self, key: KeyCode) -> &mut Self {
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            self.app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state,
+                window: Entity::PLACEHOLDER,
+            });
+            self.app.update();
+        }
+        self.app.update();
+        self
+    }
+
+    pub fn state(&self) -> GameState {
+        *self.app.world.resource::<State<GameState>>().get()
+    }
+
+    pub fn resource<R: Resource>(&self) -> &R {
+        self.app.world.resource::<R>()
+    }
+
+    // Takes every brick off the board
+    pub fn clear_bricks(&mut self) -> &mut Self {
+        let mut brick_query = self.app.world.query_filtered::<Entity, With<Brick>>();
+        let bricks: Vec<Entity> = brick_query.iter(&self.app.world).collect();
+        for brick in bricks {
+            self.app.world.despawn(brick);
+        }
+        self
+    }
+
+    // Puts a breakable brick centered on a point, off the grid if need be
+    pub fn spawn_brick(&mut self, position: Vec2, hit_points: u32) -> Entity {
+        let layout = self.resource::<GameConfig>().layout.clone();
+        self.app
+            .world
+            .spawn((
+                brick_bundle(position.extend(0.0), Color::WHITE, &layout),
+                HitPoints(hit_points),
+            ))
+            .id()
+    }
+
+    pub fn place_ball(&mut self, position: Vec2, velocity: Vec2) -> &mut Self {
+        let mut ball_query = self
+            .app
+            .world
+            .query_filtered::<(&mut Transform, &mut Velocity), (With<Ball>, Without<ExtraBall>)>();
+        let (mut transform, mut ball_velocity) = ball_query.single_mut(&mut self.app.world);
+        transform.translation = position.extend(transform.translation.z);
+        ball_velocity.0 = velocity;
+        self
+    }
+
+    // Moves a player's paddle along the floor, ignoring its bounds
+    pub fn place_paddle(&mut self, player: usize, x: f32) -> &mut Self {
+        let mut paddle_query = self
+            .app
+            .world
+            .query_filtered::<(&Player, &mut Transform), With<Paddle>>();
+        for (paddle_player, mut transform) in paddle_query.iter_mut(&mut self.app.world) {
+            if paddle_player.0 == player {
+                transform.translation.x = x;
+            }
+        }
+        self
+    }
+
+    pub fn set_lives(&mut self, lives: usize) -> &mut Self {
+        for lives_left in &mut self.app.world.resource_mut::<Lives>().lives_left {
+            *lives_left = lives;
+        }
+        self
+    }
+
+    // Counts every event of a kind sent from now on, read with `sent`
+    pub fn track<E: Event>(&mut self) -> &mut Self {
+        self.app
+            .init_resource::<Sent<E>>()
+            .add_systems(Last, count_sent::<E>);
+        self
+    }
+
+    pub fn sent<E: Event>(&self) -> usize {
+        self.app.world.resource::<Sent<E>>().count
+    }
+
+    pub fn exists(&self, entity: Entity) -> bool {
+        self.app.world.get_entity(entity).is_some()
+    }
+}
+
+#[derive(Resource)]
+struct Sent<E: Event> {
+    count: usize,
+    kind: PhantomData<E>,
+}
+
+impl<E: Event> Default for Sent<E> {
+    fn default() -> Self {
+        Sent {
+            count: 0,
+            kind: PhantomData,
+        }
+    }
+}
+
+fn count_sent<E: Event>(mut events: EventReader<E>, mut sent: ResMut<Sent<E>>) {
+    sent.count += events.iter().count();
+}
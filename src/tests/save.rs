@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+
+use super::Harness;
+use crate::level::{CurrentLevel, LevelData};
+use crate::save::migrate;
+use crate::{Brick, GameState, HitPoints, Level, Lives, Paddle, Scoreboard};
+
+// Written by the first version of saves, before brick kinds, levels, modes, bosses, power-ups
+// and the catch option
+const SAVE_V1: &str = include_str!("fixtures/savegame-v1.ron");
+
+#[test]
+fn a_version_1_save_continues() {
+    let save = migrate(1, SAVE_V1).unwrap();
+    let mut harness = Harness::new();
+    save.restore(&mut harness.app.world);
+    harness.tick(1);
+
+    assert_eq!(harness.state(), GameState::InGame);
+    assert_eq!(harness.resource::<Scoreboard>().score, 7);
+    assert_eq!(harness.resource::<Lives>().lives_left, vec![2]);
+    assert_eq!(harness.resource::<Level>().index, 1);
+    // Its bricks all took a single hit, and the wall it came back to was the classic one
+    let mut brick_query = harness
+        .app
+        .world
+        .query_filtered::<&HitPoints, With<Brick>>();
+    let hit_points: Vec<u32> = brick_query
+        .iter(&harness.app.world)
+        .map(|hit_points| hit_points.0)
+        .collect();
+    assert_eq!(hit_points, vec![1, 1, 1]);
+    assert!(harness.resource::<CurrentLevel>().0 == LevelData::default());
+    let mut paddle_query = harness
+        .app
+        .world
+        .query_filtered::<&Transform, With<Paddle>>();
+    let paddle = paddle_query.single(&harness.app.world);
+    assert_eq!(paddle.translation.x, -120.0);
+}
+
+#[test]
+fn a_save_from_a_newer_version_is_turned_down() {
+    assert!(migrate(u32::MAX, SAVE_V1).is_err());
+}
@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+
+use super::Harness;
+use crate::snapshot::GameSnapshot;
+
+// A rollback puts the game back to a snapshot and plays the same ticks again, which has to come
+// out where the first run did
+#[test]
+fn restoring_a_snapshot_and_replaying_comes_out_the_same() {
+    let mut harness = Harness::new();
+    harness
+        .start_game()
+        .place_ball(Vec2::new(-100.0, -150.0), Vec2::new(250.0, 400.0))
+        .tick(5);
+    let start = GameSnapshot::capture(&mut harness.app.world);
+    harness.tick(60);
+    let first_run = GameSnapshot::capture(&mut harness.app.world).checksum();
+    assert_ne!(first_run, start.checksum());
+
+    start.restore(&mut harness.app.world);
+    assert_eq!(
+        GameSnapshot::capture(&mut harness.app.world).checksum(),
+        start.checksum()
+    );
+    harness.tick(60);
+    assert_eq!(
+        GameSnapshot::capture(&mut harness.app.world).checksum(),
+        first_run
+    );
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension},
+        texture::DEFAULT_IMAGE_HANDLE,
+    },
+};
+use serde::Deserialize;
+
+use crate::boss::BossPart;
+use crate::config::GameConfig;
+use crate::multiball::BallAssets;
+use crate::settings::Settings;
+use crate::{Brick, BrickKind, HitPoints, Paddle, Wall};
+
+// Themes
+const THEMES_DIR: &str = "assets/themes";
+const THEME_MANIFEST: &str = "theme.ron";
+// The plain colored look, which needs no files and is used whenever a theme can't be loaded
+pub const FLAT_THEME: &str = "flat";
+// Every atlas pixel is four bytes
+const PIXEL_SIZE: usize = 4;
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        // After the frame's despawns have gone through, so nothing is skinned on its way out
+        app.init_resource::<Theme>().add_systems(
+            PostUpdate,
+            (load_theme, paint_background, skin_sprites, skin_balls).chain(),
+        );
+    }
+}
+
+// What a theme's `theme.ron` says to draw each thing with
+#[derive(Deserialize)]
+struct ThemeManifest {
+    // The image the sliced sprites are cut from, relative to the theme's folder
+    atlas: String,
+    // A whole image for the ball, which is round and can't be sliced
+    #[serde(default)]
+    ball: Option<String>,
+    #[serde(default)]
+    background: Option<Color>,
+    // Brick kinds left out keep their flat look
+    #[serde(default)]
+    bricks: HashMap<BrickKind, NineSlice>,
+    #[serde(default)]
+    paddle: Option<NineSlice>,
+    #[serde(default)]
+    wall: Option<NineSlice>,
+}
+
+// A region of the atlas drawn at any size by stretching its middle, keeping its borders as they
+// are so corners and edges don't smear
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+struct NineSlice {
+    // x, y, width and height in atlas pixels
+    rect: (u32, u32, u32, u32),
+    border: u32,
+}
+
+#[derive(Resource)]
+pub struct Theme {
+    name: String,
+    manifest: Option<ThemeManifest>,
+    atlas: Handle<Image>,
+    ball: Option<Handle<Image>>,
+    // Sliced images already drawn, by slice and size in pixels
+    baked: HashMap<(NineSlice, UVec2), Handle<Image>>,
+    // Bumped on every switch, so sprites can tell they are out of date
+    generation: u32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: FLAT_THEME.to_string(),
+            manifest: None,
+            atlas: Handle::default(),
+            ball: None,
+            baked: HashMap::new(),
+            generation: 0,
+        }
+    }
+}
+
+// The look a sprite was last given
+#[derive(Component)]
+struct Skinned {
+    generation: u32,
+    size: UVec2,
+}
+
+// The flat theme and every folder under `assets/themes` that has a manifest
+pub fn available_themes() -> Vec<String> {
+    let mut themes: Vec<String> = fs::read_dir(THEMES_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().join(THEME_MANIFEST).exists())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    themes.sort();
+    themes.insert(0, FLAT_THEME.to_string());
+    themes
+}
+
+fn read_manifest(name: &str) -> Result<ThemeManifest, String> {
+    let path = Path::new(THEMES_DIR).join(name).join(THEME_MANIFEST);
+    let contents = fs::read_to_string(&path).map_err(|error| error.to_string())?;
+    ron::from_str(&contents).map_err(|error| error.to_string())
+}
+
+// Switches to the theme picked in the settings
+fn load_theme(settings: Res<Settings>, asset_server: Res<AssetServer>, mut theme: ResMut<Theme>) {
+    if settings.theme == theme.name {
+        return;
+    }
+    let manifest = if settings.theme == FLAT_THEME {
+        None
+    } else {
+        match read_manifest(&settings.theme) {
+            Ok(manifest) => Some(manifest),
+            Err(error) => {
+                error!("Could not load the {} theme: {error}", settings.theme);
+                None
+            }
+        }
+    };
+
+    let folder = format!("themes/{}", settings.theme);
+    let generation = theme.generation + 1;
+    *theme = match manifest {
+        Some(manifest) => Theme {
+            name: settings.theme.clone(),
+            atlas: asset_server.load(format!("{folder}/{}", manifest.atlas)),
+            ball: manifest
+                .ball
+                .as_ref()
+                .map(|ball| asset_server.load(format!("{folder}/{ball}"))),
+            manifest: Some(manifest),
+            baked: HashMap::new(),
+            generation,
+        },
+        // A broken theme is remembered by name too, so it isn't tried again every frame
+        None => Theme {
+            name: settings.theme.clone(),
+            generation,
+            ..default()
+        },
+    };
+}
+
+// The theme's background, or the config's when it has none
+fn paint_background(
+    theme: Res<Theme>,
+    config: Res<GameConfig>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    let color = theme
+        .manifest
+        .as_ref()
+        .and_then(|manifest| manifest.background)
+        .unwrap_or(config.background_color);
+    // Only set when it differs, so the clear color isn't marked changed every frame
+    if clear_color.0 != color {
+        clear_color.0 = color;
+    }
+}
+
+type SkinQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static mut Sprite,
+        &'static mut Handle<Image>,
+        Option<&'static Skinned>,
+        Option<&'static HitPoints>,
+        Option<&'static Brick>,
+        Option<&'static Paddle>,
+    ),
+    (
+        Or<(With<Brick>, With<Paddle>, With<Wall>)>,
+        Without<BossPart>,
+    ),
+>;
+
+// Gives bricks, paddles and walls their theme's art, sliced to their size. Sprites keep their
+// color as a tint, so bricks still show the colors their level gave them.
+fn skin_sprites(
+    mut commands: Commands,
+    mut theme: ResMut<Theme>,
+    mut images: ResMut<Assets<Image>>,
+    mut query: SkinQuery,
+) {
+    let theme = &mut *theme;
+    let atlas = theme
+        .manifest
+        .as_ref()
+        .and_then(|_| images.get(&theme.atlas))
+        .cloned();
+    for (entity, transform, mut sprite, mut texture, skinned, hit_points, brick, paddle) in
+        &mut query
+    {
+        let size = transform.scale.truncate().round().as_uvec2();
+        if skinned
+            .is_some_and(|skinned| skinned.generation == theme.generation && skinned.size == size)
+        {
+            continue;
+        }
+        let slice = theme.manifest.as_ref().and_then(|manifest| {
+            if brick.is_some() {
+                let kind = match hit_points {
+                    Some(_) => BrickKind::Normal,
+                    None => BrickKind::Unbreakable,
+                };
+                manifest.bricks.get(&kind).copied()
+            } else if paddle.is_some() {
+                manifest.paddle
+            } else {
+                manifest.wall
+            }
+        });
+        match (slice, &atlas) {
+            (Some(slice), Some(atlas)) => {
+                let baked = theme
+                    .baked
+                    .entry((slice, size))
+                    .or_insert_with(|| images.add(bake_nine_slice(atlas, slice, size)));
+                *texture = baked.clone();
+            }
+            // Still loading, so try again next frame
+            (Some(_), None) => continue,
+            (None, _) => *texture = DEFAULT_IMAGE_HANDLE.typed(),
+        }
+        // The transform's scale is the sprite's size, so the image is drawn one unit across
+        sprite.custom_size = Some(Vec2::ONE);
+        commands.entity(entity).insert(Skinned {
+            generation: theme.generation,
+            size,
+        });
+    }
+}
+
+// Every ball shares one material, so changing it changes them all
+fn skin_balls(
+    theme: Res<Theme>,
+    config: Res<GameConfig>,
+    ball_assets: Option<Res<BallAssets>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut applied: Local<u32>,
+) {
+    let Some(ball_assets) = ball_assets else {
+        return;
+    };
+    if *applied == theme.generation && !config.is_changed() {
+        return;
+    }
+    *applied = theme.generation;
+    if let Some(material) = materials.get_mut(&ball_assets.material) {
+        material.texture = theme.ball.clone();
+        material.color = match theme.ball {
+            Some(_) => Color::WHITE,
+            None => config.ball_color,
+        };
+    }
+}
+
+// Draws a slice of the atlas at `size` pixels: corners as they are, edges stretched along their
+// length and the middle stretched both ways
+fn bake_nine_slice(atlas: &Image, slice: NineSlice, size: UVec2) -> Image {
+    let atlas_width = atlas.texture_descriptor.size.width as usize;
+    let (left, top, width, height) = slice.rect;
+    let size = size.max(UVec2::ONE);
+    let source_x = |x: u32| left + stretch(x, size.x, width, slice.border);
+    let source_y = |y: u32| top + stretch(y, size.y, height, slice.border);
+
+    let mut data = Vec::with_capacity((size.x * size.y) as usize * PIXEL_SIZE);
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let index = (source_y(y) as usize * atlas_width + source_x(x) as usize) * PIXEL_SIZE;
+            let pixel = atlas
+                .data
+                .get(index..index + PIXEL_SIZE)
+                .unwrap_or(&[255; PIXEL_SIZE]);
+            data.extend_from_slice(pixel);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        atlas.texture_descriptor.format,
+    )
+}
+
+// Where along a slice's side a pixel of the drawn image comes from
+fn stretch(at: u32, drawn: u32, source: u32, border: u32) -> u32 {
+    // Borders too big for either side are cut down to fit
+    let border = border.min(drawn / 2).min(source / 2);
+    if at < border {
+        at
+    } else if at >= drawn - border {
+        source - (drawn - at)
+    } else {
+        let middle = (drawn - 2 * border).max(1);
+        let source_middle = source - 2 * border;
+        border + (at - border) * source_middle / middle
+    }
+}